@@ -1,3 +1,8 @@
+use abootcrafter::headers::avb::AvbAlgorithm;
+use abootcrafter::headers::dts::ValueType;
+use abootcrafter::headers::id::IdHash;
+use abootcrafter::headers::mtk::MtkImageKind;
+use abootcrafter::headers::trailer::TrailerKind;
 use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
@@ -9,6 +14,7 @@ pub struct Cli {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum MainCommand {
     /// Display information about a boot image
     #[command(alias = "i")]
@@ -36,29 +42,54 @@ pub enum MainCommand {
         #[command(subcommand)]
         command: CreateCommand,
     },
-    // /// Ramdisk manipulation commands
-    // Ramdisk {
-    //     #[command(subcommand)]
-    //     command: RamdiskCommand,
-    // },
 
-    // /// Device tree manipulation commands
-    // Devicetree {
-    //     #[command(subcommand)]
-    //     command: DevicetreeCommand,
-    // },
+    /// Rebuild a boot image from an extracted directory
+    Repack {
+        #[command(subcommand)]
+        command: RepackCommand,
+    },
 
-    // /// Signature manipulation commands
-    // Signature {
-    //     #[command(subcommand)]
-    //     command: SignatureCommand,
-    // },
+    /// Vendor ramdisk fragment commands (vendor boot v4)
+    VendorRamdisk {
+        #[command(subcommand)]
+        command: VendorRamdiskCommand,
+    },
 
-    // /// Kernel manipulation commands
-    // Kernel {
+    /// Bootconfig commands (vendor boot v4)
+    Bootconfig {
+        #[command(subcommand)]
+        command: BootconfigCommand,
+    },
+    /// QCDT and DTBH device tree table commands
+    Qcdt {
+        #[command(subcommand)]
+        command: QcdtCommand,
+    },
+    /// DTBO image commands (dtbo and recovery_dtbo partitions)
+    Dtbo {
+        #[command(subcommand)]
+        command: DtboCommand,
+    },
+    // /// Ramdisk manipulation commands
+    // Ramdisk {
     //     #[command(subcommand)]
-    //     command: KernelCommand,
+    //     command: RamdiskCommand,
     // },
+    /// Device tree manipulation commands
+    Devicetree {
+        #[command(subcommand)]
+        command: DevicetreeCommand,
+    },
+    /// Signature manipulation commands
+    Signature {
+        #[command(subcommand)]
+        command: SignatureCommand,
+    },
+    /// Kernel manipulation commands
+    Kernel {
+        #[command(subcommand)]
+        command: KernelCommand,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -69,6 +100,13 @@ pub enum InfoCommand {
         #[arg(short, long, value_parser = file_exists_value_parser)]
         input_boot_file: PathBuf,
     },
+
+    /// Display information about a vendor boot image
+    VendorBootimg {
+        /// Vendor boot image file to display information about
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -82,6 +120,39 @@ pub enum ExtractCommand {
         /// Directory to extract components to
         #[arg(short, long)]
         output_dir: Option<PathBuf>,
+
+        /// Write MediaTek component headers to separate files
+        #[arg(long)]
+        strip_mtk_headers: bool,
+    },
+
+    /// Extract components from a vendor boot image
+    VendorBootimg {
+        /// Vendor boot image file to extract components from
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Directory to extract components to
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RepackCommand {
+    /// Rebuild a boot image from the directory written by `extract bootimg`
+    Bootimg {
+        /// Directory holding the extracted components
+        #[arg(short, long)]
+        input_dir: PathBuf,
+
+        /// Output boot image file
+        #[arg(short, long)]
+        output_boot_file: PathBuf,
+
+        /// Metadata file holding the header fields (defaults to bootimg.cfg in the input directory)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        metadata_file: Option<PathBuf>,
     },
 }
 
@@ -109,17 +180,25 @@ pub enum UpdateCommand {
         #[arg(long, value_parser = file_exists_value_parser)]
         recovery_dtbo_file: Option<PathBuf>,
 
-        /// DTB file to use for updating (v2 only)
+        /// DTB file to use for updating (v2 and the QCOM v0 variant)
         #[arg(long, value_parser = file_exists_value_parser)]
         dtb_file: Option<PathBuf>,
 
-        /// Kernel command line of the boot image
-        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        /// Kernel command line of the boot image (kept if omitted)
+        #[arg(long, value_parser = ascii_string_value_parser)]
         cmdline: Option<String>,
 
-        /// Extra kernel command line of the boot image
-        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        /// Extra kernel command line of the boot image (kept if omitted)
+        #[arg(long, value_parser = ascii_string_value_parser)]
         extra_cmdline: Option<String>,
+
+        /// Drop the trailers found after the last section
+        #[arg(long)]
+        strip_trailers: bool,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
     },
 }
 
@@ -143,6 +222,11 @@ pub enum CreateCommand {
         #[arg(short, long, value_parser = file_exists_value_parser)]
         second_file: Option<PathBuf>,
 
+        /// Device tree table for the QCOM variant of the v0 header, which
+        /// stores its size in place of header_version
+        #[arg(long, value_parser = file_exists_value_parser)]
+        dt_file: Option<PathBuf>,
+
         /// Page size to use for creating the boot image
         #[arg(long, default_value = "2048")]
         page_size: AndroidBootPageSizes,
@@ -175,13 +259,25 @@ pub enum CreateCommand {
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         cmdline: String,
 
-        /// timestamp / checksum / sha1 / etc
-        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
-        id: String,
+        /// timestamp / checksum / sha1 / etc (computed from the components if omitted)
+        #[arg(long, value_parser = ascii_string_value_parser)]
+        id: Option<String>,
+
+        /// Digest used to compute the id
+        #[arg(long, default_value = "sha1")]
+        id_hash: IdHashTypes,
 
         /// Extra kernel command line of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         extra_cmdline: String,
+
+        /// Wrap the kernel and ramdisk in MediaTek headers, named for a boot or recovery image
+        #[arg(long, num_args = 0..=1, default_missing_value = "boot")]
+        mtk_header: Option<MtkHeaderTypes>,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
     },
 
     /// Create a new boot image version 1 (== Android 9)
@@ -238,23 +334,31 @@ pub enum CreateCommand {
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         cmdline: String,
 
-        /// timestamp / checksum / sha1 / etc
-        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
-        id: String,
+        /// timestamp / checksum / sha1 / etc (computed from the components if omitted)
+        #[arg(long, value_parser = ascii_string_value_parser)]
+        id: Option<String>,
+
+        /// Digest used to compute the id
+        #[arg(long, default_value = "sha1")]
+        id_hash: IdHashTypes,
 
         /// Extra kernel command line of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         extra_cmdline: String,
 
-        /// Offset of the recovery DTBO partition
-        #[arg(long, default_value = "0x0000000000000000", value_parser = address64_value_parser)]
-        recovery_dtbo_offset: String,
+        /// Wrap the kernel and ramdisk in MediaTek headers, named for a boot or recovery image
+        #[arg(long, num_args = 0..=1, default_missing_value = "boot")]
+        mtk_header: Option<MtkHeaderTypes>,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
     },
 
     /// Create a new boot image version 2 (== Android 10)
     BootimgV2 {
         /// Output boot image file
-        #[arg(short, long, required = true, value_parser = file_exists_value_parser)]
+        #[arg(short, long, required = true)]
         output_boot_file: PathBuf,
 
         /// Kernel file to use for creating the boot image
@@ -307,21 +411,29 @@ pub enum CreateCommand {
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         cmdline: String,
 
-        /// timestamp / checksum / sha1 / etc
-        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
-        id: String,
+        /// timestamp / checksum / sha1 / etc (computed from the components if omitted)
+        #[arg(long, value_parser = ascii_string_value_parser)]
+        id: Option<String>,
+
+        /// Digest used to compute the id
+        #[arg(long, default_value = "sha1")]
+        id_hash: IdHashTypes,
 
         /// Extra kernel command line of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         extra_cmdline: String,
 
-        /// Offset of the recovery DTBO partition
-        #[arg(long, default_value = "0x0000000000000000", value_parser = address64_value_parser)]
-        recovery_dtbo_offset: String,
-
         /// Physical load address of the device tree
         #[arg(long, default_value = "0x0000000000000000", value_parser = address64_value_parser)]
         dtb_addr: String,
+
+        /// Wrap the kernel and ramdisk in MediaTek headers, named for a boot or recovery image
+        #[arg(long, num_args = 0..=1, default_missing_value = "boot")]
+        mtk_header: Option<MtkHeaderTypes>,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
     },
 
     /// Create a new boot image version 3 (>= Android 11)
@@ -345,6 +457,10 @@ pub enum CreateCommand {
         /// Kernel command line of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         cmdline: String,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
     },
 
     /// Create a new boot image version 4 (>= Android 12)
@@ -361,6 +477,10 @@ pub enum CreateCommand {
         #[arg(short, long, required = true, value_parser = file_exists_value_parser)]
         ramdisk_file: PathBuf,
 
+        /// Boot signature file to store in the image
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        signature_file: Option<PathBuf>,
+
         /// Android OS Version of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         os_version: String,
@@ -368,6 +488,274 @@ pub enum CreateCommand {
         /// Kernel command line of the boot image
         #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
         cmdline: String,
+
+        /// Trailer to append after the last section (can be repeated)
+        #[arg(long = "append-trailer")]
+        append_trailers: Vec<TrailerTypes>,
+    },
+
+    /// Create a new vendor boot image version 3 (>= Android 11)
+    VendorBootimgV3 {
+        /// Output vendor boot image file
+        #[arg(short, long, required = true)]
+        output_vendor_boot_file: PathBuf,
+
+        /// Vendor ramdisk file to use for creating the vendor boot image
+        #[arg(short = 'r', long, required = true, value_parser = file_exists_value_parser)]
+        vendor_ramdisk_file: PathBuf,
+
+        /// Device tree file to use for creating the vendor boot image
+        #[arg(short = 'd', long, value_parser = file_exists_value_parser)]
+        dtb_file: Option<PathBuf>,
+
+        /// Page size to use for creating the vendor boot image
+        #[arg(long, default_value = "2048")]
+        page_size: AndroidBootPageSizes,
+
+        /// Physical load address of the kernel
+        #[arg(long, default_value = "0x00008000", value_parser = address32_value_parser)]
+        kernel_addr: String,
+
+        /// Physical load address of the ramdisk
+        #[arg(long, default_value = "0x01000000", value_parser = address32_value_parser)]
+        ramdisk_addr: String,
+
+        /// Physical load address of the tags
+        #[arg(long, default_value = "0x00000100", value_parser = address32_value_parser)]
+        tags_addr: String,
+
+        /// Physical load address of the device tree
+        #[arg(long, default_value = "0x0000000000000000", value_parser = address64_value_parser)]
+        dtb_addr: String,
+
+        /// Product name of the vendor boot image
+        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        name: String,
+
+        /// Vendor kernel command line of the vendor boot image
+        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        cmdline: String,
+    },
+
+    /// Create a new vendor boot image version 4 (>= Android 12)
+    VendorBootimgV4 {
+        /// Output vendor boot image file
+        #[arg(short, long, required = true)]
+        output_vendor_boot_file: PathBuf,
+
+        /// Vendor ramdisk file to use for creating the vendor boot image
+        #[arg(short = 'r', long, required = true, value_parser = file_exists_value_parser)]
+        vendor_ramdisk_file: PathBuf,
+
+        /// Device tree file to use for creating the vendor boot image
+        #[arg(short = 'd', long, value_parser = file_exists_value_parser)]
+        dtb_file: Option<PathBuf>,
+
+        /// Page size to use for creating the vendor boot image
+        #[arg(long, default_value = "2048")]
+        page_size: AndroidBootPageSizes,
+
+        /// Physical load address of the kernel
+        #[arg(long, default_value = "0x00008000", value_parser = address32_value_parser)]
+        kernel_addr: String,
+
+        /// Physical load address of the ramdisk
+        #[arg(long, default_value = "0x01000000", value_parser = address32_value_parser)]
+        ramdisk_addr: String,
+
+        /// Physical load address of the tags
+        #[arg(long, default_value = "0x00000100", value_parser = address32_value_parser)]
+        tags_addr: String,
+
+        /// Physical load address of the device tree
+        #[arg(long, default_value = "0x0000000000000000", value_parser = address64_value_parser)]
+        dtb_addr: String,
+
+        /// Product name of the vendor boot image
+        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        name: String,
+
+        /// Vendor kernel command line of the vendor boot image
+        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        cmdline: String,
+
+        /// Bootconfig file to use for creating the vendor boot image
+        #[arg(short = 'b', long, value_parser = file_exists_value_parser)]
+        bootconfig_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VendorRamdiskCommand {
+    /// List the vendor ramdisk fragments
+    List {
+        /// Vendor boot image file to list fragments from
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+    },
+    /// Extract vendor ramdisk fragments
+    Extract {
+        /// Vendor boot image file to extract fragments from
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Directory to extract fragments to
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Index of the fragment to extract (all fragments if omitted)
+        #[arg(short = 'n', long)]
+        index: Option<usize>,
+    },
+    /// Append a vendor ramdisk fragment
+    Add {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Ramdisk fragment file to add
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        fragment_file: PathBuf,
+
+        /// Type of the fragment
+        #[arg(short = 't', long = "type", default_value = "platform")]
+        ramdisk_type: VendorRamdiskTypes,
+
+        /// Name of the fragment
+        #[arg(long, default_value = "", value_parser = ascii_string_value_parser)]
+        name: String,
+
+        /// Board ID words of the fragment (comma separated, up to 16)
+        #[arg(long, value_delimiter = ',', value_parser = u32_value_parser)]
+        board_id: Vec<u32>,
+    },
+    /// Remove a vendor ramdisk fragment
+    Remove {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Index of the fragment to remove
+        #[arg(short = 'n', long)]
+        index: usize,
+    },
+    /// Reorder the vendor ramdisk fragments
+    Reorder {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Current fragment indexes in their new order (comma separated)
+        #[arg(long, required = true, value_delimiter = ',')]
+        order: Vec<usize>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BootconfigCommand {
+    /// List the bootconfig parameters
+    List {
+        /// Vendor boot image file to list parameters from
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+    },
+    /// Add new bootconfig parameters
+    Add {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Parameter to add, as key=value, key= or a bare key (can be repeated)
+        #[arg(short, long = "param", required = true, value_parser = key_value_parser)]
+        params: Vec<String>,
+    },
+    /// Replace the value of existing bootconfig parameters
+    Replace {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Parameter to replace, as key=value, key= or a bare key (can be repeated)
+        #[arg(short, long = "param", required = true, value_parser = key_value_parser)]
+        params: Vec<String>,
+    },
+    /// Remove bootconfig parameters
+    Remove {
+        /// Vendor boot image file to update
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_vendor_boot_file: PathBuf,
+
+        /// Key of the parameter to remove (can be repeated)
+        #[arg(short, long = "key", required = true)]
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QcdtCommand {
+    /// List the entries of a device tree table
+    List {
+        /// Table file, or boot or vendor boot image holding one as its DTB
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+    },
+    /// Extract the DTBs of a device tree table
+    Extract {
+        /// Table file, or boot or vendor boot image holding one as its DTB
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Directory to extract the DTBs and dt_table.cfg to
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Build a device tree table from a set of DTBs and their ids
+    Create {
+        /// Directory holding the DTB files
+        #[arg(short, long)]
+        input_dir: PathBuf,
+
+        /// Output table file
+        #[arg(short, long)]
+        output_file: PathBuf,
+
+        /// Table description listing the DTBs and their ids (defaults to dt_table.cfg in the input directory)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        config_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DtboCommand {
+    /// List the entries of a DTBO image
+    List {
+        /// DTBO image, or boot image holding one as its recovery DTBO
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+    },
+    /// Extract the overlays of a DTBO image
+    Extract {
+        /// DTBO image, or boot image holding one as its recovery DTBO
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Directory to extract the overlays and dtbo.cfg to
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Build a DTBO image from a set of overlays and their ids
+    Create {
+        /// Directory holding the overlay files
+        #[arg(short, long)]
+        input_dir: PathBuf,
+
+        /// Output DTBO image
+        #[arg(short, long)]
+        output_file: PathBuf,
+
+        /// Image description listing the overlays and their ids (defaults to dtbo.cfg in the input directory)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        config_file: Option<PathBuf>,
     },
 }
 
@@ -430,89 +818,342 @@ pub enum RamdiskCommand {
 pub enum DevicetreeCommand {
     /// Display information about a device tree
     Info {
+        /// DTB file, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+    },
+    /// Decompile a DTB to device tree source (DTS)
+    Decompile {
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// DTS file to write (printed if omitted)
         #[arg(short, long)]
+        output_file: Option<PathBuf>,
+
+        /// DTB to decompile when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+    },
+    /// Compile device tree source (DTS) to a DTB
+    Compile {
+        /// DTS file, already run through cpp if it uses #include or macros
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// DTB file to write
+        #[arg(short, long)]
+        output_file: PathBuf,
+
+        /// Generate the __symbols__ node from the labels, like dtc -@
+        #[arg(short = '@', long)]
+        symbols: bool,
+    },
+    /// Print the value of a property, like fdtget
+    Get {
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
+
+        /// Absolute path of the node (/ for the root node)
+        #[arg(short, long)]
+        node_path: String,
+
+        /// Name of the property
+        #[arg(short, long)]
+        property: String,
+
+        /// How to print the value
+        #[arg(short = 't', long = "type", default_value = "dts")]
+        value_type: PropertyValueTypes,
+
+        /// DTB to read when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+    },
+    /// Set the value of a property, like fdtput
+    Set {
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Absolute path of the node (/ for the root node)
+        #[arg(short, long)]
+        node_path: String,
+
+        /// Name of the property
+        #[arg(short, long)]
+        property: String,
+
+        /// Value of the property, can be repeated for lists (an empty property if omitted)
+        #[arg(short, long = "value", allow_hyphen_values = true)]
+        values: Vec<String>,
+
+        /// How to parse the value
+        #[arg(short = 't', long = "type", default_value = "dts")]
+        value_type: PropertyValueTypes,
+
+        /// Create the node and its missing parents if they do not exist
+        #[arg(short, long)]
+        create: bool,
+
+        /// DTB to edit when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+
+        /// Output file (the input is updated in place if omitted)
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
     /// Remove a node from device tree
     Remove {
-        #[arg(short, long)]
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// Absolute path of the node to remove
         #[arg(short, long)]
         node_path: String,
+
+        /// DTB to edit when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+
+        /// Output file (the input is updated in place if omitted)
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
     /// Add a node to device tree
     Add {
-        #[arg(short, long)]
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// Absolute path of the node to add
         #[arg(short, long)]
         node_path: String,
 
-        #[arg(short, long)]
+        /// Property of the node in DTS syntax (name=<0x1 0x2>, name="str", name=[01 02] or name)
+        #[arg(short, long = "property")]
         properties: Vec<String>,
+
+        /// DTB to edit when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+
+        /// Output file (the input is updated in place if omitted)
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
     /// Replace a node in device tree
     Replace {
-        #[arg(short, long)]
+        /// DTB file, kernel with appended DTBs, or boot or vendor boot image holding DTBs
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// Absolute path of the node to replace
         #[arg(short, long)]
         node_path: String,
 
-        #[arg(short, long)]
+        /// DTB or DTS file whose root node replaces the node
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         replacement_file: PathBuf,
+
+        /// DTB to edit when the input holds several, starting from 0
+        #[arg(long)]
+        index: Option<usize>,
+
+        /// Output file (the input is updated in place if omitted)
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum SignatureCommand {
-    /// Display information about a signature
+    /// Display the AVB footer and vbmeta struct, or the VBoot 1.0 signature, of an image
     Info {
-        #[arg(short, long)]
+        /// Signed image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
     },
-    /// Remove signature from boot image
+    /// Remove the AVB footer and vbmeta struct from an image
     Remove {
+        /// Signed image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Verify the AVB footer or VBoot 1.0 signature of an image
+    Verify {
+        /// Signed image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
+
+        /// Public key the image must be signed with (PEM, .avbpubkey or X.509 certificate)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        key_file: Option<PathBuf>,
     },
-    /// Replace signature in boot image
-    Replace {
+    /// Append an AVB hash footer to an image
+    Generate {
+        /// Image file to sign
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// PEM RSA private key (required unless the algorithm is none)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        key_file: Option<PathBuf>,
+
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
+        output_file: Option<PathBuf>,
+
+        /// Algorithm used to sign the vbmeta struct
+        #[arg(short, long, default_value = "none")]
+        algorithm: AvbAlgorithms,
+
+        /// Name of the partition the image is flashed to
+        #[arg(long, default_value = "boot", value_parser = ascii_string_value_parser)]
+        partition_name: String,
+
+        /// Size of the partition (the smallest size that fits if omitted)
+        #[arg(long, value_parser = u64_value_parser)]
+        partition_size: Option<u64>,
+
+        /// Salt of the hash descriptor as hex (random if omitted)
+        // Fully qualified so clap parses a single value instead of a list
+        #[arg(long, value_parser = hex_value_parser)]
+        salt: Option<::std::vec::Vec<u8>>,
+
+        /// Rollback index of the vbmeta struct
+        #[arg(long, default_value = "0", value_parser = u64_value_parser)]
+        rollback_index: u64,
+    },
+    /// Sign a boot image with a VBoot 1.0 signature, like boot_signer
+    GenerateVbootSignature {
+        /// Boot image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// RSA private key (PEM, or DER PKCS#8 like .pk8 files)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        key_file: PathBuf,
+
+        /// X.509 certificate of the key (PEM or DER)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        certificate_file: PathBuf,
+
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
-        signature_file: PathBuf,
+        output_file: Option<PathBuf>,
+
+        /// Partition the image is flashed to
+        #[arg(short, long, default_value = "/boot", value_parser = ascii_string_value_parser)]
+        target: String,
     },
-    /// Generate new signature for boot image
-    Generate {
+    /// Extract the boot signature section of a boot image v4
+    ExtractBootSignature {
+        /// Boot image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Output boot signature file
+        #[arg(short, long, required = true)]
+        output_file: PathBuf,
+    },
+    /// Replace the boot signature section of a boot image v4
+    ReplaceBootSignature {
+        /// Boot image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Boot signature file to store in the image
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        signature_file: PathBuf,
+
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Remove the boot signature section of a boot image v4
+    RemoveBootSignature {
+        /// Boot image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
-        key_file: PathBuf,
+        output_file: Option<PathBuf>,
+    },
+    /// Generate the boot signature section of a boot image v4 (GKI certificate)
+    GenerateBootSignature {
+        /// Boot image file
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// PEM RSA private key (required unless the algorithm is none)
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        key_file: Option<PathBuf>,
 
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
         output_file: Option<PathBuf>,
+
+        /// Algorithm used to sign the vbmeta structs
+        #[arg(short, long, default_value = "none")]
+        algorithm: AvbAlgorithms,
+
+        /// Salt of the hash descriptors as hex (random if omitted)
+        // Fully qualified so clap parses a single value instead of a list
+        #[arg(long, value_parser = hex_value_parser)]
+        salt: Option<::std::vec::Vec<u8>>,
     },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum KernelCommand {
-    /// Display information about a kernel
+    /// Display information about a kernel and the DTBs appended to it
     Info {
-        #[arg(short, long)]
+        /// Kernel file, or boot image holding one
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
     },
-    /// Extract kernel configuration
-    ExtractConfig {
+    /// Split the DTBs appended to a kernel (Image.gz-dtb, zImage-dtb) into separate files
+    SplitDtb {
+        /// Kernel file, or boot image holding one
+        #[arg(short, long, value_parser = file_exists_value_parser)]
+        input_file: PathBuf,
+
+        /// Directory to write the kernel and DTBs to
         #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Append DTBs to a kernel
+    JoinDtb {
+        /// Kernel file, or boot image holding one
+        #[arg(short, long, value_parser = file_exists_value_parser)]
         input_file: PathBuf,
 
+        /// DTB files to append, in order
+        #[arg(short, long = "dtb-file", required = true, value_parser = file_exists_value_parser)]
+        dtb_files: Vec<PathBuf>,
+
+        /// Output file (the input is updated in place if omitted)
         #[arg(short, long)]
-        output_file: PathBuf,
+        output_file: Option<PathBuf>,
     },
+    // /// Extract kernel configuration
+    // ExtractConfig {
+    //     #[arg(short, long)]
+    //     input_file: PathBuf,
+
+    //     #[arg(short, long)]
+    //     output_file: PathBuf,
+    // },
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -523,6 +1164,80 @@ pub enum AndroidBootPageSizes {
     _16384 = 16384,
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+pub enum IdHashTypes {
+    Sha1,
+    Sha256,
+}
+
+impl From<IdHashTypes> for IdHash {
+    fn from(id_hash: IdHashTypes) -> Self {
+        match id_hash {
+            IdHashTypes::Sha1 => IdHash::Sha1,
+            IdHashTypes::Sha256 => IdHash::Sha256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum AvbAlgorithms {
+    None,
+    Sha256Rsa2048,
+    Sha256Rsa4096,
+    Sha256Rsa8192,
+    Sha512Rsa2048,
+    Sha512Rsa4096,
+    Sha512Rsa8192,
+}
+
+impl From<AvbAlgorithms> for AvbAlgorithm {
+    fn from(algorithm: AvbAlgorithms) -> Self {
+        match algorithm {
+            AvbAlgorithms::None => AvbAlgorithm::None,
+            AvbAlgorithms::Sha256Rsa2048 => AvbAlgorithm::Sha256Rsa2048,
+            AvbAlgorithms::Sha256Rsa4096 => AvbAlgorithm::Sha256Rsa4096,
+            AvbAlgorithms::Sha256Rsa8192 => AvbAlgorithm::Sha256Rsa8192,
+            AvbAlgorithms::Sha512Rsa2048 => AvbAlgorithm::Sha512Rsa2048,
+            AvbAlgorithms::Sha512Rsa4096 => AvbAlgorithm::Sha512Rsa4096,
+            AvbAlgorithms::Sha512Rsa8192 => AvbAlgorithm::Sha512Rsa8192,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum PropertyValueTypes {
+    /// DTS syntax: <0x1 0x2>, "str", [01 02]
+    Dts,
+    /// List of strings
+    String,
+    /// 32-bit cells
+    U32,
+    /// 64-bit integers
+    U64,
+    /// Bytes in hex
+    Bytes,
+}
+
+impl From<PropertyValueTypes> for ValueType {
+    fn from(value_type: PropertyValueTypes) -> Self {
+        match value_type {
+            PropertyValueTypes::Dts => ValueType::Dts,
+            PropertyValueTypes::String => ValueType::String,
+            PropertyValueTypes::U32 => ValueType::U32,
+            PropertyValueTypes::U64 => ValueType::U64,
+            PropertyValueTypes::Bytes => ValueType::Bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum VendorRamdiskTypes {
+    None = 0,
+    Platform = 1,
+    Recovery = 2,
+    Dlkm = 3,
+}
+
 fn file_exists_value_parser(path: &str) -> Result<PathBuf, String> {
     let path = PathBuf::from(path);
     if path.exists() {
@@ -541,8 +1256,8 @@ fn ascii_string_value_parser(s: &str) -> Result<String, String> {
 }
 
 fn address32_value_parser(s: &str) -> Result<String, String> {
-    if s.starts_with("0x") {
-        u32::from_str_radix(&s[2..], 16)
+    if let Some(hex) = s.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16)
             .map(|_| s.to_string())
             .map_err(|e| e.to_string())
     } else {
@@ -551,11 +1266,110 @@ fn address32_value_parser(s: &str) -> Result<String, String> {
 }
 
 fn address64_value_parser(s: &str) -> Result<String, String> {
-    if s.starts_with("0x") {
-        u64::from_str_radix(&s[2..], 16)
+    if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
             .map(|_| s.to_string())
             .map_err(|e| e.to_string())
     } else {
         Err("Address must start with 0x".to_string())
     }
 }
+
+fn u32_value_parser(s: &str) -> Result<u32, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).map_err(|e| e.to_string())
+    } else {
+        s.parse::<u32>().map_err(|e| e.to_string())
+    }
+}
+
+fn u64_value_parser(s: &str) -> Result<u64, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).map_err(|e| e.to_string())
+    } else {
+        s.parse::<u64>().map_err(|e| e.to_string())
+    }
+}
+
+fn hex_value_parser(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Value must be an even number of hex digits".to_string());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Accepts `key=value`, `key=` and a bare `key`, whose key is trimmed like
+/// the bootconfig commands do.
+fn key_value_parser(s: &str) -> Result<String, String> {
+    let key = s.split_once('=').map_or(s, |(key, _)| key);
+    if key.trim().is_empty() {
+        return Err("Parameter must be a key, optionally followed by =value".to_string());
+    }
+    Ok(s.to_string())
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum TrailerTypes {
+    Seandroidenforce,
+    LgBump,
+}
+
+impl From<TrailerTypes> for TrailerKind {
+    fn from(trailer: TrailerTypes) -> Self {
+        match trailer {
+            TrailerTypes::Seandroidenforce => TrailerKind::SeAndroidEnforce,
+            TrailerTypes::LgBump => TrailerKind::LgBump,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum MtkHeaderTypes {
+    Boot,
+    Recovery,
+}
+
+impl From<MtkHeaderTypes> for MtkImageKind {
+    fn from(image: MtkHeaderTypes) -> Self {
+        match image {
+            MtkHeaderTypes::Boot => MtkImageKind::Boot,
+            MtkHeaderTypes::Recovery => MtkImageKind::Recovery,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bootconfig_add(params: &[&str]) -> Result<Vec<String>, clap::Error> {
+        let image = tempfile::NamedTempFile::new().unwrap();
+        let mut args = vec!["abootcrafter", "bootconfig", "add", "-i"];
+        args.push(image.path().to_str().unwrap());
+        for param in params {
+            args.extend(["-p", param]);
+        }
+        match Cli::try_parse_from(args)?.command {
+            MainCommand::Bootconfig {
+                command: BootconfigCommand::Add { params, .. },
+            } => Ok(params),
+            command => panic!("Unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn bootconfig_params_accept_bare_keys() {
+        let params = ["androidboot.flag", "androidboot.empty=", " foo =bar"];
+        assert_eq!(bootconfig_add(&params).unwrap(), params);
+        for param in ["=bar", " =bar", ""] {
+            assert!(
+                bootconfig_add(&[param]).is_err(),
+                "{:?} was accepted",
+                param
+            );
+        }
+    }
+}
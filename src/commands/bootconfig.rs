@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+
+use crate::commands::output::write_atomically;
+use crate::commands::signature::print_dropped_signatures;
+use crate::errors::AbootCrafterError;
+use crate::headers::bootconfig::Bootconfig;
+use crate::headers::vendor::VendorBootFile;
+
+fn load_bootconfig(
+    input_vendor_boot_file: &PathBuf,
+) -> Result<(VendorBootFile, Bootconfig), AbootCrafterError> {
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.load(input_vendor_boot_file)?;
+
+    if vendor_boot_file.version < 4 {
+        return Err(AbootCrafterError::ConfigError(
+            "Bootconfig is only supported on vendor boot v4".to_string(),
+        ));
+    }
+
+    let bootconfig = Bootconfig::parse(&vendor_boot_file.read_bootconfig()?);
+    Ok((vendor_boot_file, bootconfig))
+}
+
+fn save_bootconfig(
+    input_vendor_boot_file: &PathBuf,
+    mut vendor_boot_file: VendorBootFile,
+    bootconfig: &Bootconfig,
+) -> Result<(), AbootCrafterError> {
+    let fragments = vendor_boot_file.read_vendor_ramdisk_fragments()?;
+    let dtb = vendor_boot_file.read_dtb()?;
+    let signatures = vendor_boot_file.signature_trailers();
+    write_atomically(input_vendor_boot_file, |temp_file| {
+        vendor_boot_file.save_v4(temp_file, &fragments, &dtb, &bootconfig.to_bytes())
+    })?;
+    print_dropped_signatures(&signatures, &vendor_boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Splits `key=value`, keeping a bare `key` apart from `key=`. Both are
+/// trimmed, as they would be when the section is parsed again.
+fn split_param(param: &str) -> (&str, Option<&str>) {
+    match param.split_once('=') {
+        Some((key, value)) => (key.trim(), Some(value.trim())),
+        None => (param.trim(), None),
+    }
+}
+
+/// Prints the bootconfig parameters of a vendor boot image.
+pub fn print_bootconfig(bootconfig: &Bootconfig) {
+    println!("[Bootconfig]");
+    print!("{}", bootconfig);
+}
+
+/// Lists the bootconfig parameters of a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+pub fn list(input_vendor_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
+    let (_, bootconfig) = load_bootconfig(input_vendor_boot_file)?;
+    print_bootconfig(&bootconfig);
+    Ok(())
+}
+
+/// Adds new `key=value` parameters to the bootconfig of a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `params` - The `key=value` parameters to add.
+pub fn add(input_vendor_boot_file: &PathBuf, params: Vec<String>) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, mut bootconfig) = load_bootconfig(input_vendor_boot_file)?;
+    for param in &params {
+        let (key, value) = split_param(param);
+        bootconfig
+            .add(key, value)
+            .map_err(AbootCrafterError::ConfigError)?;
+    }
+    save_bootconfig(input_vendor_boot_file, vendor_boot_file, &bootconfig)
+}
+
+/// Replaces the values of existing bootconfig parameters of a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `params` - The `key=value` parameters to replace.
+pub fn replace(
+    input_vendor_boot_file: &PathBuf,
+    params: Vec<String>,
+) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, mut bootconfig) = load_bootconfig(input_vendor_boot_file)?;
+    for param in &params {
+        let (key, value) = split_param(param);
+        bootconfig
+            .replace(key, value)
+            .map_err(AbootCrafterError::ConfigError)?;
+    }
+    save_bootconfig(input_vendor_boot_file, vendor_boot_file, &bootconfig)
+}
+
+/// Removes bootconfig parameters from a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `keys` - The keys of the parameters to remove.
+pub fn remove(
+    input_vendor_boot_file: &PathBuf,
+    keys: Vec<String>,
+) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, mut bootconfig) = load_bootconfig(input_vendor_boot_file)?;
+    for key in &keys {
+        bootconfig
+            .remove(key)
+            .map_err(AbootCrafterError::ConfigError)?;
+    }
+    save_bootconfig(input_vendor_boot_file, vendor_boot_file, &bootconfig)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create::tests::vendor_boot_v4;
+
+    #[test]
+    fn add_keeps_the_other_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = vendor_boot_v4(dir.path());
+
+        add(
+            &fixture.image,
+            vec![
+                "androidboot.empty=".into(),
+                "androidboot.flag".into(),
+                " androidboot.spaced = value ".into(),
+            ],
+        )
+        .unwrap();
+
+        let (vendor_boot_file, bootconfig) = load_bootconfig(&fixture.image).unwrap();
+        assert_eq!(
+            bootconfig.to_string(),
+            format!(
+                "{}androidboot.empty=\nandroidboot.flag\nandroidboot.spaced=value\n",
+                fixture.bootconfig
+            )
+        );
+        assert_eq!(vendor_boot_file.read_dtb().unwrap(), fixture.dtb);
+        let fragments = vendor_boot_file.read_vendor_ramdisk_fragments().unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].data, fixture.vendor_ramdisk);
+    }
+}
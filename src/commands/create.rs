@@ -1,27 +1,79 @@
 use crate::errors::AbootCrafterError;
 use crate::headers::android::{
-    AndroidBootFile, AndroidHeader, AndroidHeaderVersion0, AndroidHeaderVersion1,
-    AndroidHeaderVersion2, AndroidHeaderVersion3, AndroidHeaderVersion4,
+    AndroidBootFile, AndroidHeader, AndroidHeaderVersion0, AndroidHeaderVersion0Qcom,
+    AndroidHeaderVersion1, AndroidHeaderVersion2, AndroidHeaderVersion3, AndroidHeaderVersion4,
+    BootComponent, HEADER_SIZE_V1, HEADER_SIZE_V2, HEADER_SIZE_V3, HEADER_SIZE_V4,
 };
+use crate::headers::bootconfig::Bootconfig;
 use crate::headers::fields::{
     AddressU32, AddressU64, AndroidBootMagic, Cmdline, CmdlineExtended, ExtraCmdline, Id, Name,
-    OSVersion,
+    OSVersion, VendorBootMagic, VendorCmdline, VendorRamdiskName,
+};
+use crate::headers::id::IdHash;
+use crate::headers::layout::align;
+use crate::headers::mtk::{MtkHeader, MtkImageKind};
+use crate::headers::trailer::TrailerKind;
+use crate::headers::vendor::{
+    VendorBootFile, VendorHeader, VendorHeaderVersion3, VendorHeaderVersion4,
+    VendorRamdiskFragment, VendorRamdiskTableEntry, VendorRamdiskType, VENDOR_HEADER_SIZE_V3,
+    VENDOR_HEADER_SIZE_V4, VENDOR_RAMDISK_TABLE_ENTRY_SIZE,
 };
 use std::io::Write;
 use std::path::PathBuf;
 
 fn pad_data_to_page_size(data: &[u8], page_size: u32) -> Vec<u8> {
-    let padded_size = (data.len() as u32).div_ceil(page_size) * page_size;
-    let mut padded_data = vec![0u8; padded_size as usize];
-    padded_data[..data.len()].copy_from_slice(data);
+    let mut padded_data = data.to_vec();
+    padded_data.resize(align(data.len() as u64, page_size) as usize, 0);
     padded_data
 }
 
+fn read_optional_file(file: Option<PathBuf>) -> std::io::Result<Vec<u8>> {
+    file.map_or_else(|| Ok(Vec::new()), std::fs::read)
+}
+
+/// Fills every component of a new boot image and writes it out with the
+/// shared layout. The `id` field is computed from the components when
+/// `id_hash` is given, and `trailers` are appended after the last section.
+/// With `mtk_header`, the kernel and ramdisk are wrapped in MediaTek headers
+/// named for that kind of image.
+fn write_boot_file(
+    header: AndroidHeader,
+    components: Vec<(BootComponent, Vec<u8>)>,
+    id_hash: Option<IdHash>,
+    trailers: Vec<TrailerKind>,
+    mtk_header: Option<MtkImageKind>,
+    output_boot_file: PathBuf,
+) -> Result<(), AbootCrafterError> {
+    let mut boot_file = AndroidBootFile {
+        version: header.version(),
+        header,
+        ..Default::default()
+    };
+
+    for (component, mut data) in components {
+        if let Some(name) = mtk_header.and_then(|image| MtkHeader::default_name(component, image)) {
+            data = MtkHeader::rewrap(&data, name)?;
+        }
+        boot_file.replace_component(component, data)?;
+    }
+    if let Some(id_hash) = id_hash {
+        boot_file.update_id(id_hash)?;
+    }
+    for kind in trailers {
+        boot_file.append_trailer(kind)?;
+    }
+    boot_file.save_image(output_boot_file)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_v0(
     output_boot_file: PathBuf,
     kernel_file: PathBuf,
     ramdisk_file: PathBuf,
     second_file: Option<PathBuf>,
+    dt_file: Option<PathBuf>,
     page_size: u32,
     kernel_addr: String,
     ramdisk_addr: String,
@@ -30,11 +82,51 @@ pub fn create_v0(
     os_version: String,
     name: String,
     cmdline: String,
-    id: String,
+    id: Option<String>,
+    id_hash: IdHash,
     extra_cmdline: String,
+    trailers: Vec<TrailerKind>,
+    mtk_header: Option<MtkImageKind>,
 ) -> Result<(), AbootCrafterError> {
-    let mut boot_file = AndroidBootFile::default();
-    boot_file.version = 0;
+    // An explicit id is kept as given, otherwise it is computed like mkbootimg
+    let id_hash = id.is_none().then_some(id_hash);
+    let mut components = vec![
+        (BootComponent::Kernel, std::fs::read(kernel_file)?),
+        (BootComponent::Ramdisk, std::fs::read(ramdisk_file)?),
+        (BootComponent::Second, read_optional_file(second_file)?),
+    ];
+
+    // A DT section needs the QCOM variant, which stores its size in place
+    // of header_version
+    if let Some(dt_file) = dt_file {
+        let header = AndroidHeaderVersion0Qcom {
+            magic: AndroidBootMagic::default(),
+            kernel_size: 0,
+            kernel_addr: AddressU32::from(kernel_addr),
+            ramdisk_size: 0,
+            ramdisk_addr: AddressU32::from(ramdisk_addr),
+            second_size: 0,
+            second_addr: AddressU32::from(second_addr),
+            tags_addr: AddressU32::from(tags_addr),
+            page_size,
+            dt_size: 0,
+            os_version: OSVersion::from(os_version),
+            name: Name::from(name),
+            cmdline: Cmdline::from(cmdline),
+            id: Id::from(id.unwrap_or_default()),
+            extra_cmdline: ExtraCmdline::from(extra_cmdline),
+        };
+        components.push((BootComponent::Dtb, std::fs::read(dt_file)?));
+
+        return write_boot_file(
+            AndroidHeader::V0Qcom(header),
+            components,
+            id_hash,
+            trailers,
+            mtk_header,
+            output_boot_file,
+        );
+    }
 
     let header = AndroidHeaderVersion0 {
         magic: AndroidBootMagic::default(),
@@ -50,51 +142,21 @@ pub fn create_v0(
         os_version: OSVersion::from(os_version),
         name: Name::from(name),
         cmdline: Cmdline::from(cmdline),
-        id: Id::from(id),
+        id: Id::from(id.unwrap_or_default()),
         extra_cmdline: ExtraCmdline::from(extra_cmdline),
     };
 
-    boot_file.header = AndroidHeader::V0(header);
-
-    // Update header with actual sizes (not padded)
-    let header = match boot_file.header {
-        AndroidHeader::V0(ref mut header) => header,
-        _ => {
-            return Err(AbootCrafterError::ConfigError(
-                "Invalid header version".to_string(),
-            ))
-        }
-    };
-
-    // Load kernel, ramdisk, and second files
-    let kernel_data = std::fs::read(kernel_file)?;
-    let ramdisk_data = std::fs::read(ramdisk_file)?;
-    let second_data = if let Some(second_file) = second_file {
-        std::fs::read(second_file)?
-    } else {
-        Vec::new()
-    };
-
-    // Write raw size to header
-    header.kernel_size = kernel_data.len() as u32;
-    header.ramdisk_size = ramdisk_data.len() as u32;
-    header.second_size = second_data.len() as u32;
-
-    // Write header to output file
-    boot_file.save(output_boot_file, page_size)?;
-    let mut file = boot_file.get_file();
-
-    // Write padded data to output file
-    let kernel_padded = pad_data_to_page_size(&kernel_data, page_size);
-    let ramdisk_padded = pad_data_to_page_size(&ramdisk_data, page_size);
-    let second_padded = pad_data_to_page_size(&second_data, page_size);
-    file.write_all(&kernel_padded)?;
-    file.write_all(&ramdisk_padded)?;
-    file.write_all(&second_padded)?;
-
-    Ok(())
+    write_boot_file(
+        AndroidHeader::V0(header),
+        components,
+        id_hash,
+        trailers,
+        mtk_header,
+        output_boot_file,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_v1(
     output_boot_file: PathBuf,
     kernel_file: PathBuf,
@@ -109,13 +171,14 @@ pub fn create_v1(
     os_version: String,
     name: String,
     cmdline: String,
-    id: String,
+    id: Option<String>,
+    id_hash: IdHash,
     extra_cmdline: String,
-    recovery_dtbo_offset: String,
+    trailers: Vec<TrailerKind>,
+    mtk_header: Option<MtkImageKind>,
 ) -> Result<(), AbootCrafterError> {
-    let mut boot_file = AndroidBootFile::default();
-    boot_file.version = 1;
-
+    // An explicit id is kept as given, otherwise it is computed like mkbootimg
+    let id_hash = id.is_none().then_some(id_hash);
     let header = AndroidHeaderVersion1 {
         magic: AndroidBootMagic::default(),
         kernel_size: 0,
@@ -130,57 +193,32 @@ pub fn create_v1(
         os_version: OSVersion::from(os_version),
         name: Name::from(name),
         cmdline: Cmdline::from(cmdline),
-        id: Id::from(id),
+        id: Id::from(id.unwrap_or_default()),
         extra_cmdline: ExtraCmdline::from(extra_cmdline),
         recovery_dtbo_size: 0,
-        recovery_dtbo_offset: AddressU64::from(recovery_dtbo_offset),
-        header_size: 0,
-    };
-
-    boot_file.header = AndroidHeader::V1(header);
-
-    // Load kernel, ramdisk, second, and recovery_dtbo files
-    let kernel_data = std::fs::read(kernel_file)?;
-    let ramdisk_data = std::fs::read(ramdisk_file)?;
-    let second_data = if let Some(second_file) = second_file {
-        std::fs::read(second_file)?
-    } else {
-        Vec::new()
-    };
-
-    let recovery_dtbo_data = if let Some(recovery_dtbo_file) = recovery_dtbo_file {
-        std::fs::read(recovery_dtbo_file)?
-    } else {
-        Vec::new()
+        recovery_dtbo_offset: AddressU64(vec![0; 8]),
+        header_size: HEADER_SIZE_V1,
     };
 
-    // Update header sizes
-    let header = match boot_file.header {
-        AndroidHeader::V1(ref mut header) => header,
-        _ => {
-            return Err(AbootCrafterError::ConfigError(
-                "Invalid header version".to_string(),
-            ))
-        }
-    };
-
-    header.kernel_size = kernel_data.len() as u32;
-    header.ramdisk_size = ramdisk_data.len() as u32;
-    header.second_size = second_data.len() as u32;
-    header.recovery_dtbo_size = recovery_dtbo_data.len() as u32;
-
-    // Write to output file
-    boot_file.save(output_boot_file, page_size)?;
-    let mut file = boot_file.get_file();
-
-    file.write_all(&kernel_data)?;
-    file.write_all(&ramdisk_data)?;
-    file.write_all(&second_data)?;
-    file.write_all(&recovery_dtbo_data)?;
-
-    Ok(())
+    write_boot_file(
+        AndroidHeader::V1(header),
+        vec![
+            (BootComponent::Kernel, std::fs::read(kernel_file)?),
+            (BootComponent::Ramdisk, std::fs::read(ramdisk_file)?),
+            (BootComponent::Second, read_optional_file(second_file)?),
+            (
+                BootComponent::RecoveryDtbo,
+                read_optional_file(recovery_dtbo_file)?,
+            ),
+        ],
+        id_hash,
+        trailers,
+        mtk_header,
+        output_boot_file,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_v2(
     output_boot_file: PathBuf,
     kernel_file: PathBuf,
@@ -196,14 +234,15 @@ pub fn create_v2(
     os_version: String,
     name: String,
     cmdline: String,
-    id: String,
+    id: Option<String>,
+    id_hash: IdHash,
     extra_cmdline: String,
-    recovery_dtbo_offset: String,
     dtb_addr: String,
+    trailers: Vec<TrailerKind>,
+    mtk_header: Option<MtkImageKind>,
 ) -> Result<(), AbootCrafterError> {
-    let mut boot_file = AndroidBootFile::default();
-    boot_file.version = 2;
-
+    // An explicit id is kept as given, otherwise it is computed like mkbootimg
+    let id_hash = id.is_none().then_some(id_hash);
     let header = AndroidHeaderVersion2 {
         magic: AndroidBootMagic::default(),
         kernel_size: 0,
@@ -218,63 +257,32 @@ pub fn create_v2(
         os_version: OSVersion::from(os_version),
         name: Name::from(name),
         cmdline: Cmdline::from(cmdline),
-        id: Id::from(id),
+        id: Id::from(id.unwrap_or_default()),
         extra_cmdline: ExtraCmdline::from(extra_cmdline),
         recovery_dtbo_size: 0,
-        recovery_dtbo_offset: AddressU64::from(recovery_dtbo_offset),
-        header_size: 0,
+        recovery_dtbo_offset: AddressU64(vec![0; 8]),
+        header_size: HEADER_SIZE_V2,
         dtb_size: 0,
         dtb_addr: AddressU64::from(dtb_addr),
     };
 
-    boot_file.header = AndroidHeader::V2(header);
-
-    // Load kernel, ramdisk, second, recovery_dtbo, and dtb files
-    let kernel_data = std::fs::read(kernel_file)?;
-    let ramdisk_data = std::fs::read(ramdisk_file)?;
-    let second_data = if let Some(second_file) = second_file {
-        std::fs::read(second_file)?
-    } else {
-        Vec::new()
-    };
-    let recovery_dtbo_data = if let Some(recovery_dtbo_file) = recovery_dtbo_file {
-        std::fs::read(recovery_dtbo_file)?
-    } else {
-        Vec::new()
-    };
-    let dtb_data = if let Some(dtb_file) = dtb_file {
-        std::fs::read(dtb_file)?
-    } else {
-        Vec::new()
-    };
-
-    // Update header sizes
-    let header = match boot_file.header {
-        AndroidHeader::V2(ref mut header) => header,
-        _ => {
-            return Err(AbootCrafterError::ConfigError(
-                "Invalid header version".to_string(),
-            ))
-        }
-    };
-
-    header.kernel_size = kernel_data.len() as u32;
-    header.ramdisk_size = ramdisk_data.len() as u32;
-    header.second_size = second_data.len() as u32;
-    header.recovery_dtbo_size = recovery_dtbo_data.len() as u32;
-    header.dtb_size = dtb_data.len() as u32;
-
-    // Write to output file
-    boot_file.save(output_boot_file, page_size)?;
-    let mut file = boot_file.get_file();
-
-    file.write_all(&kernel_data)?;
-    file.write_all(&ramdisk_data)?;
-    file.write_all(&second_data)?;
-    file.write_all(&recovery_dtbo_data)?;
-    file.write_all(&dtb_data)?;
-
-    Ok(())
+    write_boot_file(
+        AndroidHeader::V2(header),
+        vec![
+            (BootComponent::Kernel, std::fs::read(kernel_file)?),
+            (BootComponent::Ramdisk, std::fs::read(ramdisk_file)?),
+            (BootComponent::Second, read_optional_file(second_file)?),
+            (
+                BootComponent::RecoveryDtbo,
+                read_optional_file(recovery_dtbo_file)?,
+            ),
+            (BootComponent::Dtb, read_optional_file(dtb_file)?),
+        ],
+        id_hash,
+        trailers,
+        mtk_header,
+        output_boot_file,
+    )
 }
 
 pub fn create_v3(
@@ -283,97 +291,239 @@ pub fn create_v3(
     ramdisk_file: PathBuf,
     os_version: String,
     cmdline: String,
+    trailers: Vec<TrailerKind>,
 ) -> Result<(), AbootCrafterError> {
-    let mut boot_file = AndroidBootFile::default();
-    boot_file.version = 3;
-
     let header = AndroidHeaderVersion3 {
         magic: AndroidBootMagic::default(),
         kernel_size: 0,
         ramdisk_size: 0,
         os_version: OSVersion::from(os_version),
-        header_size: 0,
+        header_size: HEADER_SIZE_V3,
         reserved: [0; 4],
         header_version: 3,
         cmdline: CmdlineExtended::from(cmdline),
     };
 
-    boot_file.header = AndroidHeader::V3(header);
-
-    // Load kernel and ramdisk files
-    let kernel_data = std::fs::read(kernel_file)?;
-    let ramdisk_data = std::fs::read(ramdisk_file)?;
-
-    // Update header sizes
-    let header = match boot_file.header {
-        AndroidHeader::V3(ref mut header) => header,
-        _ => {
-            return Err(AbootCrafterError::ConfigError(
-                "Invalid header version".to_string(),
-            ))
-        }
-    };
-
-    header.kernel_size = kernel_data.len() as u32;
-    header.ramdisk_size = ramdisk_data.len() as u32;
-
-    // Write to output file
-    boot_file.save(output_boot_file, 4096)?;
-    let mut file = boot_file.get_file();
-
-    file.write_all(&kernel_data)?;
-    file.write_all(&ramdisk_data)?;
-
-    Ok(())
+    write_boot_file(
+        AndroidHeader::V3(header),
+        vec![
+            (BootComponent::Kernel, std::fs::read(kernel_file)?),
+            (BootComponent::Ramdisk, std::fs::read(ramdisk_file)?),
+        ],
+        None,
+        trailers,
+        None,
+        output_boot_file,
+    )
 }
 
 pub fn create_v4(
     output_boot_file: PathBuf,
     kernel_file: PathBuf,
     ramdisk_file: PathBuf,
+    signature_file: Option<PathBuf>,
     os_version: String,
     cmdline: String,
+    trailers: Vec<TrailerKind>,
 ) -> Result<(), AbootCrafterError> {
-    let mut boot_file = AndroidBootFile::default();
-    boot_file.version = 4;
-
     let header = AndroidHeaderVersion4 {
         magic: AndroidBootMagic::default(),
         kernel_size: 0,
         ramdisk_size: 0,
         os_version: OSVersion::from(os_version),
-        header_size: 0,
+        header_size: HEADER_SIZE_V4,
         reserved: [0; 4],
         header_version: 4,
         cmdline: CmdlineExtended::from(cmdline),
         signature_size: 0,
     };
 
-    boot_file.header = AndroidHeader::V4(header);
+    let mut components = vec![
+        (BootComponent::Kernel, std::fs::read(kernel_file)?),
+        (BootComponent::Ramdisk, std::fs::read(ramdisk_file)?),
+    ];
+    if let Some(signature_file) = signature_file {
+        components.push((BootComponent::Signature, std::fs::read(signature_file)?));
+    }
+
+    write_boot_file(
+        AndroidHeader::V4(header),
+        components,
+        None,
+        trailers,
+        None,
+        output_boot_file,
+    )
+}
 
-    // Load kernel and ramdisk files
-    let kernel_data = std::fs::read(kernel_file)?;
-    let ramdisk_data = std::fs::read(ramdisk_file)?;
+#[allow(clippy::too_many_arguments, clippy::field_reassign_with_default)]
+pub fn create_vendor_v3(
+    output_vendor_boot_file: PathBuf,
+    vendor_ramdisk_file: PathBuf,
+    dtb_file: Option<PathBuf>,
+    page_size: u32,
+    kernel_addr: String,
+    ramdisk_addr: String,
+    tags_addr: String,
+    dtb_addr: String,
+    name: String,
+    cmdline: String,
+) -> Result<(), AbootCrafterError> {
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.version = 3;
 
-    // Update header sizes
-    let header = match boot_file.header {
-        AndroidHeader::V4(ref mut header) => header,
-        _ => {
-            return Err(AbootCrafterError::ConfigError(
-                "Invalid header version".to_string(),
-            ))
-        }
+    // Load vendor ramdisk and dtb files
+    let vendor_ramdisk_data = std::fs::read(vendor_ramdisk_file)?;
+    let dtb_data = if let Some(dtb_file) = dtb_file {
+        std::fs::read(dtb_file)?
+    } else {
+        Vec::new()
+    };
+
+    let header = VendorHeaderVersion3 {
+        magic: VendorBootMagic::default(),
+        header_version: 3,
+        page_size,
+        kernel_addr: AddressU32::from(kernel_addr),
+        ramdisk_addr: AddressU32::from(ramdisk_addr),
+        vendor_ramdisk_size: vendor_ramdisk_data.len() as u32,
+        cmdline: VendorCmdline::from(cmdline),
+        tags_addr: AddressU32::from(tags_addr),
+        name: Name::from(name),
+        header_size: VENDOR_HEADER_SIZE_V3,
+        dtb_size: dtb_data.len() as u32,
+        dtb_addr: AddressU64::from(dtb_addr),
+    };
+
+    vendor_boot_file.header = VendorHeader::V3(header);
+
+    // Write header to output file
+    vendor_boot_file.save(output_vendor_boot_file, page_size)?;
+    let mut file = vendor_boot_file.get_file();
+
+    // Write padded data to output file
+    file.write_all(&pad_data_to_page_size(&vendor_ramdisk_data, page_size))?;
+    file.write_all(&pad_data_to_page_size(&dtb_data, page_size))?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments, clippy::field_reassign_with_default)]
+pub fn create_vendor_v4(
+    output_vendor_boot_file: PathBuf,
+    vendor_ramdisk_file: PathBuf,
+    dtb_file: Option<PathBuf>,
+    page_size: u32,
+    kernel_addr: String,
+    ramdisk_addr: String,
+    tags_addr: String,
+    dtb_addr: String,
+    name: String,
+    cmdline: String,
+    bootconfig_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.version = 4;
+
+    // Load vendor ramdisk and dtb files
+    let vendor_ramdisk_data = std::fs::read(vendor_ramdisk_file)?;
+    let dtb_data = if let Some(dtb_file) = dtb_file {
+        std::fs::read(dtb_file)?
+    } else {
+        Vec::new()
     };
 
-    header.kernel_size = kernel_data.len() as u32;
-    header.ramdisk_size = ramdisk_data.len() as u32;
+    // The bootconfig is stored with its trailer, as the bootloader expects it
+    let bootconfig_data = if let Some(bootconfig_file) = bootconfig_file {
+        Bootconfig::parse(&std::fs::read(bootconfig_file)?).to_bytes()
+    } else {
+        Vec::new()
+    };
 
-    // Write to output file
-    boot_file.save(output_boot_file, 4096)?;
-    let mut file = boot_file.get_file();
+    // A single vendor ramdisk is described as one platform fragment
+    let fragment = VendorRamdiskFragment {
+        entry: VendorRamdiskTableEntry {
+            ramdisk_size: vendor_ramdisk_data.len() as u32,
+            ramdisk_offset: 0,
+            ramdisk_type: VendorRamdiskType::Platform.into(),
+            ramdisk_name: VendorRamdiskName::from(String::new()),
+            board_id: [0; 16],
+        },
+        data: vendor_ramdisk_data,
+    };
 
-    file.write_all(&kernel_data)?;
-    file.write_all(&ramdisk_data)?;
+    let header = VendorHeaderVersion4 {
+        magic: VendorBootMagic::default(),
+        header_version: 4,
+        page_size,
+        kernel_addr: AddressU32::from(kernel_addr),
+        ramdisk_addr: AddressU32::from(ramdisk_addr),
+        vendor_ramdisk_size: 0,
+        cmdline: VendorCmdline::from(cmdline),
+        tags_addr: AddressU32::from(tags_addr),
+        name: Name::from(name),
+        header_size: VENDOR_HEADER_SIZE_V4,
+        dtb_size: 0,
+        dtb_addr: AddressU64::from(dtb_addr),
+        vendor_ramdisk_table_size: 0,
+        vendor_ramdisk_table_entry_num: 0,
+        vendor_ramdisk_table_entry_size: VENDOR_RAMDISK_TABLE_ENTRY_SIZE,
+        bootconfig_size: 0,
+    };
+
+    vendor_boot_file.header = VendorHeader::V4(header);
+
+    // Write header, sections and vendor ramdisk table to output file
+    vendor_boot_file.save_v4(
+        output_vendor_boot_file,
+        &[fragment],
+        &dtb_data,
+        &bootconfig_data,
+    )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    /// A vendor boot v4 image and the sections it was built from.
+    pub(crate) struct VendorBootFixture {
+        pub image: PathBuf,
+        pub vendor_ramdisk: Vec<u8>,
+        pub dtb: Vec<u8>,
+        pub bootconfig: String,
+    }
+
+    /// Creates `vendor_boot.img` in `dir`, with a single vendor ramdisk
+    /// fragment, a DTB and a bootconfig.
+    pub(crate) fn vendor_boot_v4(dir: &Path) -> VendorBootFixture {
+        let fixture = VendorBootFixture {
+            image: dir.join("vendor_boot.img"),
+            vendor_ramdisk: vec![1; 5000],
+            dtb: vec![0xd0; 3000],
+            bootconfig: "androidboot.hardware=qcom\n".to_string(),
+        };
+        fs::write(dir.join("ramdisk"), &fixture.vendor_ramdisk).unwrap();
+        fs::write(dir.join("dtb"), &fixture.dtb).unwrap();
+        fs::write(dir.join("bootconfig"), &fixture.bootconfig).unwrap();
+        create_vendor_v4(
+            fixture.image.clone(),
+            dir.join("ramdisk"),
+            Some(dir.join("dtb")),
+            4096,
+            "0x00008000".into(),
+            "0x01000000".into(),
+            "0x00000100".into(),
+            "0x01f00000".into(),
+            String::new(),
+            String::new(),
+            Some(dir.join("bootconfig")),
+        )
+        .unwrap();
+        fixture
+    }
+}
@@ -0,0 +1,643 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::output::{write_atomically, write_file_atomically};
+use crate::commands::signature::print_dropped_signatures;
+use crate::errors::AbootCrafterError;
+use crate::headers::android::{AndroidBootFile, BootComponent};
+use crate::headers::dtbo::{DtboCompression, DtboImage};
+use crate::headers::dts::{self, ValueType};
+use crate::headers::fdt::{split_fdts, Fdt, FdtHeader, FdtNode};
+use crate::headers::fields::{AndroidBootMagic, VendorBootMagic};
+use crate::headers::kernel::{append_dtbs, split_appended_dtbs};
+use crate::headers::mtk::MtkHeader;
+use crate::headers::qcdt::DtTable;
+use crate::headers::vendor::VendorBootFile;
+
+/// Where the DTBs of a file were read from.
+#[allow(clippy::large_enum_variant)]
+enum DtbLocation {
+    /// A `.dtb` file, a table file, a DTBO image or a kernel
+    File,
+    /// The DTB section of a boot image
+    BootDtb(AndroidBootFile),
+    /// The kernel section of a boot image, with its MediaTek header if any
+    BootKernel(AndroidBootFile, Option<MtkHeader>),
+    /// The DTB section of a vendor boot image
+    VendorBootDtb(VendorBootFile),
+}
+
+/// How the DTBs are stored at their location.
+enum DtbLayout {
+    /// Concatenated blobs
+    Blobs,
+    /// A QCDT or DTBH table, whose entries may share a DTB
+    Table(DtTable),
+    /// A DTBO image, whose entries may share an overlay
+    Dtbo(DtboImage),
+    /// Blobs appended to this kernel
+    Appended(Vec<u8>),
+}
+
+/// The DTBs of a file, along with what is needed to write them back.
+struct DtbSource {
+    location: DtbLocation,
+    layout: DtbLayout,
+    dtbs: Vec<Vec<u8>>,
+}
+
+/// Reads the DTBs of a standalone `.dtb` file, of a kernel with appended
+/// DTBs, or of a boot or vendor boot image. Boot images without a DTB
+/// section give the DTBs appended to their kernel. Concatenated blobs and
+/// QCDT or DTBH tables give one DTB per blob or distinct table entry, and
+/// DTBO images one overlay per distinct entry.
+fn read_dtbs(input_file: &Path) -> Result<DtbSource, AbootCrafterError> {
+    let mut data = fs::read(input_file)?;
+    let mut location = DtbLocation::File;
+    if data.starts_with(&AndroidBootMagic::default().0) {
+        let boot_file = AndroidBootFile::open(input_file)?;
+        data = if boot_file.header.supports(BootComponent::Dtb) {
+            boot_file.get_component(BootComponent::Dtb)?
+        } else {
+            Vec::new()
+        };
+        location = if data.is_empty() {
+            let kernel = boot_file.get_component(BootComponent::Kernel)?;
+            let mtk_header = match MtkHeader::split(&kernel) {
+                Some((header, payload)) => {
+                    data = payload.to_vec();
+                    Some(header)
+                }
+                None => {
+                    data = kernel;
+                    None
+                }
+            };
+            DtbLocation::BootKernel(boot_file, mtk_header)
+        } else {
+            DtbLocation::BootDtb(boot_file)
+        };
+    } else if data.starts_with(&VendorBootMagic::default().0) {
+        let vendor_boot_file = VendorBootFile::open(input_file)?;
+        data = vendor_boot_file.read_dtb()?;
+        location = DtbLocation::VendorBootDtb(vendor_boot_file);
+    }
+
+    if DtTable::is_dt_table(&data) {
+        let table = DtTable::parse(&data)?;
+        let mut offsets = Vec::new();
+        let mut dtbs = Vec::new();
+        for entry in &table.entries {
+            if !offsets.contains(&entry.offset) {
+                offsets.push(entry.offset);
+                dtbs.push(table.dtb(&data, entry)?.to_vec());
+            }
+        }
+        return Ok(DtbSource {
+            location,
+            layout: DtbLayout::Table(table),
+            dtbs,
+        });
+    }
+
+    if DtboImage::is_dtbo(&data) {
+        let image = DtboImage::parse(&data)?;
+        let mut offsets = Vec::new();
+        let mut dtbs = Vec::new();
+        for entry in &image.entries {
+            if image.compression(entry) != DtboCompression::None {
+                return Err(AbootCrafterError::ConfigError(format!(
+                    "Compressed DTBO entries are not supported in {}",
+                    input_file.display()
+                )));
+            }
+            if !offsets.contains(&entry.offset) {
+                offsets.push(entry.offset);
+                dtbs.push(image.overlay(&data, entry)?.to_vec());
+            }
+        }
+        return Ok(DtbSource {
+            location,
+            layout: DtbLayout::Dtbo(image),
+            dtbs,
+        });
+    }
+
+    let (layout, dtbs) = match split_fdts(&data) {
+        Some(blobs) => (DtbLayout::Blobs, blobs),
+        None => {
+            let (kernel, blobs) = split_appended_dtbs(&data);
+            (DtbLayout::Appended(kernel.to_vec()), blobs)
+        }
+    };
+    if dtbs.is_empty() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No DTB found in {}",
+            input_file.display()
+        )));
+    }
+    let dtbs = dtbs.into_iter().map(<[u8]>::to_vec).collect();
+    Ok(DtbSource {
+        location,
+        layout,
+        dtbs,
+    })
+}
+
+/// Writes the DTBs back where they were read from, in the same layout.
+/// Boot images get their section sizes updated, and an `id` that matched
+/// the components is recomputed.
+fn write_dtbs(source: DtbSource, output_file: &Path) -> Result<(), AbootCrafterError> {
+    let data = match source.layout {
+        DtbLayout::Blobs => source.dtbs.concat(),
+        DtbLayout::Table(table) => {
+            let mut offsets: Vec<u32> = Vec::new();
+            let mut entries = Vec::with_capacity(table.entries.len());
+            for entry in &table.entries {
+                let index = shared_index(&mut offsets, entry.offset);
+                entries.push((entry.id.clone(), source.dtbs[index].clone()));
+            }
+            DtTable::build(table.format, table.version, table.page_size(), &entries)?
+        }
+        DtbLayout::Dtbo(image) => {
+            let mut offsets: Vec<u32> = Vec::new();
+            let mut entries = Vec::with_capacity(image.entries.len());
+            for entry in &image.entries {
+                let index = shared_index(&mut offsets, entry.offset);
+                entries.push((entry.clone(), source.dtbs[index].clone()));
+            }
+            DtboImage::build(image.version, image.page_size, &entries)?
+        }
+        DtbLayout::Appended(kernel) => append_dtbs(&kernel, &source.dtbs),
+    };
+
+    let (mut boot_file, component, data) = match source.location {
+        DtbLocation::File => {
+            write_file_atomically(output_file, &data)?;
+            return Ok(());
+        }
+        DtbLocation::VendorBootDtb(mut vendor_boot_file) => {
+            let signatures = vendor_boot_file.signature_trailers();
+            write_atomically(output_file, |temp_file| {
+                vendor_boot_file.save_with_dtb(temp_file, &data)
+            })?;
+            print_dropped_signatures(&signatures, &vendor_boot_file.signature_trailers());
+            return Ok(());
+        }
+        DtbLocation::BootDtb(boot_file) => (boot_file, BootComponent::Dtb, data),
+        DtbLocation::BootKernel(boot_file, mtk_header) => {
+            let data = match mtk_header {
+                Some(header) => header.wrap(&data)?,
+                None => data,
+            };
+            (boot_file, BootComponent::Kernel, data)
+        }
+    };
+    let id_hash = boot_file.verify_id()?;
+    let signatures = boot_file.signature_trailers();
+    boot_file.replace_component(component, data)?;
+    if let Some(id_hash) = id_hash {
+        boot_file.update_id(id_hash)?;
+    }
+    boot_file.drop_signature_trailers();
+    write_atomically(output_file, |temp_file| boot_file.save_image(temp_file))?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Index of the DTB stored at `offset` among the distinct offsets seen so
+/// far, adding it if it is new.
+fn shared_index(offsets: &mut Vec<u32>, offset: u32) -> usize {
+    match offsets.iter().position(|&seen| seen == offset) {
+        Some(index) => index,
+        None => {
+            offsets.push(offset);
+            offsets.len() - 1
+        }
+    }
+}
+
+/// Picks the DTB at `index`, which is required when there are several.
+fn select_dtb(
+    input_file: &Path,
+    dtbs: &[Vec<u8>],
+    index: Option<usize>,
+) -> Result<usize, AbootCrafterError> {
+    match index {
+        Some(index) if index < dtbs.len() => Ok(index),
+        Some(index) => Err(AbootCrafterError::ConfigError(format!(
+            "No DTB {} in {}, it holds {}",
+            index,
+            input_file.display(),
+            dtbs.len()
+        ))),
+        None if dtbs.len() == 1 => Ok(0),
+        None => Err(AbootCrafterError::ConfigError(format!(
+            "{} holds {} DTBs, select one with --index",
+            input_file.display(),
+            dtbs.len()
+        ))),
+    }
+}
+
+/// Applies `edit` to one DTB of a file, and writes the file to
+/// `output_file`, or back in place.
+fn edit_dtb(
+    input_file: &Path,
+    index: Option<usize>,
+    output_file: Option<PathBuf>,
+    edit: impl FnOnce(&mut Fdt) -> Result<(), AbootCrafterError>,
+) -> Result<(), AbootCrafterError> {
+    let mut source = read_dtbs(input_file)?;
+    let index = select_dtb(input_file, &source.dtbs, index)?;
+    let mut fdt = Fdt::parse(&source.dtbs[index])?;
+    edit(&mut fdt)?;
+    dts::prune_symbols(&mut fdt.root);
+    source.dtbs[index] = fdt.to_bytes()?;
+    write_dtbs(source, output_file.as_deref().unwrap_or(input_file))
+}
+
+/// Splits an absolute node path into the parent path and the node name.
+fn split_node_path(node_path: &str) -> Result<(&str, &str), AbootCrafterError> {
+    let node_path = node_path.trim_end_matches('/');
+    match node_path.rsplit_once('/') {
+        Some((parent, name)) if !name.is_empty() => Ok((parent, name)),
+        _ if node_path.is_empty() => Err(AbootCrafterError::ConfigError(
+            "The root node cannot be added or removed".to_string(),
+        )),
+        _ => Err(AbootCrafterError::ConfigError(format!(
+            "Node paths start with /: {}",
+            node_path
+        ))),
+    }
+}
+
+fn check_node_path(node_path: &str) -> Result<(), AbootCrafterError> {
+    if node_path.starts_with('/') {
+        Ok(())
+    } else {
+        Err(AbootCrafterError::ConfigError(format!(
+            "Node paths start with /: {}",
+            node_path
+        )))
+    }
+}
+
+fn node_not_found(node_path: &str) -> AbootCrafterError {
+    AbootCrafterError::ConfigError(format!("Node {} not found", node_path))
+}
+
+/// Prints the header fields, the memory reservations and the node tree of
+/// a device tree.
+pub fn print_fdt(fdt: &Fdt, title: &str) {
+    println!("[{}]", title);
+    println!(
+        "FDT Version: {} (last compatible {})",
+        fdt.header.version, fdt.header.last_comp_version
+    );
+    println!("Size: {}", fdt.header.totalsize);
+    println!("Boot CPU: {}", fdt.header.boot_cpuid_phys);
+    println!("Model: {}", fdt.model().unwrap_or_default());
+    println!("Compatible: {}", fdt.compatible().join(", "));
+    println!("Nodes: {}", fdt.root.node_count());
+    println!("Memory Reservations: {}", fdt.reservations.len());
+    for reservation in &fdt.reservations {
+        println!(
+            "  0x{:016x} size 0x{:x}",
+            reservation.address, reservation.size
+        );
+    }
+    println!("[Nodes]");
+    print!("{}", dts::decompile(fdt));
+}
+
+/// Displays the header, model, compatible strings and node tree of every
+/// DTB in a file, with property values decoded heuristically.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+pub fn info(input_file: &Path) -> Result<(), AbootCrafterError> {
+    let dtbs = read_dtbs(input_file)?.dtbs;
+    for (index, dtb) in dtbs.iter().enumerate() {
+        let title = if dtbs.len() == 1 {
+            "Device Tree".to_string()
+        } else {
+            format!("Device Tree {}", index)
+        };
+        print_fdt(&Fdt::parse(dtb)?, &title);
+    }
+    Ok(())
+}
+
+/// Decompiles a DTB to device tree source. Labels and references to them
+/// are restored when the DTB has a `__symbols__` node.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `output_file` - Optional DTS file to write, the source is printed if omitted.
+/// * `index` - DTB to decompile, required if the input holds several.
+pub fn decompile(
+    input_file: &Path,
+    output_file: Option<PathBuf>,
+    index: Option<usize>,
+) -> Result<(), AbootCrafterError> {
+    let dtbs = read_dtbs(input_file)?.dtbs;
+    let dtb = &dtbs[select_dtb(input_file, &dtbs, index)?];
+
+    let source = dts::decompile(&Fdt::parse(dtb)?);
+    match output_file {
+        Some(output_file) => fs::write(output_file, source)?,
+        None => print!("{}", source),
+    }
+    Ok(())
+}
+
+/// Compiles device tree source to a DTB, without needing `dtc`.
+///
+/// # Arguments
+///
+/// * `input_file` - DTS file, already run through `cpp` if it uses `#include` or macros.
+/// * `output_file` - DTB file to write.
+/// * `symbols` - Whether to generate the `__symbols__` node from the labels.
+pub fn compile(
+    input_file: &Path,
+    output_file: &Path,
+    symbols: bool,
+) -> Result<(), AbootCrafterError> {
+    let source = fs::read_to_string(input_file)?;
+    let fdt = dts::compile(&source, symbols).map_err(|err| {
+        AbootCrafterError::ConfigError(format!("{}: {}", input_file.display(), err))
+    })?;
+    fs::write(output_file, fdt.to_bytes()?)?;
+    Ok(())
+}
+
+/// Removes a node and its subtree from a DTB. Labels of the removed nodes
+/// are dropped from `__symbols__`.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `node_path` - Absolute path of the node, `/soc/serial@78b0000` for instance.
+/// * `index` - DTB to edit, required if the input holds several.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn remove(
+    input_file: &Path,
+    node_path: &str,
+    index: Option<usize>,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let (parent_path, name) = split_node_path(node_path)?;
+    edit_dtb(input_file, index, output_file, |fdt| {
+        fdt.root
+            .node_mut(parent_path)
+            .and_then(|parent| parent.remove_child(name))
+            .ok_or_else(|| node_not_found(node_path))?;
+        Ok(())
+    })
+}
+
+/// Adds a node to a DTB, with the given properties. The parent node must
+/// exist.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `node_path` - Absolute path of the new node.
+/// * `properties` - Properties in DTS syntax: `name=<0x1 0x2>`, `name="str"`, `name=[01 02]` or `name`.
+/// * `index` - DTB to edit, required if the input holds several.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn add(
+    input_file: &Path,
+    node_path: &str,
+    properties: Vec<String>,
+    index: Option<usize>,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let (parent_path, name) = split_node_path(node_path)?;
+    let mut node = FdtNode {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for property in &properties {
+        let property = dts::parse_property(property).map_err(|err| {
+            AbootCrafterError::ConfigError(format!("Invalid property {}: {}", property, err))
+        })?;
+        node.set_property(&property.name, property.value);
+    }
+
+    edit_dtb(input_file, index, output_file, |fdt| {
+        let parent = fdt
+            .root
+            .node_mut(parent_path)
+            .ok_or_else(|| node_not_found(parent_path))?;
+        if parent.child(name).is_some() {
+            return Err(AbootCrafterError::ConfigError(format!(
+                "Node {} already exists",
+                node_path
+            )));
+        }
+        parent.children.push(node);
+        Ok(())
+    })
+}
+
+/// Replaces the properties and subnodes of a node with those of the root
+/// node of another device tree, given as a DTB or as DTS source. The node
+/// keeps its name, and its phandle if the replacement has none, so
+/// references to it stay valid.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `node_path` - Absolute path of the node, `/` for the whole tree.
+/// * `replacement_file` - DTB or DTS file whose root node replaces the node.
+/// * `index` - DTB to edit, required if the input holds several.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn replace(
+    input_file: &Path,
+    node_path: &str,
+    replacement_file: &Path,
+    index: Option<usize>,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let data = fs::read(replacement_file)?;
+    let replacement = if FdtHeader::total_size(&data).is_some() {
+        Fdt::parse(&data)?
+    } else {
+        dts::compile(&String::from_utf8_lossy(&data), false).map_err(|err| {
+            AbootCrafterError::ConfigError(format!("{}: {}", replacement_file.display(), err))
+        })?
+    };
+
+    edit_dtb(input_file, index, output_file, |fdt| {
+        let node = fdt
+            .root
+            .node_mut(node_path)
+            .ok_or_else(|| node_not_found(node_path))?;
+        let mut properties = replacement.root.properties;
+        for name in ["phandle", "linux,phandle"] {
+            if let Some(phandle) = node.property(name) {
+                if !properties.iter().any(|property| property.name == name) {
+                    properties.push(phandle.clone());
+                }
+            }
+        }
+        node.properties = properties;
+        node.children = replacement.root.children;
+        Ok(())
+    })
+}
+
+/// Prints the value of a property, like `fdtget` does.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `node_path` - Absolute path of the node, `/` for the root node.
+/// * `property` - Name of the property.
+/// * `value_type` - How to print the value.
+/// * `index` - DTB to read, required if the input holds several.
+pub fn get(
+    input_file: &Path,
+    node_path: &str,
+    property: &str,
+    value_type: ValueType,
+    index: Option<usize>,
+) -> Result<(), AbootCrafterError> {
+    check_node_path(node_path)?;
+    let dtbs = read_dtbs(input_file)?.dtbs;
+    let fdt = Fdt::parse(&dtbs[select_dtb(input_file, &dtbs, index)?])?;
+    let node = fdt
+        .root
+        .node(node_path)
+        .ok_or_else(|| node_not_found(node_path))?;
+    let property = node.property(property).ok_or_else(|| {
+        AbootCrafterError::ConfigError(format!("Property {} not found in {}", property, node_path))
+    })?;
+    println!(
+        "{}",
+        value_type
+            .format(property)
+            .map_err(AbootCrafterError::ConfigError)?
+    );
+    Ok(())
+}
+
+/// Sets the value of a property, like `fdtput` does, adding it if the node
+/// does not have it.
+///
+/// # Arguments
+///
+/// * `input_file` - A `.dtb` file, a kernel with appended DTBs, or a boot or vendor boot image.
+/// * `node_path` - Absolute path of the node, `/` for the root node.
+/// * `property` - Name of the property.
+/// * `values` - The value, as words parsed according to `value_type`. Empty for an empty property.
+/// * `value_type` - How to parse the value.
+/// * `create` - Add the node, and its missing parents, if it does not exist.
+/// * `index` - DTB to edit, required if the input holds several.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+#[allow(clippy::too_many_arguments)]
+pub fn set(
+    input_file: &Path,
+    node_path: &str,
+    property: &str,
+    values: Vec<String>,
+    value_type: ValueType,
+    create: bool,
+    index: Option<usize>,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    check_node_path(node_path)?;
+    let value = value_type.parse(&values).map_err(|err| {
+        AbootCrafterError::ConfigError(format!("Invalid value for {}: {}", property, err))
+    })?;
+
+    edit_dtb(input_file, index, output_file, |fdt| {
+        let node = if create {
+            fdt.root.create_node(node_path)
+        } else {
+            fdt.root
+                .node_mut(node_path)
+                .ok_or_else(|| node_not_found(node_path))?
+        };
+        node.set_property(property, value);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create::create_v2;
+    use crate::headers::android::AndroidHeader;
+    use crate::headers::id::IdHash;
+    use crate::headers::layout::BootImageLayout;
+
+    #[test]
+    fn set_in_appended_dtb_moves_recovery_dtbo() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("boot.img");
+        let dtb = dts::compile("/dts-v1/;\n/ {\n\tmodel = \"A\";\n};\n", false)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let dtbo = vec![0xd7; 100];
+        fs::write(dir.path().join("kernel"), [vec![1; 1900], dtb].concat()).unwrap();
+        fs::write(dir.path().join("ramdisk"), [2; 100]).unwrap();
+        fs::write(dir.path().join("dtbo"), &dtbo).unwrap();
+        create_v2(
+            image.clone(),
+            dir.path().join("kernel"),
+            dir.path().join("ramdisk"),
+            None,
+            Some(dir.path().join("dtbo")),
+            None,
+            2048,
+            "0x00008000".into(),
+            "0x01000000".into(),
+            "0x00000000".into(),
+            "0x00000100".into(),
+            String::new(),
+            String::new(),
+            String::new(),
+            None,
+            IdHash::default(),
+            String::new(),
+            "0x0000000000000000".into(),
+            Vec::new(),
+            None,
+        )
+        .unwrap();
+
+        set(
+            &image,
+            "/",
+            "model",
+            vec!["B".repeat(1000)],
+            ValueType::String,
+            false,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let boot_file = AndroidBootFile::open(&image).unwrap();
+        let layout = BootImageLayout::new(&boot_file.header);
+        let section = layout.section(BootComponent::RecoveryDtbo).unwrap();
+        // The kernel grew past its first page, pushing recovery_dtbo back
+        assert_eq!(section.offset, 8192);
+        let AndroidHeader::V2(header) = &boot_file.header else {
+            unreachable!()
+        };
+        assert_eq!(header.recovery_dtbo_offset.0, section.offset.to_le_bytes());
+        assert_eq!(
+            boot_file
+                .get_component(BootComponent::RecoveryDtbo)
+                .unwrap(),
+            dtbo
+        );
+    }
+}
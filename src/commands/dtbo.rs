@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::AbootCrafterError;
+use crate::headers::android::{AndroidBootFile, BootComponent};
+use crate::headers::dtbo::{DtboConfig, DtboImage, DTBO_FILE_NAME};
+use crate::headers::fields::AndroidBootMagic;
+
+/// Reads a DTBO image, either from a raw `dtbo.img` file or from the
+/// recovery DTBO section of a boot image.
+fn read_dtbo(input_file: &Path) -> Result<(DtboImage, Vec<u8>), AbootCrafterError> {
+    let mut data = fs::read(input_file)?;
+    if data.starts_with(&AndroidBootMagic::default().0) {
+        let boot_file = AndroidBootFile::open(input_file)?;
+        data = if boot_file.header.supports(BootComponent::RecoveryDtbo) {
+            boot_file.get_component(BootComponent::RecoveryDtbo)?
+        } else {
+            Vec::new()
+        };
+    }
+
+    if !DtboImage::is_dtbo(&data) {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No DTBO image found in {}",
+            input_file.display()
+        )));
+    }
+    Ok((DtboImage::parse(&data)?, data))
+}
+
+/// Prints the entries of a DTBO image.
+pub fn print_dtbo(image: &DtboImage) {
+    println!("[DTBO Table]");
+    println!("Version: {}", image.version);
+    println!("Page Size: {}", image.page_size);
+    println!("Entries: {}", image.entries.len());
+    let custom_names = DtboImage::custom_names(image.version);
+    for (index, entry) in image.entries.iter().enumerate() {
+        println!("Entry {}:", index);
+        println!("  id: 0x{:08x}", entry.id);
+        println!("  rev: 0x{:08x}", entry.rev);
+        for (name, word) in custom_names.iter().zip(&entry.custom) {
+            println!("  {}: 0x{:08x}", name, word);
+        }
+        if image.version > 0 {
+            println!("  Compression: {}", image.compression(entry));
+        }
+        println!("  Offset: {}", entry.offset);
+        println!("  Size: {}", entry.size);
+    }
+}
+
+/// Lists the entries of a DTBO image.
+///
+/// # Arguments
+///
+/// * `input_file` - A DTBO image, or a boot image holding one as its recovery DTBO.
+pub fn list(input_file: &Path) -> Result<(), AbootCrafterError> {
+    let (image, _) = read_dtbo(input_file)?;
+    print_dtbo(&image);
+    Ok(())
+}
+
+/// Extracts every overlay of a DTBO image, as stored, along with a
+/// `dtbo.cfg` file describing the image so `create` can rebuild it. Entries
+/// sharing an overlay share the file.
+///
+/// # Arguments
+///
+/// * `input_file` - A DTBO image, or a boot image holding one as its recovery DTBO.
+/// * `output_dir` - Optional path to the output directory.
+pub fn extract(input_file: &Path, output_dir: Option<PathBuf>) -> Result<(), AbootCrafterError> {
+    let (image, data) = read_dtbo(input_file)?;
+
+    let directory_name = if let Some(output_dir) = output_dir {
+        output_dir
+    } else {
+        let file_name = input_file.file_name().unwrap();
+        PathBuf::from(format!("{}_dtbos", file_name.to_str().unwrap()))
+    };
+    fs::create_dir_all(&directory_name)?;
+
+    let mut files: Vec<(u32, String)> = Vec::new();
+    let mut config = DtboConfig {
+        version: image.version,
+        page_size: image.page_size,
+        entries: Vec::new(),
+    };
+    for entry in &image.entries {
+        let file_name = match files.iter().find(|(offset, _)| *offset == entry.offset) {
+            Some((_, file_name)) => file_name.clone(),
+            None => {
+                let file_name = format!("dtbo{:02}.dtbo", files.len());
+                fs::write(
+                    directory_name.join(&file_name),
+                    image.overlay(&data, entry)?,
+                )?;
+                files.push((entry.offset, file_name.clone()));
+                file_name
+            }
+        };
+        config.entries.push((file_name, entry.clone()));
+    }
+    fs::write(directory_name.join(DTBO_FILE_NAME), config.to_string())?;
+
+    Ok(())
+}
+
+/// Builds a DTBO image from a set of overlays and their ids, like `mkdtimg`
+/// does.
+///
+/// # Arguments
+///
+/// * `input_dir` - Directory holding the overlay files.
+/// * `output_file` - Path to the image to write.
+/// * `config_file` - Optional image description, defaults to `dtbo.cfg` in `input_dir`.
+pub fn create(
+    input_dir: &Path,
+    output_file: &PathBuf,
+    config_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let config_file = config_file.unwrap_or_else(|| input_dir.join(DTBO_FILE_NAME));
+    let config = DtboConfig::parse(&fs::read_to_string(&config_file)?)
+        .map_err(AbootCrafterError::ConfigError)?;
+    if config.entries.is_empty() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No entries in {}",
+            config_file.display()
+        )));
+    }
+
+    let mut entries = Vec::with_capacity(config.entries.len());
+    for (file_name, entry) in config.entries {
+        entries.push((entry, fs::read(input_dir.join(file_name))?));
+    }
+    let image = DtboImage::build(config.version, config.page_size, &entries)
+        .map_err(|err| AbootCrafterError::ConfigError(err.to_string()))?;
+    fs::write(output_file, image)?;
+
+    Ok(())
+}
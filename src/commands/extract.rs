@@ -1,16 +1,24 @@
 use std::fs;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
 
 use crate::errors::AbootCrafterError;
-use crate::headers::android::{AndroidBootFile, AndroidHeader, PAGE_SIZE_V3};
+use crate::headers::android::AndroidBootFile;
+use crate::headers::bootconfig::Bootconfig;
+use crate::headers::metadata::{BootImageMetadata, METADATA_FILE_NAME};
+use crate::headers::mtk::{MtkHeader, MTK_HEADER_FILE_SUFFIX, MTK_HEADER_SIZE};
+use crate::headers::trailer::TRAILER_FILE_NAME;
+use crate::headers::vendor::VendorBootFile;
 
-/// Extracts components from an Android boot image file to a specified output directory.
+/// Extracts components from an Android boot image file to a specified output directory,
+/// along with a metadata file holding the header fields and a `trailer` file
+/// holding any data after the last section.
 ///
 /// # Arguments
 ///
 /// * `input_boot_file` - Path to the input boot image file.
 /// * `output_dir` - Optional path to the output directory where components will be extracted.
+/// * `strip_mtk_headers` - Write MediaTek component headers to separate files
+///   instead of leaving them in front of the components.
 ///
 /// # Returns
 ///
@@ -18,11 +26,11 @@ use crate::headers::android::{AndroidBootFile, AndroidHeader, PAGE_SIZE_V3};
 pub fn extract(
     input_boot_file: &PathBuf,
     output_dir: Option<PathBuf>,
+    strip_mtk_headers: bool,
 ) -> Result<(), AbootCrafterError> {
     // Load the Android boot file
     let mut boot_file = AndroidBootFile::default();
     boot_file.load(input_boot_file)?;
-    let mut file: &fs::File = boot_file.get_file();
 
     // Determine the output directory name
     let directory_name = if let Some(output_dir) = output_dir {
@@ -33,71 +41,96 @@ pub fn extract(
     };
     fs::create_dir_all(&directory_name)?;
 
-    // Define paths for extracted components
-    let kernel_path = directory_name.join("kernel");
-    let ramdisk_path = directory_name.join("ramdisk");
-    let second_path = directory_name.join("second");
+    // Extract every component the header version supports, if it exists
+    for &component in boot_file.header.supported_components() {
+        let buf = boot_file.get_component(component)?;
+        if buf.is_empty() {
+            continue;
+        }
 
-    // Get the file and component sizes
-    let page_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.page_size,
-        AndroidHeader::V1(ref header) => header.page_size,
-        AndroidHeader::V2(ref header) => header.page_size,
-        AndroidHeader::V3(_) => PAGE_SIZE_V3,
-        AndroidHeader::V4(_) => PAGE_SIZE_V3,
-    };
-    let kernel_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.kernel_size,
-        AndroidHeader::V1(ref header) => header.kernel_size,
-        AndroidHeader::V2(ref header) => header.kernel_size,
-        AndroidHeader::V3(ref header) => header.kernel_size,
-        AndroidHeader::V4(ref header) => header.kernel_size,
-    };
-    let ramdisk_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.ramdisk_size,
-        AndroidHeader::V1(ref header) => header.ramdisk_size,
-        AndroidHeader::V2(ref header) => header.ramdisk_size,
-        AndroidHeader::V3(ref header) => header.ramdisk_size,
-        AndroidHeader::V4(ref header) => header.ramdisk_size,
-    };
-    let second_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.second_size,
-        AndroidHeader::V1(ref header) => header.second_size,
-        AndroidHeader::V2(ref header) => header.second_size,
-        AndroidHeader::V3(_) => 0,
-        AndroidHeader::V4(_) => 0,
+        // `repack` puts stripped headers back from their own file
+        match MtkHeader::split(&buf).filter(|_| strip_mtk_headers) {
+            Some((_, payload)) => {
+                let header_file = format!("{}{}", component.name(), MTK_HEADER_FILE_SUFFIX);
+                fs::write(directory_name.join(header_file), &buf[..MTK_HEADER_SIZE])?;
+                fs::write(directory_name.join(component.name()), payload)?;
+            }
+            None => fs::write(directory_name.join(component.name()), buf)?,
+        }
+    }
+
+    // Keep the data after the last section so `repack` can append it again
+    if !boot_file.trailers.is_empty() {
+        let trailers: Vec<u8> = boot_file
+            .trailers
+            .iter()
+            .flat_map(|trailer| trailer.data.iter().copied())
+            .collect();
+        fs::write(directory_name.join(TRAILER_FILE_NAME), trailers)?;
+    }
+
+    // Write the remaining header fields so the directory describes the whole image
+    let mut metadata = BootImageMetadata::from_header(&boot_file.header);
+    // Remember how the id was computed so `repack` can refresh it after edits
+    if let Some(id_hash) = boot_file.verify_id()? {
+        metadata.set("id_hash", id_hash.name().to_string());
+    }
+    fs::write(
+        directory_name.join(METADATA_FILE_NAME),
+        metadata.to_string(),
+    )?;
+
+    Ok(())
+}
+
+/// Extracts components from an Android vendor boot image file to a specified output directory.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - Path to the input vendor boot image file.
+/// * `output_dir` - Optional path to the output directory where components will be extracted.
+///
+/// # Returns
+///
+/// * `Result<(), AbootCrafterError>` - Ok if successful, or an error if the extraction fails.
+pub fn extract_vendor(
+    input_vendor_boot_file: &PathBuf,
+    output_dir: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    // Load the Android vendor boot file
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.load(input_vendor_boot_file)?;
+
+    // Determine the output directory name
+    let directory_name = if let Some(output_dir) = output_dir {
+        output_dir
+    } else {
+        let file_name = input_vendor_boot_file.file_name().unwrap();
+        PathBuf::from(format!("{}_extracted", file_name.to_str().unwrap()))
     };
+    fs::create_dir_all(&directory_name)?;
 
-    // Calculate offsets and pages for components
-    let header_pages = 1;
-    let kernel_offset = page_size * header_pages;
-    let kernel_pages = kernel_size.div_ceil(page_size);
-    let ramdisk_offset = page_size * (header_pages + kernel_pages);
-    let ramdisk_pages = ramdisk_size.div_ceil(page_size);
-    let second_offset = page_size * (header_pages + kernel_pages + ramdisk_pages);
+    // Define paths for extracted components
+    let vendor_ramdisk_path = directory_name.join("vendor_ramdisk");
+    let dtb_path = directory_name.join("dtb");
+    let bootconfig_path = directory_name.join("bootconfig");
 
-    // Extract the kernel component if it exists
-    if kernel_size > 0 {
-        let mut kernel_buf = vec![0; kernel_size as usize];
-        file.seek(SeekFrom::Start(kernel_offset as u64))?;
-        file.read_exact(&mut kernel_buf)?;
-        fs::write(&kernel_path, kernel_buf)?;
+    // Extract the vendor ramdisk component if it exists
+    let vendor_ramdisk_buf = vendor_boot_file.read_vendor_ramdisk()?;
+    if !vendor_ramdisk_buf.is_empty() {
+        fs::write(&vendor_ramdisk_path, vendor_ramdisk_buf)?;
     }
 
-    // Extract the ramdisk component if it exists
-    if ramdisk_size > 0 {
-        let mut ramdisk_buf = vec![0; ramdisk_size as usize];
-        file.seek(SeekFrom::Start(ramdisk_offset as u64))?;
-        file.read_exact(&mut ramdisk_buf)?;
-        fs::write(&ramdisk_path, ramdisk_buf)?;
+    // Extract the dtb component if it exists
+    let dtb_buf = vendor_boot_file.read_dtb()?;
+    if !dtb_buf.is_empty() {
+        fs::write(&dtb_path, dtb_buf)?;
     }
 
-    // Extract the second component if it exists
-    if second_size > 0 {
-        let mut second_buf = vec![0; second_size as usize];
-        file.seek(SeekFrom::Start(second_offset as u64))?;
-        file.read_exact(&mut second_buf)?;
-        fs::write(&second_path, second_buf)?;
+    // Extract the bootconfig parameters (without trailer) if they exist
+    let bootconfig = Bootconfig::parse(&vendor_boot_file.read_bootconfig()?);
+    if !bootconfig.params.is_empty() {
+        fs::write(&bootconfig_path, bootconfig.to_string())?;
     }
 
     Ok(())
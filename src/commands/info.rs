@@ -1,5 +1,19 @@
+use crate::commands::bootconfig::print_bootconfig;
+use crate::commands::dtbo::print_dtbo;
+use crate::commands::kernel::print_kernel_info;
+use crate::commands::qcdt::print_dt_table;
+use crate::commands::signature::{
+    print_avb_footer, print_boot_signature, print_vboot_signature, split_vboot_signature,
+};
+use crate::commands::vendor_ramdisk::print_vendor_ramdisk_table;
 use crate::errors::AbootCrafterError;
-use crate::headers::android::{AndroidBootFile, AndroidHeader};
+use crate::headers::android::{AndroidBootFile, AndroidHeader, BootComponent};
+use crate::headers::bootconfig::Bootconfig;
+use crate::headers::dtbo::DtboImage;
+use crate::headers::layout::BootImageLayout;
+use crate::headers::mtk::MtkHeader;
+use crate::headers::qcdt::DtTable;
+use crate::headers::vendor::{VendorBootFile, VendorHeader};
 use std::fs::File;
 use std::path::PathBuf;
 
@@ -40,11 +54,28 @@ pub fn info(input_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
             println!("OS Version: {}", header.os_version);
             println!("Product Name: {}", header.name);
             println!("Command Line Arguments: {}", header.cmdline);
-            println!("ID: {}", header.id);
+            println!("ID: {}", header.id.to_hex());
+            println!("Extra Command Line Arguments: {}", header.extra_cmdline);
+        }
+        AndroidHeader::V0Qcom(ref header) => {
+            println!("Magic: {}", header.magic);
+            println!("Kernel Size: {}", header.kernel_size);
+            println!("Kernel Address: {}", header.kernel_addr);
+            println!("Ramdisk Size: {}", header.ramdisk_size);
+            println!("Ramdisk Address: {}", header.ramdisk_addr);
+            println!("Second Size: {}", header.second_size);
+            println!("Second Address: {}", header.second_addr);
+            println!("Tags Address: {}", header.tags_addr);
+            println!("Page Size: {}", header.page_size);
+            println!("Header Variant: QCOM");
+            println!("DT Size: {}", header.dt_size);
+            println!("OS Version: {}", header.os_version);
+            println!("Product Name: {}", header.name);
+            println!("Command Line Arguments: {}", header.cmdline);
+            println!("ID: {}", header.id.to_hex());
             println!("Extra Command Line Arguments: {}", header.extra_cmdline);
         }
         AndroidHeader::V1(ref header) => {
-            println!("[Header]");
             println!("Magic: {}", header.magic);
             println!("Kernel Size: {}", header.kernel_size);
             println!("Kernel Address: {}", header.kernel_addr);
@@ -58,7 +89,7 @@ pub fn info(input_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
             println!("OS Version: {}", header.os_version);
             println!("Product Name: {}", header.name);
             println!("Command Line Arguments: {}", header.cmdline);
-            println!("ID: {}", header.id);
+            println!("ID: {}", header.id.to_hex());
             println!("Extra Command Line Arguments: {}", header.extra_cmdline);
             println!("Recovery DTBO Size: {}", header.recovery_dtbo_size);
             println!("Recovery DTBO Offset: {}", header.recovery_dtbo_offset);
@@ -78,7 +109,7 @@ pub fn info(input_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
             println!("OS Version: {}", header.os_version);
             println!("Product Name: {}", header.name);
             println!("Command Line Arguments: {}", header.cmdline);
-            println!("ID: {}", header.id);
+            println!("ID: {}", header.id.to_hex());
             println!("Extra Command Line Arguments: {}", header.extra_cmdline);
             println!("Recovery DTBO Size: {}", header.recovery_dtbo_size);
             println!("Recovery DTBO Offset: {}", header.recovery_dtbo_offset);
@@ -107,5 +138,171 @@ pub fn info(input_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
         }
     }
 
+    println!("[Components]");
+    for &component in boot_file.header.supported_components() {
+        println!(
+            "{}: size {}, offset {}, alignment {}",
+            component,
+            boot_file.header.component_size(component).unwrap_or(0),
+            boot_file.header.component_offset(component).unwrap_or(0),
+            boot_file.header.component_alignment(component)
+        );
+    }
+
+    let mut mtk_headers = Vec::new();
+    for &component in boot_file.header.supported_components() {
+        let data = boot_file.get_component(component)?;
+        if let Some((header, payload)) = MtkHeader::split(&data) {
+            mtk_headers.push((component, header, payload.len()));
+        }
+    }
+    if !mtk_headers.is_empty() {
+        println!("[MTK Headers]");
+        for (component, header, payload_size) in mtk_headers {
+            println!(
+                "{}: name {}, size {}{}",
+                component,
+                header.name(),
+                header.size,
+                if header.size as usize == payload_size {
+                    ""
+                } else {
+                    " (does not match the payload)"
+                }
+            );
+        }
+    }
+
+    let kernel = boot_file.get_component(BootComponent::Kernel)?;
+    if !kernel.is_empty() {
+        match MtkHeader::split(&kernel) {
+            Some((_, payload)) => print_kernel_info(payload),
+            None => print_kernel_info(&kernel),
+        }
+    }
+
+    if boot_file.header.supports(BootComponent::Dtb) {
+        let dtb = boot_file.get_component(BootComponent::Dtb)?;
+        if DtTable::is_dt_table(&dtb) {
+            print_dt_table(&DtTable::parse(&dtb)?);
+        }
+    }
+
+    if boot_file.header.supports(BootComponent::RecoveryDtbo) {
+        let recovery_dtbo = boot_file.get_component(BootComponent::RecoveryDtbo)?;
+        if DtboImage::is_dtbo(&recovery_dtbo) {
+            print_dtbo(&DtboImage::parse(&recovery_dtbo)?);
+        }
+    }
+
+    if boot_file.header.id().is_some() {
+        println!("[ID]");
+        match boot_file.verify_id()? {
+            Some(id_hash) => println!("ID Digest: {} (matches the components)", id_hash),
+            None => println!("ID Digest: does not match the components"),
+        }
+    }
+
+    if !boot_file.trailers.is_empty() {
+        println!("[Trailers]");
+        let mut offset = BootImageLayout::new(&boot_file.header).image_size();
+        for trailer in &boot_file.trailers {
+            println!(
+                "{}: size {}, offset {}",
+                trailer.kind,
+                trailer.data.len(),
+                offset
+            );
+            offset += trailer.data.len() as u64;
+        }
+    }
+
+    print_boot_signature(&boot_file)?;
+    if let Ok((_, Some(signature))) = split_vboot_signature(input_boot_file) {
+        print_vboot_signature(&signature)?;
+    }
+    print_avb_footer(&mut File::open(input_boot_file)?)?;
+
+    Ok(())
+}
+
+/// Displays information about the given Android vendor boot image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be opened or if the file is not a
+/// vendor boot image.
+pub fn info_vendor(input_vendor_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.load(input_vendor_boot_file)?;
+
+    println!("[General]");
+    println!("File: {}", input_vendor_boot_file.display());
+    println!(
+        "File Size: {}",
+        File::open(input_vendor_boot_file)?.metadata()?.len()
+    );
+    println!("[Header]");
+
+    match vendor_boot_file.header {
+        VendorHeader::V3(ref header) => {
+            println!("Magic: {}", header.magic);
+            println!("Header Version: {}", header.header_version);
+            println!("Page Size: {}", header.page_size);
+            println!("Kernel Address: {}", header.kernel_addr);
+            println!("Ramdisk Address: {}", header.ramdisk_addr);
+            println!("Vendor Ramdisk Size: {}", header.vendor_ramdisk_size);
+            println!("Command Line Arguments: {}", header.cmdline);
+            println!("Tags Address: {}", header.tags_addr);
+            println!("Product Name: {}", header.name);
+            println!("Header Size: {}", header.header_size);
+            println!("DTB Size: {}", header.dtb_size);
+            println!("DTB Address: {}", header.dtb_addr);
+        }
+        VendorHeader::V4(ref header) => {
+            println!("Magic: {}", header.magic);
+            println!("Header Version: {}", header.header_version);
+            println!("Page Size: {}", header.page_size);
+            println!("Kernel Address: {}", header.kernel_addr);
+            println!("Ramdisk Address: {}", header.ramdisk_addr);
+            println!("Vendor Ramdisk Size: {}", header.vendor_ramdisk_size);
+            println!("Command Line Arguments: {}", header.cmdline);
+            println!("Tags Address: {}", header.tags_addr);
+            println!("Product Name: {}", header.name);
+            println!("Header Size: {}", header.header_size);
+            println!("DTB Size: {}", header.dtb_size);
+            println!("DTB Address: {}", header.dtb_addr);
+            println!(
+                "Vendor Ramdisk Table Size: {}",
+                header.vendor_ramdisk_table_size
+            );
+            println!(
+                "Vendor Ramdisk Table Entry Num: {}",
+                header.vendor_ramdisk_table_entry_num
+            );
+            println!(
+                "Vendor Ramdisk Table Entry Size: {}",
+                header.vendor_ramdisk_table_entry_size
+            );
+            println!("Bootconfig Size: {}", header.bootconfig_size);
+        }
+    }
+
+    let dtb = vendor_boot_file.read_dtb()?;
+    if DtTable::is_dt_table(&dtb) {
+        print_dt_table(&DtTable::parse(&dtb)?);
+    }
+
+    if vendor_boot_file.version >= 4 {
+        print_vendor_ramdisk_table(&vendor_boot_file.read_vendor_ramdisk_table()?);
+        print_bootconfig(&Bootconfig::parse(&vendor_boot_file.read_bootconfig()?));
+    }
+
+    print_avb_footer(&mut File::open(input_vendor_boot_file)?)?;
+
     Ok(())
 }
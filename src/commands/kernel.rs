@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::output::{write_atomically, write_file_atomically};
+use crate::commands::signature::print_dropped_signatures;
+use crate::errors::AbootCrafterError;
+use crate::headers::android::{AndroidBootFile, BootComponent};
+use crate::headers::fdt::FdtHeader;
+use crate::headers::fields::AndroidBootMagic;
+use crate::headers::kernel::{append_dtbs, split_appended_dtbs, KernelFormat};
+use crate::headers::mtk::MtkHeader;
+
+/// A kernel read from a raw file, or from the kernel section of a boot
+/// image, without its MediaTek header.
+struct KernelSource {
+    boot_file: Option<AndroidBootFile>,
+    mtk_header: Option<MtkHeader>,
+    data: Vec<u8>,
+}
+
+fn read_kernel(input_file: &Path) -> Result<KernelSource, AbootCrafterError> {
+    let data = fs::read(input_file)?;
+    if !data.starts_with(&AndroidBootMagic::default().0) {
+        return Ok(KernelSource {
+            boot_file: None,
+            mtk_header: None,
+            data,
+        });
+    }
+
+    let boot_file = AndroidBootFile::open(input_file)?;
+    let kernel = boot_file.get_component(BootComponent::Kernel)?;
+    let (mtk_header, data) = match MtkHeader::split(&kernel) {
+        Some((header, payload)) => (Some(header), payload.to_vec()),
+        None => (None, kernel),
+    };
+    Ok(KernelSource {
+        boot_file: Some(boot_file),
+        mtk_header,
+        data,
+    })
+}
+
+/// Writes a kernel back where it was read from: as a raw file, or as the
+/// kernel section of the boot image, in the same MediaTek header. An id
+/// that matched the components is recomputed.
+fn write_kernel(
+    source: KernelSource,
+    data: Vec<u8>,
+    output_file: &Path,
+) -> Result<(), AbootCrafterError> {
+    let Some(mut boot_file) = source.boot_file else {
+        write_file_atomically(output_file, &data)?;
+        return Ok(());
+    };
+
+    let data = match source.mtk_header {
+        Some(header) => header.wrap(&data)?,
+        None => data,
+    };
+    let id_hash = boot_file.verify_id()?;
+    let signatures = boot_file.signature_trailers();
+    boot_file.replace_component(BootComponent::Kernel, data)?;
+    if let Some(id_hash) = id_hash {
+        boot_file.update_id(id_hash)?;
+    }
+    write_atomically(output_file, |temp_file| boot_file.save_image(temp_file))?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Prints the format of a kernel and the DTBs appended to it.
+pub fn print_kernel_info(data: &[u8]) {
+    let (kernel, dtbs) = split_appended_dtbs(data);
+    println!("[Kernel]");
+    println!("Format: {}", KernelFormat::detect(kernel));
+    println!("Size: {}", kernel.len());
+    println!("Appended DTBs: {}", dtbs.len());
+    let mut offset = kernel.len();
+    for (index, dtb) in dtbs.iter().enumerate() {
+        println!("DTB {}: size {}, offset {}", index, dtb.len(), offset);
+        offset += dtb.len();
+    }
+}
+
+/// Displays the format of a kernel and the DTBs appended to it.
+///
+/// # Arguments
+///
+/// * `input_file` - A kernel, or a boot image holding one.
+pub fn info(input_file: &Path) -> Result<(), AbootCrafterError> {
+    let source = read_kernel(input_file)?;
+    print_kernel_info(&source.data);
+    Ok(())
+}
+
+/// Splits the DTBs appended to a kernel (`Image.gz-dtb`, `zImage-dtb`) into
+/// separate files: `kernel` without them, and `dtb00.dtb`, `dtb01.dtb`, ...
+///
+/// # Arguments
+///
+/// * `input_file` - A kernel, or a boot image holding one.
+/// * `output_dir` - Optional path to the output directory.
+pub fn split_dtb(input_file: &Path, output_dir: Option<PathBuf>) -> Result<(), AbootCrafterError> {
+    let source = read_kernel(input_file)?;
+    let (kernel, dtbs) = split_appended_dtbs(&source.data);
+    if dtbs.is_empty() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No DTBs appended to the kernel in {}",
+            input_file.display()
+        )));
+    }
+
+    let directory_name = if let Some(output_dir) = output_dir {
+        output_dir
+    } else {
+        let file_name = input_file.file_name().unwrap();
+        PathBuf::from(format!("{}_kernel", file_name.to_str().unwrap()))
+    };
+    fs::create_dir_all(&directory_name)?;
+
+    fs::write(directory_name.join(BootComponent::Kernel.name()), kernel)?;
+    for (index, dtb) in dtbs.iter().enumerate() {
+        fs::write(directory_name.join(format!("dtb{:02}.dtb", index)), dtb)?;
+    }
+
+    Ok(())
+}
+
+/// Appends DTBs to a kernel, after any it already carries.
+///
+/// # Arguments
+///
+/// * `input_file` - A kernel, or a boot image holding one.
+/// * `dtb_files` - DTBs to append, in order.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn join_dtb(
+    input_file: &PathBuf,
+    dtb_files: Vec<PathBuf>,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let mut dtbs = Vec::with_capacity(dtb_files.len());
+    for dtb_file in dtb_files {
+        let dtb = fs::read(&dtb_file)?;
+        if FdtHeader::total_size(&dtb) != Some(dtb.len()) {
+            return Err(AbootCrafterError::ConfigError(format!(
+                "{} is not a DTB",
+                dtb_file.display()
+            )));
+        }
+        dtbs.push(dtb);
+    }
+
+    let source = read_kernel(input_file)?;
+    let data = append_dtbs(&source.data, &dtbs);
+    write_kernel(source, data, output_file.as_ref().unwrap_or(input_file))
+}
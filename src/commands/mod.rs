@@ -1,4 +1,13 @@
+pub mod bootconfig;
 pub mod create;
+pub mod devicetree;
+pub mod dtbo;
 pub mod extract;
 pub mod info;
+pub mod kernel;
+pub mod output;
+pub mod qcdt;
+pub mod repack;
+pub mod signature;
 pub mod update;
+pub mod vendor_ramdisk;
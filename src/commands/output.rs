@@ -0,0 +1,54 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Writes `path` through `write`, which is given a temporary file next to it
+/// that then replaces `path`. Commands default to editing their input in
+/// place, so a failure partway through must never leave the only copy of an
+/// image half-written.
+pub fn write_atomically<P, F>(path: P, write: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let path = path.as_ref();
+    let mut temp_file = path.as_os_str().to_owned();
+    temp_file.push(".tmp");
+    let temp_file = PathBuf::from(temp_file);
+
+    if let Err(err) = write(&temp_file) {
+        let _ = fs::remove_file(&temp_file);
+        return Err(err);
+    }
+    fs::rename(&temp_file, path)
+}
+
+/// Writes `data` to `path` through [`write_atomically`].
+pub fn write_file_atomically<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<()> {
+    write_atomically(path, |temp_file| fs::write(temp_file, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_write_keeps_the_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("boot.img");
+        fs::write(&path, b"original").unwrap();
+
+        let err = write_atomically(&path, |temp_file| {
+            fs::write(temp_file, b"half")?;
+            Err(io::Error::other("write failed"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "write failed");
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert!(!dir.path().join("boot.img.tmp").exists());
+
+        write_file_atomically(&path, b"updated").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"updated");
+        assert!(!dir.path().join("boot.img.tmp").exists());
+    }
+}
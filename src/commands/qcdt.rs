@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::AbootCrafterError;
+use crate::headers::android::{AndroidBootFile, BootComponent};
+use crate::headers::fields::{AndroidBootMagic, VendorBootMagic};
+use crate::headers::qcdt::{DtTable, DtTableConfig, DT_TABLE_FILE_NAME};
+use crate::headers::vendor::VendorBootFile;
+
+/// Reads a device tree table, either from a raw table file or from the DTB
+/// section of a boot or vendor boot image.
+fn read_dt_table(input_file: &Path) -> Result<(DtTable, Vec<u8>), AbootCrafterError> {
+    let mut data = fs::read(input_file)?;
+    if data.starts_with(&AndroidBootMagic::default().0) {
+        data = AndroidBootFile::open(input_file)?.get_component(BootComponent::Dtb)?;
+    } else if data.starts_with(&VendorBootMagic::default().0) {
+        let mut vendor_boot_file = VendorBootFile::default();
+        vendor_boot_file.load(input_file)?;
+        data = vendor_boot_file.read_dtb()?;
+    }
+
+    if !DtTable::is_dt_table(&data) {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No QCDT or DTBH table found in {}",
+            input_file.display()
+        )));
+    }
+    Ok((DtTable::parse(&data)?, data))
+}
+
+/// Prints the entries of a QCDT or DTBH table.
+pub fn print_dt_table(table: &DtTable) {
+    println!("[{} Table]", table.format);
+    println!("Version: {}", table.version);
+    println!("Entries: {}", table.entries.len());
+    let id_names = table.format.id_names(table.version);
+    for (index, entry) in table.entries.iter().enumerate() {
+        println!("Entry {}:", index);
+        for (name, word) in id_names.iter().zip(&entry.id) {
+            println!("  {}: 0x{:08x}", name, word);
+        }
+        println!("  Offset: {}", entry.offset);
+        println!("  Size: {}", entry.size);
+    }
+}
+
+/// Lists the entries of a QCDT or DTBH device tree table.
+///
+/// # Arguments
+///
+/// * `input_file` - A table file, or a boot or vendor boot image holding one.
+pub fn list(input_file: &Path) -> Result<(), AbootCrafterError> {
+    let (table, _) = read_dt_table(input_file)?;
+    print_dt_table(&table);
+    Ok(())
+}
+
+/// Extracts every DTB of a QCDT or DTBH table, along with a `dt_table.cfg`
+/// file describing the table so `create` can rebuild it. Entries sharing a
+/// DTB share the file.
+///
+/// # Arguments
+///
+/// * `input_file` - A table file, or a boot or vendor boot image holding one.
+/// * `output_dir` - Optional path to the output directory.
+pub fn extract(input_file: &Path, output_dir: Option<PathBuf>) -> Result<(), AbootCrafterError> {
+    let (table, data) = read_dt_table(input_file)?;
+
+    let directory_name = if let Some(output_dir) = output_dir {
+        output_dir
+    } else {
+        let file_name = input_file.file_name().unwrap();
+        PathBuf::from(format!("{}_dtbs", file_name.to_str().unwrap()))
+    };
+    fs::create_dir_all(&directory_name)?;
+
+    let mut files: Vec<(u32, String)> = Vec::new();
+    let mut config = DtTableConfig {
+        format: table.format,
+        version: table.version,
+        page_size: table.page_size(),
+        entries: Vec::new(),
+    };
+    for entry in &table.entries {
+        let file_name = match files.iter().find(|(offset, _)| *offset == entry.offset) {
+            Some((_, file_name)) => file_name.clone(),
+            None => {
+                let file_name = format!("dtb{:02}.dtb", files.len());
+                fs::write(directory_name.join(&file_name), table.dtb(&data, entry)?)?;
+                files.push((entry.offset, file_name.clone()));
+                file_name
+            }
+        };
+        config.entries.push((file_name, entry.id.clone()));
+    }
+    fs::write(directory_name.join(DT_TABLE_FILE_NAME), config.to_string())?;
+
+    Ok(())
+}
+
+/// Builds a QCDT or DTBH table from a set of DTBs and their ids, like
+/// `dtbTool` does.
+///
+/// # Arguments
+///
+/// * `input_dir` - Directory holding the DTB files.
+/// * `output_file` - Path to the table to write.
+/// * `config_file` - Optional table description, defaults to `dt_table.cfg` in `input_dir`.
+pub fn create(
+    input_dir: &Path,
+    output_file: &PathBuf,
+    config_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let config_file = config_file.unwrap_or_else(|| input_dir.join(DT_TABLE_FILE_NAME));
+    let config = DtTableConfig::parse(&fs::read_to_string(&config_file)?)
+        .map_err(AbootCrafterError::ConfigError)?;
+    if config.entries.is_empty() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "No entries in {}",
+            config_file.display()
+        )));
+    }
+
+    let mut entries = Vec::with_capacity(config.entries.len());
+    for (file_name, id) in config.entries {
+        entries.push((id, fs::read(input_dir.join(file_name))?));
+    }
+    let table = DtTable::build(config.format, config.version, config.page_size, &entries)
+        .map_err(|err| AbootCrafterError::ConfigError(err.to_string()))?;
+    fs::write(output_file, table)?;
+
+    Ok(())
+}
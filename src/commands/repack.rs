@@ -0,0 +1,286 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::AbootCrafterError;
+use crate::headers::android::AndroidBootFile;
+use crate::headers::id::IdHash;
+use crate::headers::layout::BootImageLayout;
+use crate::headers::metadata::{BootImageMetadata, METADATA_FILE_NAME};
+use crate::headers::mtk::{MtkHeader, MTK_HEADER_FILE_SUFFIX};
+use crate::headers::trailer::{Trailer, TRAILER_FILE_NAME};
+
+/// Rebuilds an Android boot image from a directory produced by `extract`.
+///
+/// The header is built from the metadata file and every component file found
+/// in the directory is added back, behind its MediaTek header if `extract`
+/// stripped one, followed by the `trailer` file, so an unmodified directory
+/// reproduces the original image.
+///
+/// # Arguments
+///
+/// * `input_dir` - Directory holding the extracted components.
+/// * `output_boot_file` - Path to the boot image to write.
+/// * `metadata_file` - Optional metadata file, defaults to `bootimg.cfg` in `input_dir`.
+///
+/// # Returns
+///
+/// * `Result<(), AbootCrafterError>` - Ok if successful, or an error if the repack fails.
+pub fn repack(
+    input_dir: &Path,
+    output_boot_file: &PathBuf,
+    metadata_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let metadata_file = metadata_file.unwrap_or_else(|| input_dir.join(METADATA_FILE_NAME));
+    let metadata = BootImageMetadata::parse(&fs::read_to_string(&metadata_file)?)
+        .map_err(AbootCrafterError::ConfigError)?;
+
+    let mut boot_file = AndroidBootFile {
+        header: metadata
+            .to_header()
+            .map_err(AbootCrafterError::ConfigError)?,
+        ..Default::default()
+    };
+    boot_file.version = boot_file.header.version();
+
+    // Missing component files are left empty, as `extract` skips empty sections
+    for &component in boot_file.header.supported_components() {
+        let component_path = input_dir.join(component.name());
+        if !component_path.is_file() {
+            continue;
+        }
+
+        // MediaTek headers stripped by `extract` go back in front, with
+        // the size of the possibly edited component
+        let mut data = fs::read(component_path)?;
+        let header_path = input_dir.join(format!("{}{}", component.name(), MTK_HEADER_FILE_SUFFIX));
+        if header_path.is_file() {
+            let header_data = fs::read(&header_path)?;
+            let (header, _) = MtkHeader::split(&header_data).ok_or_else(|| {
+                AbootCrafterError::ConfigError(format!(
+                    "{} is not a MediaTek header",
+                    header_path.display()
+                ))
+            })?;
+            data = header.wrap(&data)?;
+        }
+        boot_file.replace_component(component, data)?;
+    }
+
+    // An id that matched the extracted components is recomputed, so edited
+    // components get a fresh one and unmodified ones the same
+    if let Some(id_hash) = metadata.get("id_hash") {
+        let id_hash = IdHash::from_name(id_hash.trim()).ok_or_else(|| {
+            AbootCrafterError::ConfigError(format!("Unknown id hash: {}", id_hash))
+        })?;
+        boot_file.update_id(id_hash)?;
+    }
+
+    // Trailers are appended as extracted. Signatures among them only match
+    // if nothing was edited
+    let trailer_path = input_dir.join(TRAILER_FILE_NAME);
+    if trailer_path.is_file() {
+        let image_size = BootImageLayout::new(&boot_file.header).image_size();
+        boot_file.trailers = Trailer::parse_all(&fs::read(trailer_path)?, image_size);
+    }
+
+    boot_file.save_image(output_boot_file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create::{create_v0, create_v2, create_v4};
+    use crate::commands::extract::extract;
+    use crate::headers::android::{AndroidHeader, BootComponent};
+    use crate::headers::avb::{AvbAlgorithm, AvbFooter, AvbHashFooterOptions};
+    use crate::headers::mtk::MtkImageKind;
+    use crate::headers::trailer::TrailerKind;
+
+    /// Bytes that are not valid UTF-8, stored at the start of the name and
+    /// command lines.
+    const RAW_BYTES: [u8; 4] = [0xff, 0xfe, 0x80, 0x01];
+
+    fn write_inputs(dir: &Path) {
+        for (name, data) in [
+            ("kernel", vec![1; 3000]),
+            ("ramdisk", vec![2; 5000]),
+            ("second", vec![3; 100]),
+            ("dt", vec![4; 2100]),
+            ("recovery_dtbo", vec![5; 700]),
+            ("dtb", vec![6; 900]),
+            ("signature", vec![7; 4096]),
+        ] {
+            fs::write(dir.join(name), data).unwrap();
+        }
+    }
+
+    /// Puts raw bytes in the name and command lines of the image, and a
+    /// stray word in place of the v0 header_version, and signs it with an AVB
+    /// footer after its trailers.
+    fn finish_image(image: &Path) -> Vec<u8> {
+        let mut boot_file = AndroidBootFile::open(image).unwrap();
+        match &mut boot_file.header {
+            AndroidHeader::V0(header) => {
+                header.header_version = 0x7fff_0000;
+                header.name.0[..4].copy_from_slice(&RAW_BYTES);
+                header.cmdline.0[..4].copy_from_slice(&RAW_BYTES);
+                header.extra_cmdline.0[..4].copy_from_slice(&RAW_BYTES);
+            }
+            AndroidHeader::V0Qcom(header) => {
+                header.name.0[..4].copy_from_slice(&RAW_BYTES);
+                header.cmdline.0[..4].copy_from_slice(&RAW_BYTES);
+            }
+            AndroidHeader::V2(header) => {
+                header.name.0[..4].copy_from_slice(&RAW_BYTES);
+                header.extra_cmdline.0[..4].copy_from_slice(&RAW_BYTES);
+            }
+            AndroidHeader::V4(header) => header.cmdline.0[..4].copy_from_slice(&RAW_BYTES),
+            _ => unreachable!(),
+        }
+        boot_file.save_image(image).unwrap();
+
+        let mut data = fs::read(image).unwrap();
+        let options = AvbHashFooterOptions {
+            partition_name: "boot".to_string(),
+            partition_size: Some(64 * 1024),
+            salt: Some(vec![0x5a; 32]),
+            rollback_index: 0,
+            algorithm: AvbAlgorithm::None,
+            key: None,
+        };
+        AvbFooter::add_hash_footer(&mut data, &options).unwrap();
+        fs::write(image, &data).unwrap();
+        data
+    }
+
+    fn assert_round_trip(dir: &Path, image: &Path, strip_mtk_headers: bool) {
+        let data = finish_image(image);
+        let extracted = dir.join("extracted");
+        extract(
+            &image.to_path_buf(),
+            Some(extracted.clone()),
+            strip_mtk_headers,
+        )
+        .unwrap();
+        let repacked = dir.join("repacked.img");
+        repack(&extracted, &repacked, None).unwrap();
+        assert!(fs::read(repacked).unwrap() == data);
+    }
+
+    fn create_v0_image(
+        dir: &Path,
+        dt_file: Option<PathBuf>,
+        mtk_header: Option<MtkImageKind>,
+    ) -> PathBuf {
+        let image = dir.join("boot.img");
+        create_v0(
+            image.clone(),
+            dir.join("kernel"),
+            dir.join("ramdisk"),
+            Some(dir.join("second")),
+            dt_file,
+            2048,
+            "0x10008000".into(),
+            "0x11000000".into(),
+            "0x10f00000".into(),
+            "0x10000100".into(),
+            "11.0.0 2021-05".into(),
+            "name".into(),
+            "console=ttyMSM0".into(),
+            None,
+            IdHash::default(),
+            "extra".into(),
+            vec![TrailerKind::SeAndroidEnforce],
+            mtk_header,
+        )
+        .unwrap();
+        image
+    }
+
+    #[test]
+    fn round_trip_v0() {
+        let dir = tempfile::tempdir().unwrap();
+        write_inputs(dir.path());
+        let image = create_v0_image(dir.path(), None, None);
+        assert_round_trip(dir.path(), &image, false);
+    }
+
+    #[test]
+    fn round_trip_v0_qcom() {
+        let dir = tempfile::tempdir().unwrap();
+        write_inputs(dir.path());
+        let image = create_v0_image(dir.path(), Some(dir.path().join("dt")), None);
+        assert!(matches!(
+            AndroidBootFile::open(&image).unwrap().header,
+            AndroidHeader::V0Qcom(_)
+        ));
+        assert_round_trip(dir.path(), &image, false);
+    }
+
+    #[test]
+    fn round_trip_v0_mtk_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        write_inputs(dir.path());
+        let image = create_v0_image(dir.path(), None, Some(MtkImageKind::Recovery));
+        let boot_file = AndroidBootFile::open(&image).unwrap();
+        let names: Vec<String> = [BootComponent::Kernel, BootComponent::Ramdisk]
+            .into_iter()
+            .map(|component| {
+                let data = boot_file.get_component(component).unwrap();
+                MtkHeader::split(&data).unwrap().0.name()
+            })
+            .collect();
+        assert_eq!(names, ["KERNEL", "RECOVERY"]);
+        assert_round_trip(dir.path(), &image, true);
+    }
+
+    #[test]
+    fn round_trip_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        write_inputs(dir.path());
+        let image = dir.path().join("boot.img");
+        create_v2(
+            image.clone(),
+            dir.path().join("kernel"),
+            dir.path().join("ramdisk"),
+            Some(dir.path().join("second")),
+            Some(dir.path().join("recovery_dtbo")),
+            Some(dir.path().join("dtb")),
+            4096,
+            "0x00008000".into(),
+            "0x01000000".into(),
+            "0x00f00000".into(),
+            "0x00000100".into(),
+            "10.0.0 2020-01".into(),
+            "name".into(),
+            "console=ttyMSM0".into(),
+            None,
+            IdHash::Sha256,
+            "extra".into(),
+            "0x0000000001f00000".into(),
+            vec![TrailerKind::SeAndroidEnforce],
+            None,
+        )
+        .unwrap();
+        assert_round_trip(dir.path(), &image, false);
+    }
+
+    #[test]
+    fn round_trip_v4() {
+        let dir = tempfile::tempdir().unwrap();
+        write_inputs(dir.path());
+        let image = dir.path().join("boot.img");
+        create_v4(
+            image.clone(),
+            dir.path().join("kernel"),
+            dir.path().join("ramdisk"),
+            Some(dir.path().join("signature")),
+            "13.0.0 2023-02".into(),
+            "console=ttyMSM0".into(),
+            vec![TrailerKind::SeAndroidEnforce],
+        )
+        .unwrap();
+        assert_round_trip(dir.path(), &image, false);
+    }
+}
@@ -0,0 +1,682 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use rsa::traits::PublicKeyParts;
+use sha1::{Digest, Sha1};
+
+use crate::commands::output::{write_atomically, write_file_atomically};
+use crate::errors::AbootCrafterError;
+use crate::headers::android::{AndroidBootFile, BootComponent};
+use crate::headers::avb::{
+    decode_public_key, read_private_key, read_private_key_pem, read_public_key, AvbAlgorithm,
+    AvbDescriptor, AvbFooter, AvbHashFooterOptions, VbmetaImage, AVB_VBMETA_HEADER_SIZE,
+};
+use crate::headers::layout::BootImageLayout;
+use crate::headers::trailer::TrailerKind;
+use crate::headers::vboot::{certificate_public_key, read_certificate, VbootSignature};
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Verification results, printed as they are recorded.
+#[derive(Default)]
+struct Checks {
+    failures: Vec<String>,
+    /// Whether the public key was checked against a trusted one
+    trusted: bool,
+}
+
+impl Checks {
+    fn check(&mut self, name: &str, passed: bool, failure: String) {
+        println!("{}: {}", name, if passed { "OK" } else { "FAILED" });
+        if !passed {
+            self.failures.push(failure);
+        }
+    }
+
+    /// Checks the public key the image is signed with against the trusted
+    /// one, if any. Without one, the image can only be found consistent.
+    fn check_key(&mut self, trusted_key: Option<bool>, failure: String) {
+        match trusted_key {
+            Some(passed) => {
+                self.trusted = true;
+                self.check("Public Key", passed, failure);
+            }
+            None => println!("Public Key: not checked, no trusted key given"),
+        }
+    }
+
+    /// Fails with the first failed check, if any. Images that pass without
+    /// a trusted key are not reported as OK.
+    fn finish(self) -> Result<(), AbootCrafterError> {
+        match self.failures.into_iter().next() {
+            Some(failure) => Err(AbootCrafterError::VerificationError(failure)),
+            None if !self.trusted => {
+                println!("Result: consistent, but signature not checked against a trusted key");
+                Ok(())
+            }
+            None => {
+                println!("Result: OK");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Prints the vbmeta header and every descriptor it carries.
+pub fn print_vbmeta(vbmeta: &VbmetaImage) -> Result<(), AbootCrafterError> {
+    let header = &vbmeta.header;
+    println!("[VBMeta]");
+    println!(
+        "Minimum libavb Version: {}.{}",
+        header.required_libavb_version_major, header.required_libavb_version_minor
+    );
+    println!("Header Block: {} bytes", AVB_VBMETA_HEADER_SIZE);
+    println!(
+        "Authentication Block: {} bytes",
+        header.authentication_data_block_size
+    );
+    println!(
+        "Auxiliary Block: {} bytes",
+        header.auxiliary_data_block_size
+    );
+    println!("Algorithm: {}", header.algorithm());
+    if !vbmeta.public_key().is_empty() {
+        println!(
+            "Public Key (sha1): {}",
+            hex(&Sha1::digest(vbmeta.public_key()))
+        );
+    }
+    println!("Rollback Index: {}", header.rollback_index);
+    println!(
+        "Rollback Index Location: {}",
+        header.rollback_index_location
+    );
+    println!("Flags: {}", header.flags);
+    println!("Release String: {}", header.release_string());
+
+    for (index, descriptor) in vbmeta.descriptors()?.iter().enumerate() {
+        println!("[Descriptor {}: {}]", index, descriptor.name());
+        match descriptor {
+            AvbDescriptor::Property(property) => {
+                println!("Key: {}", text(&property.key));
+                if property
+                    .value
+                    .iter()
+                    .all(|&c| c.is_ascii_graphic() || c == b' ')
+                {
+                    println!("Value: {}", text(&property.value));
+                } else {
+                    println!("Value: {} bytes", property.value.len());
+                }
+            }
+            AvbDescriptor::Hashtree(hashtree) => {
+                println!("Version of dm-verity: {}", hashtree.dm_verity_version);
+                println!("Image Size: {}", hashtree.image_size);
+                println!("Tree Offset: {}", hashtree.tree_offset);
+                println!("Tree Size: {}", hashtree.tree_size);
+                println!("Data Block Size: {}", hashtree.data_block_size);
+                println!("Hash Block Size: {}", hashtree.hash_block_size);
+                println!("FEC Num Roots: {}", hashtree.fec_num_roots);
+                println!("FEC Offset: {}", hashtree.fec_offset);
+                println!("FEC Size: {}", hashtree.fec_size);
+                println!("Hash Algorithm: {}", text(&hashtree.hash_algorithm));
+                println!("Partition Name: {}", text(&hashtree.partition_name));
+                println!("Salt: {}", hex(&hashtree.salt));
+                println!("Root Digest: {}", hex(&hashtree.root_digest));
+                println!("Flags: {}", hashtree.flags);
+            }
+            AvbDescriptor::Hash(hash) => {
+                println!("Image Size: {}", hash.image_size);
+                println!("Hash Algorithm: {}", text(&hash.hash_algorithm));
+                println!("Partition Name: {}", text(&hash.partition_name));
+                println!("Salt: {}", hex(&hash.salt));
+                println!("Digest: {}", hex(&hash.digest));
+                println!("Flags: {}", hash.flags);
+            }
+            AvbDescriptor::KernelCmdline(cmdline) => {
+                println!("Flags: {}", cmdline.flags);
+                println!("Kernel Cmdline: {}", text(&cmdline.kernel_cmdline));
+            }
+            AvbDescriptor::ChainPartition(chain) => {
+                println!("Partition Name: {}", text(&chain.partition_name));
+                println!("Rollback Index Location: {}", chain.rollback_index_location);
+                println!(
+                    "Public Key (sha1): {}",
+                    hex(&Sha1::digest(&chain.public_key))
+                );
+                println!("Flags: {}", chain.flags);
+            }
+            AvbDescriptor::Unknown { tag, data } => {
+                println!("Tag: {}", tag);
+                println!("Size: {}", data.len());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the AVB footer at the end of `file` and the vbmeta it points to,
+/// if the file has one.
+pub fn print_avb_footer(file: &mut File) -> Result<(), AbootCrafterError> {
+    let Some(footer) = AvbFooter::read_from(file)? else {
+        return Ok(());
+    };
+
+    println!("[AVB Footer]");
+    println!(
+        "Footer Version: {}.{}",
+        footer.version_major, footer.version_minor
+    );
+    println!("Image Size: {}", file.metadata()?.len());
+    println!("Original Image Size: {}", footer.original_image_size);
+    println!("VBMeta Offset: {}", footer.vbmeta_offset);
+    println!("VBMeta Size: {}", footer.vbmeta_size);
+
+    print_vbmeta(&footer.read_vbmeta(file)?)
+}
+
+/// Prints the vbmeta structs of the boot signature section, if the image has one.
+pub fn print_boot_signature(boot_file: &AndroidBootFile) -> Result<(), AbootCrafterError> {
+    let signature = boot_file.get_component(BootComponent::Signature)?;
+    if signature.is_empty() {
+        return Ok(());
+    }
+
+    println!("[Boot Signature]");
+    println!("Size: {}", signature.len());
+    let vbmeta_images = VbmetaImage::parse_all(&signature)?;
+    println!("VBMeta Structs: {}", vbmeta_images.len());
+    for vbmeta in &vbmeta_images {
+        print_vbmeta(vbmeta)?;
+    }
+    Ok(())
+}
+
+/// Prints a VBoot 1.0 signature and its certificate.
+pub fn print_vboot_signature(signature: &VbootSignature) -> Result<(), AbootCrafterError> {
+    let certificate = &signature.certificate.tbs_certificate;
+    println!("[VBoot Signature]");
+    println!("Format Version: {}", signature.format_version);
+    println!("Target: {}", signature.target());
+    println!("Length: {}", signature.authenticated_attributes.length);
+    println!("Algorithm: {}", signature.algorithm_name());
+    println!("Signature: {} bytes", signature.signature.as_bytes().len());
+    println!("Certificate Subject: {}", certificate.subject);
+    println!("Certificate Issuer: {}", certificate.issuer);
+    println!(
+        "Certificate Serial: {}",
+        hex(certificate.serial_number.as_bytes())
+    );
+    println!(
+        "Certificate Not Before: {}",
+        certificate.validity.not_before
+    );
+    println!("Certificate Not After: {}", certificate.validity.not_after);
+    println!(
+        "Public Key: RSA {} bits",
+        signature.public_key()?.size() * 8
+    );
+    Ok(())
+}
+
+/// Splits a boot image into the part `boot_signer` signs, the header and
+/// every section up to the last one, and the VBoot 1.0 signature appended
+/// after it, if there is one.
+pub fn split_vboot_signature(
+    input_file: &PathBuf,
+) -> Result<(Vec<u8>, Option<VbootSignature>), AbootCrafterError> {
+    let boot_file = AndroidBootFile::open(input_file)?;
+    let image_size = BootImageLayout::new(&boot_file.header).image_size() as usize;
+    let mut data = fs::read(input_file)?;
+    if data.len() < image_size {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "Boot image is truncated, expected at least {} bytes",
+            image_size
+        )));
+    }
+
+    let signature = VbootSignature::parse(&data[image_size..]);
+    data.truncate(image_size);
+    Ok((data, signature))
+}
+
+/// Prints a note for every signature trailer of `signatures` missing from
+/// `remaining`, as the image has to be signed again.
+pub fn print_dropped_signatures(signatures: &[TrailerKind], remaining: &[TrailerKind]) {
+    for kind in signatures.iter().filter(|kind| !remaining.contains(kind)) {
+        println!(
+            "{} dropped, it no longer matches the image and has to be generated again",
+            kind
+        );
+    }
+}
+
+/// Opens a boot image and checks its header has a boot signature section.
+fn open_v4(input_file: &PathBuf) -> Result<AndroidBootFile, AbootCrafterError> {
+    let boot_file = AndroidBootFile::open(input_file)?;
+    if !boot_file.header.supports(BootComponent::Signature) {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "Boot signatures require a boot image header v4, got v{}",
+            boot_file.header.version()
+        )));
+    }
+    Ok(boot_file)
+}
+
+/// Displays the AVB footer and vbmeta struct of an image, or the VBoot 1.0
+/// signature of a boot image without one.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the signed image.
+pub fn info(input_file: &PathBuf) -> Result<(), AbootCrafterError> {
+    let mut file = File::open(input_file)?;
+    if AvbFooter::read_from(&mut file)?.is_some() {
+        return print_avb_footer(&mut file);
+    }
+
+    // Anything that is not a boot image cannot have a VBoot signature either
+    match split_vboot_signature(input_file) {
+        Ok((_, Some(signature))) => print_vboot_signature(&signature),
+        _ => {
+            println!("No AVB footer or VBoot signature found");
+            Ok(())
+        }
+    }
+}
+
+/// Removes the AVB footer and vbmeta struct from an image, like
+/// `avbtool erase_footer`.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the signed image.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn remove(input_file: &PathBuf, output_file: Option<PathBuf>) -> Result<(), AbootCrafterError> {
+    let mut data = fs::read(input_file)?;
+    if !AvbFooter::erase(&mut data) {
+        return Err(AbootCrafterError::ConfigError(
+            "No AVB footer found".to_string(),
+        ));
+    }
+    write_file_atomically(output_file.as_ref().unwrap_or(input_file), &data)?;
+    Ok(())
+}
+
+/// Appends an AVB hash footer to an image, like `avbtool add_hash_footer`.
+/// Any existing footer is replaced.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the image to sign.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+/// * `key_file` - PEM RSA private key, required unless `algorithm` is NONE.
+/// * `algorithm` - Algorithm used to sign the vbmeta struct.
+/// * `partition_name` - Name of the partition in the hash descriptor.
+/// * `partition_size` - Size of the partition, or `None` for the smallest size that fits.
+/// * `salt` - Salt of the hash descriptor, random if omitted.
+/// * `rollback_index` - Rollback index of the vbmeta struct.
+#[allow(clippy::too_many_arguments)]
+pub fn generate(
+    input_file: &PathBuf,
+    output_file: Option<PathBuf>,
+    key_file: Option<PathBuf>,
+    algorithm: AvbAlgorithm,
+    partition_name: String,
+    partition_size: Option<u64>,
+    salt: Option<Vec<u8>>,
+    rollback_index: u64,
+) -> Result<(), AbootCrafterError> {
+    let key = match key_file {
+        Some(key_file) => Some(read_private_key_pem(&fs::read_to_string(key_file)?)?),
+        None => None,
+    };
+    let options = AvbHashFooterOptions {
+        partition_name,
+        partition_size,
+        salt,
+        rollback_index,
+        algorithm,
+        key,
+    };
+
+    let mut data = fs::read(input_file)?;
+    AvbFooter::add_hash_footer(&mut data, &options)?;
+    write_file_atomically(output_file.as_ref().unwrap_or(input_file), &data)?;
+    Ok(())
+}
+
+/// Checks the signature of an image and prints every check, returning the
+/// first failure as an error.
+///
+/// Images with an AVB footer have the public key of their vbmeta struct
+/// checked against `key_file`, its hash and signature, and the digest of
+/// every hash descriptor against the image contents. Boot images without
+/// one have their VBoot 1.0 signature checked instead. Without `key_file`
+/// the image can only be found consistent, as anyone can re-sign it, so it
+/// is not reported as OK.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the signed image.
+/// * `key_file` - Optional public key the image must be signed with (PEM, `.avbpubkey`, or an X.509 certificate for VBoot 1.0).
+pub fn verify(input_file: &PathBuf, key_file: Option<PathBuf>) -> Result<(), AbootCrafterError> {
+    let data = fs::read(input_file)?;
+    match AvbFooter::parse(&data) {
+        Some(footer) => verify_avb(&data, &footer, key_file),
+        None => match split_vboot_signature(input_file) {
+            Ok((image, Some(signature))) => verify_vboot(&image, &signature, key_file),
+            _ => Err(AbootCrafterError::VerificationError(
+                "No AVB footer or VBoot signature found".to_string(),
+            )),
+        },
+    }
+}
+
+fn verify_avb(
+    data: &[u8],
+    footer: &AvbFooter,
+    key_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let vbmeta = footer
+        .vbmeta_data(data)
+        .map_err(|err| AbootCrafterError::VerificationError(err.to_string()))
+        .and_then(|vbmeta| Ok(VbmetaImage::parse(vbmeta)?))?;
+
+    let mut checks = Checks::default();
+    println!("[Verification]");
+    let algorithm = vbmeta.header.algorithm();
+    println!("Algorithm: {}", algorithm);
+    if algorithm == AvbAlgorithm::None {
+        // Unsigned images are never trusted, and fail when a key is expected
+        if key_file.is_some() {
+            checks.check("Signature", false, "vbmeta is not signed".to_string());
+        } else {
+            println!("Signature: none");
+        }
+    } else {
+        let expected_key = match key_file {
+            Some(key_file) => Some(read_public_key(&fs::read(key_file)?)?),
+            None => None,
+        };
+        checks.check_key(
+            expected_key
+                .as_ref()
+                .map(|expected_key| *expected_key == vbmeta.public_key()),
+            format!(
+                "vbmeta is signed with public key {}, expected {}",
+                hex(&Sha1::digest(vbmeta.public_key())),
+                expected_key
+                    .as_ref()
+                    .map_or(String::new(), |key| hex(&Sha1::digest(key)))
+            ),
+        );
+        checks.check(
+            "VBMeta Hash",
+            vbmeta.verify_hash()?,
+            "vbmeta hash does not match its header and auxiliary block".to_string(),
+        );
+        // A key that cannot be decoded fails the check like a bad signature
+        match vbmeta.verify_signature() {
+            Ok(passed) => checks.check(
+                "Signature",
+                passed,
+                "vbmeta signature does not match its public key".to_string(),
+            ),
+            Err(err) => checks.check(
+                "Signature",
+                false,
+                format!("vbmeta public key is not usable: {}", err),
+            ),
+        }
+    }
+
+    let image = &data[..footer.original_image_size.min(data.len() as u64) as usize];
+    for descriptor in vbmeta.descriptors()? {
+        if let AvbDescriptor::Hash(hash) = descriptor {
+            let partition_name = text(&hash.partition_name);
+            checks.check(
+                &format!("Hash Descriptor ({})", partition_name),
+                hash.verify(image),
+                format!(
+                    "{} digest does not match the image, it was modified after signing",
+                    partition_name
+                ),
+            );
+        }
+    }
+
+    checks.finish()
+}
+
+fn verify_vboot(
+    image: &[u8],
+    signature: &VbootSignature,
+    key_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let mut checks = Checks::default();
+    println!("[Verification]");
+    println!("Algorithm: {}", signature.algorithm_name());
+    println!("Target: {}", signature.target());
+
+    let public_key = signature.public_key()?;
+    let expected_key = match key_file {
+        Some(key_file) => {
+            let key_data = fs::read(key_file)?;
+            Some(match read_certificate(&key_data) {
+                Ok(certificate) => certificate_public_key(&certificate)?,
+                Err(_) => decode_public_key(&read_public_key(&key_data)?)?,
+            })
+        }
+        None => None,
+    };
+    checks.check_key(
+        expected_key.map(|expected_key| expected_key == public_key),
+        "image is signed with a different certificate".to_string(),
+    );
+
+    let length = signature.authenticated_attributes.length;
+    checks.check(
+        "Length",
+        length == image.len() as u64,
+        format!(
+            "signature covers {} bytes, the image is {} bytes",
+            length,
+            image.len()
+        ),
+    );
+    checks.check(
+        "Signature",
+        signature.verify(image, &public_key)?,
+        "signature does not match the image, it was modified after signing".to_string(),
+    );
+
+    checks.finish()
+}
+
+/// Writes the boot signature section of a v4 boot image to a file.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the boot image.
+/// * `output_file` - The path to write the boot signature to.
+pub fn extract_boot_signature(
+    input_file: &PathBuf,
+    output_file: &PathBuf,
+) -> Result<(), AbootCrafterError> {
+    let boot_file = open_v4(input_file)?;
+    let signature = boot_file.get_component(BootComponent::Signature)?;
+    if signature.is_empty() {
+        return Err(AbootCrafterError::ConfigError(
+            "No boot signature found".to_string(),
+        ));
+    }
+    fs::write(output_file, signature)?;
+    Ok(())
+}
+
+/// Replaces the boot signature section of a v4 boot image with the contents
+/// of a file, such as a certificate signed elsewhere.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the boot image.
+/// * `signature_file` - The boot signature to store.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn replace_boot_signature(
+    input_file: &PathBuf,
+    signature_file: &PathBuf,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let mut boot_file = open_v4(input_file)?;
+    let signatures = boot_file.signature_trailers();
+    boot_file.replace_component(BootComponent::Signature, fs::read(signature_file)?)?;
+    write_atomically(output_file.as_ref().unwrap_or(input_file), |temp_file| {
+        boot_file.save_image(temp_file)
+    })?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Removes the boot signature section of a v4 boot image.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the boot image.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+pub fn remove_boot_signature(
+    input_file: &PathBuf,
+    output_file: Option<PathBuf>,
+) -> Result<(), AbootCrafterError> {
+    let mut boot_file = open_v4(input_file)?;
+    if boot_file
+        .get_component(BootComponent::Signature)?
+        .is_empty()
+    {
+        return Err(AbootCrafterError::ConfigError(
+            "No boot signature found".to_string(),
+        ));
+    }
+    let signatures = boot_file.signature_trailers();
+    boot_file.replace_component(BootComponent::Signature, Vec::new())?;
+    write_atomically(output_file.as_ref().unwrap_or(input_file), |temp_file| {
+        boot_file.save_image(temp_file)
+    })?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Generates the boot signature section of a v4 boot image, like mkbootimg
+/// with `--gki_signing_key`: vbmeta structs with "boot" and "generic_kernel"
+/// hash descriptors, padded to 16 KiB. Any existing section is replaced.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the boot image.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+/// * `key_file` - PEM RSA private key, required unless `algorithm` is NONE.
+/// * `algorithm` - Algorithm used to sign the vbmeta structs.
+/// * `salt` - Salt of the hash descriptors, random if omitted.
+pub fn generate_boot_signature(
+    input_file: &PathBuf,
+    output_file: Option<PathBuf>,
+    key_file: Option<PathBuf>,
+    algorithm: AvbAlgorithm,
+    salt: Option<Vec<u8>>,
+) -> Result<(), AbootCrafterError> {
+    let key = match key_file {
+        Some(key_file) => Some(read_private_key_pem(&fs::read_to_string(key_file)?)?),
+        None => None,
+    };
+
+    let mut boot_file = open_v4(input_file)?;
+    let signatures = boot_file.signature_trailers();
+    boot_file.generate_boot_signature(algorithm, key.as_ref(), salt)?;
+    write_atomically(output_file.as_ref().unwrap_or(input_file), |temp_file| {
+        boot_file.save_image(temp_file)
+    })?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+    Ok(())
+}
+
+/// Signs a boot image like `boot_signer`, appending a VBoot 1.0 signature
+/// for `target`. Anything after the last section, like an older signature,
+/// is dropped first.
+///
+/// # Arguments
+///
+/// * `input_file` - The path to the boot image.
+/// * `output_file` - Optional output path, the input is updated in place if omitted.
+/// * `key_file` - RSA private key, PEM or DER PKCS#8 (`.pk8`).
+/// * `certificate_file` - X.509 certificate of the key, PEM or DER.
+/// * `target` - Partition the image is flashed to, `/boot` or `/recovery`.
+pub fn generate_vboot_signature(
+    input_file: &PathBuf,
+    output_file: Option<PathBuf>,
+    key_file: &PathBuf,
+    certificate_file: &PathBuf,
+    target: String,
+) -> Result<(), AbootCrafterError> {
+    let key = read_private_key(&fs::read(key_file)?)?;
+    let certificate = read_certificate(&fs::read(certificate_file)?)?;
+    if certificate_public_key(&certificate)? != key.to_public_key() {
+        return Err(AbootCrafterError::ConfigError(
+            "The certificate does not match the private key".to_string(),
+        ));
+    }
+
+    let (mut image, _) = split_vboot_signature(input_file)?;
+    let signature = VbootSignature::sign(&image, &target, &key, certificate)?;
+    image.extend(signature.to_bytes()?);
+    write_file_atomically(output_file.as_ref().unwrap_or(input_file), &image)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::avb::encode_public_key;
+
+    #[test]
+    fn unusable_public_key_fails_the_signature_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("vendor_boot.img");
+        let key = read_private_key_pem(include_str!("../headers/testdata/rsa2048.pem")).unwrap();
+        let public_key = encode_public_key(&key.to_public_key());
+        let mut data = vec![0x42; 4096];
+        let options = AvbHashFooterOptions {
+            partition_name: "vendor_boot".to_string(),
+            algorithm: AvbAlgorithm::Sha256Rsa2048,
+            key: Some(key),
+            ..Default::default()
+        };
+        AvbFooter::add_hash_footer(&mut data, &options).unwrap();
+
+        // Claim a key size the vbmeta struct cannot hold
+        let start = data
+            .windows(public_key.len())
+            .position(|window| window == public_key)
+            .unwrap();
+        data[start..start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        fs::write(&image, &data).unwrap();
+
+        match verify(&image, None) {
+            Err(AbootCrafterError::VerificationError(failure)) => {
+                assert_eq!(
+                    failure,
+                    "vbmeta hash does not match its header and auxiliary block"
+                )
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
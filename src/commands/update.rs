@@ -1,12 +1,34 @@
-use std::io::{Seek, Write};
 use std::path::PathBuf;
 
+use crate::commands::output::write_atomically;
+use crate::commands::signature::print_dropped_signatures;
 use crate::errors::AbootCrafterError;
-use crate::headers::android::AndroidBootFile;
-use crate::headers::android::{AndroidHeader, PAGE_SIZE_V3};
-
-const EMPTY_SIZE: u32 = 0;
+use crate::headers::android::{AndroidBootFile, AndroidHeader, BootComponent};
+use crate::headers::mtk::MtkHeader;
+use crate::headers::trailer::TrailerKind;
 
+/// Updates an existing Android boot image in place.
+///
+/// Only the components and command lines that are given are changed; every
+/// other component and header field is kept as it was, except for an `id`
+/// that matched the components, which is recomputed from the new ones.
+/// Replacements for components wrapped in a MediaTek header are wrapped in
+/// the same header.
+///
+/// Trailers after the last section, like `SEANDROIDENFORCE`, are kept unless
+/// `strip_trailers` is set. AVB footers and VBoot signatures are always
+/// dropped, as they no longer match the updated image, and a note is printed
+/// for each so the image can be signed again.
+///
+/// # Arguments
+///
+/// * `input_boot_file` - The path to the Android boot image file.
+/// * `kernel_file`, `ramdisk_file`, `second_file`, `recovery_dtbo_file`,
+///   `dtb_file` - Optional replacements for the matching components.
+/// * `cmdline`, `extra_cmdline` - Optional replacements for the command lines.
+/// * `strip_trailers` - Drop every trailer of the original image.
+/// * `append_trailers` - Marker trailers to add if the image lacks them.
+#[allow(clippy::too_many_arguments)]
 pub fn update(
     input_boot_file: &PathBuf,
     kernel_file: Option<PathBuf>,
@@ -16,159 +38,91 @@ pub fn update(
     dtb_file: Option<PathBuf>,
     cmdline: Option<String>,
     extra_cmdline: Option<String>,
+    strip_trailers: bool,
+    append_trailers: Vec<TrailerKind>,
 ) -> Result<(), AbootCrafterError> {
     let mut boot_file = AndroidBootFile::default();
     boot_file.load(input_boot_file)?;
 
-    let kernel_data = kernel_file.map_or_else(|| Ok(Vec::new()), std::fs::read)?;
-    let ramdisk_data = ramdisk_file.map_or_else(|| Ok(Vec::new()), std::fs::read)?;
-    let second_data = second_file.map_or_else(|| Ok(Vec::new()), std::fs::read)?;
-    let recovery_dtbo_data = recovery_dtbo_file.map_or_else(|| Ok(Vec::new()), std::fs::read)?;
-    let dtb_data = dtb_file.map_or_else(|| Ok(Vec::new()), std::fs::read)?;
+    let version = boot_file.header.version();
+    let signatures = boot_file.signature_trailers();
+
+    // Only an id that matched the components is recomputed, with the same
+    // digest; any other id is custom and kept as it is
+    let id_hash = boot_file.verify_id()?;
+    let replacements = [
+        (BootComponent::Kernel, kernel_file),
+        (BootComponent::Ramdisk, ramdisk_file),
+        (BootComponent::Second, second_file),
+        (BootComponent::RecoveryDtbo, recovery_dtbo_file),
+        (BootComponent::Dtb, dtb_file),
+    ];
+
+    // Only the given components are replaced, every other section and
+    // header field is carried over from the original image
+    for (component, component_file) in replacements {
+        let Some(component_file) = component_file else {
+            continue;
+        };
+        if !boot_file.header.supports(component) {
+            println!("{} is not supported on v{}", component, version);
+            continue;
+        }
+        // A replacement for a component with a MediaTek header gets the
+        // same header, unless it already carries one
+        let mut data = std::fs::read(component_file)?;
+        if let Some((header, _)) = MtkHeader::split(&boot_file.get_component(component)?) {
+            if MtkHeader::split(&data).is_none() {
+                data = header.wrap(&data)?;
+            }
+        }
+        boot_file.replace_component(component, data)?;
+    }
 
     match &mut boot_file.header {
         AndroidHeader::V0(ref mut header) => {
-            header.kernel_size = if kernel_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                kernel_data.len() as u32
-            };
-            header.ramdisk_size = if ramdisk_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                ramdisk_data.len() as u32
-            };
-            header.second_size = if second_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                second_data.len() as u32
-            };
-            if !recovery_dtbo_data.is_empty() {
-                println!("recovery_dtbo is not supported on v0");
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
-            if !dtb_data.is_empty() {
-                println!("dtb is not supported on v0");
+            if let Some(extra_cmdline) = extra_cmdline {
+                header.extra_cmdline = extra_cmdline.into();
             }
-            if cmdline.is_some() {
-                header.cmdline = cmdline.unwrap().into();
+        }
+        AndroidHeader::V0Qcom(ref mut header) => {
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
-            if extra_cmdline.is_some() {
-                header.extra_cmdline = extra_cmdline.unwrap().into();
+            if let Some(extra_cmdline) = extra_cmdline {
+                header.extra_cmdline = extra_cmdline.into();
             }
         }
         AndroidHeader::V1(ref mut header) => {
-            header.kernel_size = if kernel_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                kernel_data.len() as u32
-            };
-            header.ramdisk_size = if ramdisk_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                ramdisk_data.len() as u32
-            };
-            header.second_size = if second_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                second_data.len() as u32
-            };
-            header.recovery_dtbo_size = if recovery_dtbo_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                recovery_dtbo_data.len() as u32
-            };
-            if !dtb_data.is_empty() {
-                println!("dtb is not supported on v1");
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
-            if cmdline.is_some() {
-                header.cmdline = cmdline.unwrap().into();
-            }
-            if extra_cmdline.is_some() {
-                header.extra_cmdline = extra_cmdline.unwrap().into();
+            if let Some(extra_cmdline) = extra_cmdline {
+                header.extra_cmdline = extra_cmdline.into();
             }
         }
         AndroidHeader::V2(ref mut header) => {
-            header.kernel_size = if kernel_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                kernel_data.len() as u32
-            };
-            header.ramdisk_size = if ramdisk_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                ramdisk_data.len() as u32
-            };
-            header.second_size = if second_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                second_data.len() as u32
-            };
-            header.recovery_dtbo_size = if recovery_dtbo_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                recovery_dtbo_data.len() as u32
-            };
-            header.dtb_size = if dtb_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                dtb_data.len() as u32
-            };
-            if cmdline.is_some() {
-                header.cmdline = cmdline.unwrap().into();
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
-            if extra_cmdline.is_some() {
-                header.extra_cmdline = extra_cmdline.unwrap().into();
+            if let Some(extra_cmdline) = extra_cmdline {
+                header.extra_cmdline = extra_cmdline.into();
             }
         }
         AndroidHeader::V3(ref mut header) => {
-            header.kernel_size = if kernel_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                kernel_data.len() as u32
-            };
-            header.ramdisk_size = if ramdisk_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                ramdisk_data.len() as u32
-            };
-            if !second_data.is_empty() {
-                println!("second is not supported on v3");
-            }
-            if !recovery_dtbo_data.is_empty() {
-                println!("recovery_dtbo is not supported on v3");
-            }
-            if !dtb_data.is_empty() {
-                println!("dtb is not supported on v3");
-            }
-            if cmdline.is_some() {
-                header.cmdline = cmdline.unwrap().into();
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
             if extra_cmdline.is_some() {
                 println!("extra_cmdline is not supported on v3");
             }
         }
         AndroidHeader::V4(ref mut header) => {
-            header.kernel_size = if kernel_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                kernel_data.len() as u32
-            };
-            header.ramdisk_size = if ramdisk_data.is_empty() {
-                EMPTY_SIZE
-            } else {
-                ramdisk_data.len() as u32
-            };
-            if !second_data.is_empty() {
-                println!("second is not supported on v4");
-            }
-            if !recovery_dtbo_data.is_empty() {
-                println!("recovery_dtbo is not supported on v4");
-            }
-            if !dtb_data.is_empty() {
-                println!("dtb is not supported on v4");
-            }
-            if cmdline.is_some() {
-                header.cmdline = cmdline.unwrap().into();
+            if let Some(cmdline) = cmdline {
+                header.cmdline = cmdline.into();
             }
             if extra_cmdline.is_some() {
                 println!("extra_cmdline is not supported on v4");
@@ -176,50 +130,141 @@ pub fn update(
         }
     }
 
-    // Get the file and component sizes
-    let page_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.page_size,
-        AndroidHeader::V1(ref header) => header.page_size,
-        AndroidHeader::V2(ref header) => header.page_size,
-        AndroidHeader::V3(_) => PAGE_SIZE_V3,
-        AndroidHeader::V4(_) => PAGE_SIZE_V3,
-    };
-    let kernel_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.kernel_size,
-        AndroidHeader::V1(ref header) => header.kernel_size,
-        AndroidHeader::V2(ref header) => header.kernel_size,
-        AndroidHeader::V3(ref header) => header.kernel_size,
-        AndroidHeader::V4(ref header) => header.kernel_size,
-    };
-    let ramdisk_size = match boot_file.header {
-        AndroidHeader::V0(ref header) => header.ramdisk_size,
-        AndroidHeader::V1(ref header) => header.ramdisk_size,
-        AndroidHeader::V2(ref header) => header.ramdisk_size,
-        AndroidHeader::V3(ref header) => header.ramdisk_size,
-        AndroidHeader::V4(ref header) => header.ramdisk_size,
-    };
-
-    boot_file.save(input_boot_file, page_size)?;
-
-    let mut file = boot_file.get_file();
-    let kernel_offset = page_size;
-    let ramdisk_offset = kernel_offset + kernel_size;
-    let second_offset = ramdisk_offset + ramdisk_size;
-
-    if !kernel_data.is_empty() {
-        file.seek(std::io::SeekFrom::Start(kernel_offset as u64))?;
-        file.write_all(&kernel_data)?;
+    if let Some(id_hash) = id_hash {
+        boot_file.update_id(id_hash)?;
     }
 
-    if !ramdisk_data.is_empty() {
-        file.seek(std::io::SeekFrom::Start(ramdisk_offset as u64))?;
-        file.write_all(&ramdisk_data)?;
+    boot_file.drop_signature_trailers();
+    if strip_trailers {
+        boot_file.trailers.clear();
     }
-
-    if !second_data.is_empty() {
-        file.seek(std::io::SeekFrom::Start(second_offset as u64))?;
-        file.write_all(&second_data)?;
+    for kind in append_trailers {
+        boot_file.append_trailer(kind)?;
     }
 
+    // Rewrite the whole image so every section keeps its alignment
+    write_atomically(input_boot_file, |temp_file| boot_file.save_image(temp_file))?;
+    print_dropped_signatures(&signatures, &boot_file.signature_trailers());
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create::create_v2;
+    use crate::headers::id::IdHash;
+    use crate::headers::layout::BootImageLayout;
+    use std::fs;
+    use std::path::Path;
+
+    fn recovery_dtbo_offset(boot_file: &AndroidBootFile) -> Vec<u8> {
+        match &boot_file.header {
+            AndroidHeader::V2(header) => header.recovery_dtbo_offset.0.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Creates a v2 image in `dir` with 100-byte components, including a
+    /// recovery DTBO of `0xd7` bytes.
+    fn create_image(dir: &Path, id: Option<String>) -> PathBuf {
+        let image = dir.join("boot.img");
+        for (name, data) in [
+            ("kernel", vec![1; 100]),
+            ("ramdisk", vec![2; 100]),
+            ("dtbo", vec![0xd7; 100]),
+        ] {
+            fs::write(dir.join(name), data).unwrap();
+        }
+        create_v2(
+            image.clone(),
+            dir.join("kernel"),
+            dir.join("ramdisk"),
+            None,
+            Some(dir.join("dtbo")),
+            None,
+            2048,
+            "0x00008000".into(),
+            "0x01000000".into(),
+            "0x00000000".into(),
+            "0x00000100".into(),
+            String::new(),
+            String::new(),
+            String::new(),
+            id,
+            IdHash::default(),
+            String::new(),
+            "0x0000000000000000".into(),
+            Vec::new(),
+            None,
+        )
+        .unwrap();
+        image
+    }
+
+    #[test]
+    fn update_moves_recovery_dtbo_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = create_image(dir.path(), None);
+        fs::write(dir.path().join("kernel.new"), vec![3; 10000]).unwrap();
+        let boot_file = AndroidBootFile::open(&image).unwrap();
+        assert_eq!(recovery_dtbo_offset(&boot_file), 6144u64.to_le_bytes());
+
+        update(
+            &image,
+            Some(dir.path().join("kernel.new")),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            Vec::new(),
+        )
+        .unwrap();
+        let boot_file = AndroidBootFile::open(&image).unwrap();
+        let layout = BootImageLayout::new(&boot_file.header);
+        let section = layout.section(BootComponent::RecoveryDtbo).unwrap();
+        assert_eq!(section.offset, 14336);
+        assert_eq!(
+            recovery_dtbo_offset(&boot_file),
+            section.offset.to_le_bytes()
+        );
+        assert_eq!(
+            boot_file
+                .get_component(BootComponent::RecoveryDtbo)
+                .unwrap(),
+            vec![0xd7; 100]
+        );
+    }
+
+    #[test]
+    fn update_keeps_a_custom_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = create_image(dir.path(), Some("custom".into()));
+        let id = AndroidBootFile::open(&image)
+            .unwrap()
+            .header
+            .id()
+            .unwrap()
+            .to_vec();
+
+        update(
+            &image,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some("console=ttyMSM0".into()),
+            None,
+            false,
+            Vec::new(),
+        )
+        .unwrap();
+        let boot_file = AndroidBootFile::open(&image).unwrap();
+        assert_eq!(boot_file.header.id().unwrap(), id);
+        assert_eq!(boot_file.verify_id().unwrap(), None);
+    }
+}
@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::commands::output::write_atomically;
+use crate::commands::signature::print_dropped_signatures;
+use crate::errors::AbootCrafterError;
+use crate::headers::fields::VendorRamdiskName;
+use crate::headers::vendor::{
+    VendorBootFile, VendorRamdiskFragment, VendorRamdiskTableEntry, VendorRamdiskType,
+};
+
+fn load_fragments(
+    input_vendor_boot_file: &PathBuf,
+) -> Result<(VendorBootFile, Vec<VendorRamdiskFragment>), AbootCrafterError> {
+    let mut vendor_boot_file = VendorBootFile::default();
+    vendor_boot_file.load(input_vendor_boot_file)?;
+
+    if vendor_boot_file.version < 4 {
+        return Err(AbootCrafterError::ConfigError(
+            "Vendor ramdisk fragments are only supported on vendor boot v4".to_string(),
+        ));
+    }
+
+    let fragments = vendor_boot_file.read_vendor_ramdisk_fragments()?;
+    Ok((vendor_boot_file, fragments))
+}
+
+fn save_fragments(
+    input_vendor_boot_file: &PathBuf,
+    mut vendor_boot_file: VendorBootFile,
+    fragments: &[VendorRamdiskFragment],
+) -> Result<(), AbootCrafterError> {
+    let dtb = vendor_boot_file.read_dtb()?;
+    let bootconfig = vendor_boot_file.read_bootconfig()?;
+    let signatures = vendor_boot_file.signature_trailers();
+    write_atomically(input_vendor_boot_file, |temp_file| {
+        vendor_boot_file.save_v4(temp_file, fragments, &dtb, &bootconfig)
+    })?;
+    print_dropped_signatures(&signatures, &vendor_boot_file.signature_trailers());
+    Ok(())
+}
+
+fn fragment_file_name(index: usize, entry: &VendorRamdiskTableEntry) -> String {
+    let name = entry.ramdisk_name.to_string();
+    if name.is_empty() {
+        format!("vendor_ramdisk{:02}", index)
+    } else {
+        format!("vendor_ramdisk{:02}_{}", index, name)
+    }
+}
+
+/// Prints the vendor ramdisk table entries of a vendor boot image.
+pub fn print_vendor_ramdisk_table(entries: &[VendorRamdiskTableEntry]) {
+    println!("[Vendor Ramdisk Table]");
+    for (index, entry) in entries.iter().enumerate() {
+        let board_id: Vec<String> = entry
+            .board_id
+            .iter()
+            .map(|id| format!("0x{:08x}", id))
+            .collect();
+        println!("Fragment {}:", index);
+        println!("  Name: {}", entry.ramdisk_name);
+        println!("  Type: {}", VendorRamdiskType::from(entry.ramdisk_type));
+        println!("  Size: {}", entry.ramdisk_size);
+        println!("  Offset: {}", entry.ramdisk_offset);
+        println!("  Board ID: {}", board_id.join(" "));
+    }
+}
+
+/// Lists the vendor ramdisk fragments of a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+pub fn list(input_vendor_boot_file: &PathBuf) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, _) = load_fragments(input_vendor_boot_file)?;
+    print_vendor_ramdisk_table(&vendor_boot_file.read_vendor_ramdisk_table()?);
+    Ok(())
+}
+
+/// Extracts vendor ramdisk fragments from a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `output_dir` - Optional path to the output directory.
+/// * `index` - Optional fragment index; every fragment is extracted if omitted.
+pub fn extract(
+    input_vendor_boot_file: &PathBuf,
+    output_dir: Option<PathBuf>,
+    index: Option<usize>,
+) -> Result<(), AbootCrafterError> {
+    let (_, fragments) = load_fragments(input_vendor_boot_file)?;
+
+    // Determine the output directory name
+    let directory_name = if let Some(output_dir) = output_dir {
+        output_dir
+    } else {
+        let file_name = input_vendor_boot_file.file_name().unwrap();
+        PathBuf::from(format!("{}_fragments", file_name.to_str().unwrap()))
+    };
+    fs::create_dir_all(&directory_name)?;
+
+    for (fragment_index, fragment) in fragments.iter().enumerate() {
+        if index.is_some_and(|index| index != fragment_index) {
+            continue;
+        }
+        let path = directory_name.join(fragment_file_name(fragment_index, &fragment.entry));
+        fs::write(path, &fragment.data)?;
+    }
+
+    if index.is_some_and(|index| index >= fragments.len()) {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "Vendor ramdisk fragment {} does not exist",
+            index.unwrap()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Appends a new vendor ramdisk fragment to a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `fragment_file` - The ramdisk fragment to add.
+/// * `ramdisk_type` - The type of the fragment (platform, recovery, dlkm).
+/// * `name` - The name of the fragment.
+/// * `board_id` - Up to 16 board id words.
+pub fn add(
+    input_vendor_boot_file: &PathBuf,
+    fragment_file: PathBuf,
+    ramdisk_type: u32,
+    name: String,
+    board_id: Vec<u32>,
+) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, mut fragments) = load_fragments(input_vendor_boot_file)?;
+
+    if board_id.len() > 16 {
+        return Err(AbootCrafterError::ConfigError(
+            "Board ID can have at most 16 words".to_string(),
+        ));
+    }
+    let mut board_id_words = [0u32; 16];
+    board_id_words[..board_id.len()].copy_from_slice(&board_id);
+
+    let data = fs::read(fragment_file)?;
+    fragments.push(VendorRamdiskFragment {
+        entry: VendorRamdiskTableEntry {
+            ramdisk_size: data.len() as u32,
+            ramdisk_offset: 0,
+            ramdisk_type,
+            ramdisk_name: VendorRamdiskName::from(name),
+            board_id: board_id_words,
+        },
+        data,
+    });
+
+    save_fragments(input_vendor_boot_file, vendor_boot_file, &fragments)
+}
+
+/// Removes a vendor ramdisk fragment from a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `index` - The index of the fragment to remove.
+pub fn remove(input_vendor_boot_file: &PathBuf, index: usize) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, mut fragments) = load_fragments(input_vendor_boot_file)?;
+
+    if index >= fragments.len() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "Vendor ramdisk fragment {} does not exist",
+            index
+        )));
+    }
+    fragments.remove(index);
+
+    save_fragments(input_vendor_boot_file, vendor_boot_file, &fragments)
+}
+
+/// Reorders the vendor ramdisk fragments of a vendor boot v4 image.
+///
+/// # Arguments
+///
+/// * `input_vendor_boot_file` - The path to the Android vendor boot image file.
+/// * `order` - The current fragment indexes, listed in their new order.
+pub fn reorder(
+    input_vendor_boot_file: &PathBuf,
+    order: Vec<usize>,
+) -> Result<(), AbootCrafterError> {
+    let (vendor_boot_file, fragments) = load_fragments(input_vendor_boot_file)?;
+
+    let mut sorted_order = order.clone();
+    sorted_order.sort_unstable();
+    if sorted_order != (0..fragments.len()).collect::<Vec<_>>() {
+        return Err(AbootCrafterError::ConfigError(format!(
+            "Order must list every fragment index from 0 to {} exactly once",
+            fragments.len().saturating_sub(1)
+        )));
+    }
+
+    let reordered: Vec<VendorRamdiskFragment> = order
+        .iter()
+        .map(|&index| fragments[index].clone())
+        .collect();
+
+    save_fragments(input_vendor_boot_file, vendor_boot_file, &reordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create::tests::vendor_boot_v4;
+    use crate::headers::avb::{AvbFooter, AvbHashFooterOptions};
+    use crate::headers::trailer::{TrailerKind, SEANDROIDENFORCE_MAGIC};
+
+    #[test]
+    fn table_follows_added_reordered_and_removed_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = vendor_boot_v4(dir.path());
+        fs::write(dir.path().join("dlkm"), vec![2; 700]).unwrap();
+
+        add(
+            &fixture.image,
+            dir.path().join("dlkm"),
+            3,
+            "dlkm".into(),
+            vec![1, 2],
+        )
+        .unwrap();
+        reorder(&fixture.image, vec![1, 0]).unwrap();
+
+        let (vendor_boot_file, fragments) = load_fragments(&fixture.image).unwrap();
+        let entries: Vec<_> = fragments
+            .iter()
+            .map(|fragment| {
+                let entry = &fragment.entry;
+                (
+                    entry.ramdisk_name.to_string(),
+                    entry.ramdisk_offset,
+                    entry.ramdisk_size,
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [("dlkm".to_string(), 0, 700), (String::new(), 700, 5000)]
+        );
+        assert_eq!(fragments[0].entry.board_id[..3], [1, 2, 0]);
+        assert_eq!(fragments[1].data, fixture.vendor_ramdisk);
+        assert_eq!(vendor_boot_file.read_dtb().unwrap(), fixture.dtb);
+
+        remove(&fixture.image, 0).unwrap();
+        let (_, fragments) = load_fragments(&fixture.image).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].entry.ramdisk_offset, 0);
+        assert_eq!(fragments[0].data, fixture.vendor_ramdisk);
+    }
+
+    #[test]
+    fn edits_keep_markers_and_drop_the_avb_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = vendor_boot_v4(dir.path());
+        let mut data = fs::read(&fixture.image).unwrap();
+        data.extend_from_slice(SEANDROIDENFORCE_MAGIC);
+        let options = AvbHashFooterOptions {
+            partition_name: "vendor_boot".to_string(),
+            partition_size: Some(64 * 1024),
+            ..Default::default()
+        };
+        AvbFooter::add_hash_footer(&mut data, &options).unwrap();
+        fs::write(&fixture.image, &data).unwrap();
+
+        let (vendor_boot_file, _) = load_fragments(&fixture.image).unwrap();
+        assert_eq!(
+            vendor_boot_file.signature_trailers(),
+            [TrailerKind::AvbFooter]
+        );
+
+        remove(&fixture.image, 0).unwrap();
+        let data = fs::read(&fixture.image).unwrap();
+        assert!(data.ends_with(SEANDROIDENFORCE_MAGIC));
+        let (vendor_boot_file, _) = load_fragments(&fixture.image).unwrap();
+        let kinds: Vec<TrailerKind> = vendor_boot_file
+            .trailers
+            .iter()
+            .map(|trailer| trailer.kind)
+            .collect();
+        assert_eq!(kinds, [TrailerKind::SeAndroidEnforce]);
+    }
+}
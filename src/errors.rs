@@ -7,4 +7,7 @@ pub enum AbootCrafterError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("Verification failed: {0}")]
+    VerificationError(String),
 }
@@ -1,17 +1,33 @@
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{File, OpenOptions},
-    io::{self, Seek, SeekFrom, Write},
+    io::{self, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
+use super::avb::{AvbAlgorithm, AvbDescriptor, AvbHashDescriptor, VbmetaImage};
 use super::fields::{
     AddressU32, AddressU64, AndroidBootMagic, Cmdline, CmdlineExtended, ExtraCmdline, Id, Name,
     OSVersion,
 };
+use super::id::IdHash;
+use super::layout::{align, BootImageLayout, PAGE_SIZES};
+use super::trailer::{Trailer, TrailerKind};
 use binrw::{BinRead, BinWrite};
+use rsa::RsaPrivateKey;
 
 pub const PAGE_SIZE_V3: u32 = 4096;
 
+pub const HEADER_SIZE_V0: u32 = 1632;
+pub const HEADER_SIZE_V1: u32 = 1648;
+pub const HEADER_SIZE_V2: u32 = 1660;
+pub const HEADER_SIZE_V3: u32 = 1580;
+pub const HEADER_SIZE_V4: u32 = 1584;
+
+/// Size of the boot signature section mkbootimg reserves in v4 boot images.
+pub const BOOT_SIGNATURE_SIZE_V4: u32 = 16 * 1024;
+
 #[derive(Debug, Default, BinRead, BinWrite)]
 #[br(little)]
 pub struct AndroidHeaderVersion0 {
@@ -32,6 +48,29 @@ pub struct AndroidHeaderVersion0 {
     pub extra_cmdline: ExtraCmdline,
 }
 
+/// Qualcomm variant of the v0 header, which stores the size of a device tree
+/// section (usually a QCDT table) where later versions put `header_version`.
+/// The DT section follows the second stage.
+#[derive(Debug, Default, BinRead, BinWrite)]
+#[br(little)]
+pub struct AndroidHeaderVersion0Qcom {
+    pub magic: AndroidBootMagic,
+    pub kernel_size: u32,
+    pub kernel_addr: AddressU32,
+    pub ramdisk_size: u32,
+    pub ramdisk_addr: AddressU32,
+    pub second_size: u32,
+    pub second_addr: AddressU32,
+    pub tags_addr: AddressU32,
+    pub page_size: u32,
+    pub dt_size: u32,
+    pub os_version: OSVersion,
+    pub name: Name,
+    pub cmdline: Cmdline,
+    pub id: Id,
+    pub extra_cmdline: ExtraCmdline,
+}
+
 #[derive(Debug, Default, BinRead, BinWrite)]
 #[br(little)]
 pub struct AndroidHeaderVersion1 {
@@ -107,16 +146,61 @@ pub struct AndroidHeaderVersion4 {
     pub signature_size: u32,
 }
 
+/// The sections a boot image can carry, in on-disk order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BootComponent {
+    Kernel,
+    Ramdisk,
+    Second,
+    RecoveryDtbo,
+    Dtb,
+    Signature,
+}
+
+impl BootComponent {
+    pub const ALL: [BootComponent; 6] = [
+        BootComponent::Kernel,
+        BootComponent::Ramdisk,
+        BootComponent::Second,
+        BootComponent::RecoveryDtbo,
+        BootComponent::Dtb,
+        BootComponent::Signature,
+    ];
+
+    /// Name used for the extracted file of this component
+    pub fn name(&self) -> &'static str {
+        match self {
+            BootComponent::Kernel => "kernel",
+            BootComponent::Ramdisk => "ramdisk",
+            BootComponent::Second => "second",
+            BootComponent::RecoveryDtbo => "recovery_dtbo",
+            BootComponent::Dtb => "dtb",
+            BootComponent::Signature => "signature",
+        }
+    }
+}
+
+impl fmt::Display for BootComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct AndroidBootFile {
     pub header: AndroidHeader,
     pub version: u32,
     pub file: Option<File>,
+    /// Components replaced in memory, which take precedence over the file
+    pub components: BTreeMap<BootComponent, Vec<u8>>,
+    /// Data after the last section, written back after it on save
+    pub trailers: Vec<Trailer>,
 }
 
 #[derive(Debug)]
 pub enum AndroidHeader {
     V0(AndroidHeaderVersion0),
+    V0Qcom(AndroidHeaderVersion0Qcom),
     V1(AndroidHeaderVersion1),
     V2(AndroidHeaderVersion2),
     V3(AndroidHeaderVersion3),
@@ -129,6 +213,204 @@ impl Default for AndroidHeader {
     }
 }
 
+impl AndroidHeader {
+    pub fn version(&self) -> u32 {
+        match self {
+            AndroidHeader::V0(_) | AndroidHeader::V0Qcom(_) => 0,
+            AndroidHeader::V1(_) => 1,
+            AndroidHeader::V2(_) => 2,
+            AndroidHeader::V3(_) => 3,
+            AndroidHeader::V4(_) => 4,
+        }
+    }
+
+    pub fn page_size(&self) -> u32 {
+        match self {
+            AndroidHeader::V0(header) => header.page_size,
+            AndroidHeader::V0Qcom(header) => header.page_size,
+            AndroidHeader::V1(header) => header.page_size,
+            AndroidHeader::V2(header) => header.page_size,
+            AndroidHeader::V3(_) | AndroidHeader::V4(_) => PAGE_SIZE_V3,
+        }
+    }
+
+    /// Size of the header structure on disk, before padding.
+    pub fn header_size(&self) -> u32 {
+        match self {
+            AndroidHeader::V0(_) | AndroidHeader::V0Qcom(_) => HEADER_SIZE_V0,
+            AndroidHeader::V1(_) => HEADER_SIZE_V1,
+            AndroidHeader::V2(_) => HEADER_SIZE_V2,
+            AndroidHeader::V3(_) => HEADER_SIZE_V3,
+            AndroidHeader::V4(_) => HEADER_SIZE_V4,
+        }
+    }
+
+    /// Stores the header size in the `header_size` field of versions that have one.
+    pub fn update_header_size(&mut self) {
+        let header_size = self.header_size();
+        match self {
+            AndroidHeader::V0(_) | AndroidHeader::V0Qcom(_) => {}
+            AndroidHeader::V1(header) => header.header_size = header_size,
+            AndroidHeader::V2(header) => header.header_size = header_size,
+            AndroidHeader::V3(header) => header.header_size = header_size,
+            AndroidHeader::V4(header) => header.header_size = header_size,
+        }
+    }
+
+    /// Components this header version has a size field for, in on-disk order.
+    pub fn supported_components(&self) -> &'static [BootComponent] {
+        match self {
+            AndroidHeader::V0(_) => &BootComponent::ALL[..3],
+            AndroidHeader::V0Qcom(_) => &[
+                BootComponent::Kernel,
+                BootComponent::Ramdisk,
+                BootComponent::Second,
+                BootComponent::Dtb,
+            ],
+            AndroidHeader::V1(_) => &BootComponent::ALL[..4],
+            AndroidHeader::V2(_) => &BootComponent::ALL[..5],
+            AndroidHeader::V3(_) => &BootComponent::ALL[..2],
+            AndroidHeader::V4(_) => &[
+                BootComponent::Kernel,
+                BootComponent::Ramdisk,
+                BootComponent::Signature,
+            ],
+        }
+    }
+
+    pub fn supports(&self, component: BootComponent) -> bool {
+        self.supported_components().contains(&component)
+    }
+
+    /// Size of a component, or `None` if this header version does not have it.
+    pub fn component_size(&self, component: BootComponent) -> Option<u32> {
+        use BootComponent::*;
+        match (self, component) {
+            (AndroidHeader::V0(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V0(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V0(header), Second) => Some(header.second_size),
+            (AndroidHeader::V0Qcom(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V0Qcom(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V0Qcom(header), Second) => Some(header.second_size),
+            (AndroidHeader::V0Qcom(header), Dtb) => Some(header.dt_size),
+            (AndroidHeader::V1(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V1(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V1(header), Second) => Some(header.second_size),
+            (AndroidHeader::V1(header), RecoveryDtbo) => Some(header.recovery_dtbo_size),
+            (AndroidHeader::V2(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V2(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V2(header), Second) => Some(header.second_size),
+            (AndroidHeader::V2(header), RecoveryDtbo) => Some(header.recovery_dtbo_size),
+            (AndroidHeader::V2(header), Dtb) => Some(header.dtb_size),
+            (AndroidHeader::V3(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V3(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V4(header), Kernel) => Some(header.kernel_size),
+            (AndroidHeader::V4(header), Ramdisk) => Some(header.ramdisk_size),
+            (AndroidHeader::V4(header), Signature) => Some(header.signature_size),
+            _ => None,
+        }
+    }
+
+    /// Sets the size of a component, failing if this header version does not have it.
+    pub fn set_component_size(&mut self, component: BootComponent, size: u32) -> io::Result<()> {
+        use BootComponent::*;
+        let version = self.version();
+        let field = match (self, component) {
+            (AndroidHeader::V0(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V0(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V0(header), Second) => &mut header.second_size,
+            (AndroidHeader::V0Qcom(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V0Qcom(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V0Qcom(header), Second) => &mut header.second_size,
+            (AndroidHeader::V0Qcom(header), Dtb) => &mut header.dt_size,
+            (AndroidHeader::V1(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V1(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V1(header), Second) => &mut header.second_size,
+            (AndroidHeader::V1(header), RecoveryDtbo) => &mut header.recovery_dtbo_size,
+            (AndroidHeader::V2(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V2(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V2(header), Second) => &mut header.second_size,
+            (AndroidHeader::V2(header), RecoveryDtbo) => &mut header.recovery_dtbo_size,
+            (AndroidHeader::V2(header), Dtb) => &mut header.dtb_size,
+            (AndroidHeader::V3(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V3(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V4(header), Kernel) => &mut header.kernel_size,
+            (AndroidHeader::V4(header), Ramdisk) => &mut header.ramdisk_size,
+            (AndroidHeader::V4(header), Signature) => &mut header.signature_size,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{} is not supported on v{}", component, version),
+                ))
+            }
+        };
+        *field = size;
+        Ok(())
+    }
+
+    /// The `id` field, or `None` for header versions without one.
+    pub fn id(&self) -> Option<&[u8]> {
+        match self {
+            AndroidHeader::V0(header) => Some(&header.id.0),
+            AndroidHeader::V0Qcom(header) => Some(&header.id.0),
+            AndroidHeader::V1(header) => Some(&header.id.0),
+            AndroidHeader::V2(header) => Some(&header.id.0),
+            AndroidHeader::V3(_) | AndroidHeader::V4(_) => None,
+        }
+    }
+
+    /// Stores `id` in header versions that have an `id` field.
+    pub fn set_id(&mut self, id: Id) {
+        match self {
+            AndroidHeader::V0(header) => header.id = id,
+            AndroidHeader::V0Qcom(header) => header.id = id,
+            AndroidHeader::V1(header) => header.id = id,
+            AndroidHeader::V2(header) => header.id = id,
+            AndroidHeader::V3(_) | AndroidHeader::V4(_) => {}
+        }
+    }
+
+    /// Stores the offset of the recovery DTBO section in header versions
+    /// that have a `recovery_dtbo_offset` field.
+    pub fn set_recovery_dtbo_offset(&mut self, offset: u64) {
+        let offset = AddressU64(offset.to_le_bytes().to_vec());
+        match self {
+            AndroidHeader::V1(header) => header.recovery_dtbo_offset = offset,
+            AndroidHeader::V2(header) => header.recovery_dtbo_offset = offset,
+            _ => {}
+        }
+    }
+
+    /// Serializes the header, without the padding up to the page size.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut writer = io::Cursor::new(Vec::new());
+        let result = match self {
+            AndroidHeader::V0(header) => header.write_le(&mut writer),
+            AndroidHeader::V0Qcom(header) => header.write_le(&mut writer),
+            AndroidHeader::V1(header) => header.write_le(&mut writer),
+            AndroidHeader::V2(header) => header.write_le(&mut writer),
+            AndroidHeader::V3(header) => header.write_le(&mut writer),
+            AndroidHeader::V4(header) => header.write_le(&mut writer),
+        };
+        result.map_err(io::Error::other)?;
+        Ok(writer.into_inner())
+    }
+
+    /// Alignment of a component inside the image. Every section starts on a
+    /// page boundary.
+    pub fn component_alignment(&self, _component: BootComponent) -> u32 {
+        self.page_size()
+    }
+
+    /// Offset of a component inside the image, or `None` if this header
+    /// version does not have it.
+    pub fn component_offset(&self, component: BootComponent) -> Option<u64> {
+        BootImageLayout::new(self)
+            .section(component)
+            .map(|section| section.offset)
+    }
+}
+
 impl AndroidBootFile {
     fn detect_version(file: &mut File) -> io::Result<u32> {
         file.seek(SeekFrom::Start(0))?;
@@ -142,6 +424,32 @@ impl AndroidBootFile {
         Ok(version)
     }
 
+    /// Reads a v0 header whose `header_version` field holds something else
+    /// than a known version. It is the QCOM variant if the field, read as a
+    /// DT size, makes the DT section end within the file; a real DT table is
+    /// never small enough to be mistaken for versions 1 to 4.
+    fn read_v0_variant(file: &mut File) -> binrw::BinResult<AndroidHeader> {
+        let file_size = file.metadata()?.len();
+        let qcom_header = AndroidHeader::V0Qcom(AndroidHeaderVersion0Qcom::read_le(file)?);
+        let dt_fits = PAGE_SIZES.contains(&qcom_header.page_size())
+            && BootImageLayout::new(&qcom_header)
+                .section(BootComponent::Dtb)
+                .is_some_and(|section| section.offset + section.size as u64 <= file_size);
+        if dt_fits {
+            return Ok(qcom_header);
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        AndroidHeaderVersion0::read_le(file).map(AndroidHeader::V0)
+    }
+
+    /// Opens and parses the image at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut boot_file = AndroidBootFile::default();
+        boot_file.load(path)?;
+        Ok(boot_file)
+    }
+
     pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
         let mut file = File::open(path)?;
         let version = Self::detect_version(&mut file)?;
@@ -153,6 +461,7 @@ impl AndroidBootFile {
                     version: 0,
                     header,
                     file: Some(file),
+                    ..Default::default()
                 }),
             1 => AndroidHeaderVersion1::read_le(&mut file)
                 .map(AndroidHeader::V1)
@@ -160,6 +469,7 @@ impl AndroidBootFile {
                     version: 1,
                     header,
                     file: Some(file),
+                    ..Default::default()
                 }),
             2 => AndroidHeaderVersion2::read_le(&mut file)
                 .map(AndroidHeader::V2)
@@ -167,6 +477,7 @@ impl AndroidBootFile {
                     version: 2,
                     header,
                     file: Some(file),
+                    ..Default::default()
                 }),
             3 => AndroidHeaderVersion3::read_le(&mut file)
                 .map(AndroidHeader::V3)
@@ -174,6 +485,7 @@ impl AndroidBootFile {
                     version: 3,
                     header,
                     file: Some(file),
+                    ..Default::default()
                 }),
             4 => AndroidHeaderVersion4::read_le(&mut file)
                 .map(AndroidHeader::V4)
@@ -181,18 +493,30 @@ impl AndroidBootFile {
                     version: 4,
                     header,
                     file: Some(file),
+                    ..Default::default()
                 }),
-            _ => AndroidHeaderVersion0::read_le(&mut file)
-                .map(AndroidHeader::V0)
-                .map(|header| AndroidBootFile {
-                    version: 0,
-                    header,
-                    file: Some(file),
-                }),
+            // Any other value is either a QCOM DT size or garbage in an
+            // old v0 header
+            _ => Self::read_v0_variant(&mut file).map(|header| AndroidBootFile {
+                version: 0,
+                header,
+                file: Some(file),
+                ..Default::default()
+            }),
         };
 
         match result {
             Ok(mut boot_file) => {
+                // Sections are aligned to the page size, which has to be
+                // one mkbootimg accepts before anything past the header is read
+                let page_size = boot_file.header.page_size();
+                if !PAGE_SIZES.contains(&page_size) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid page size: {}", page_size),
+                    ));
+                }
+                boot_file.trailers = boot_file.read_trailers()?;
                 // Just to be sure we are at the beginning of the file
                 if let Some(ref mut file) = boot_file.file {
                     file.seek(SeekFrom::Start(0))?;
@@ -200,38 +524,272 @@ impl AndroidBootFile {
                 *self = boot_file;
                 Ok(())
             }
-            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+            Err(err) => Err(io::Error::other(err)),
         }
     }
 
     pub fn save<P: AsRef<Path>>(&mut self, path: P, page_size: u32) -> io::Result<()> {
         let mut file = OpenOptions::new()
+            .read(true)
             .write(true)
             .create(true)
             .truncate(true)
             .open(path)?;
 
-        let result = match &self.header {
-            AndroidHeader::V0(header) => header.write_le(&mut file),
-            AndroidHeader::V1(header) => header.write_le(&mut file),
-            AndroidHeader::V2(header) => header.write_le(&mut file),
-            AndroidHeader::V3(header) => header.write_le(&mut file),
-            AndroidHeader::V4(header) => header.write_le(&mut file),
+        // The header is padded to a page boundary, like the layout expects
+        let mut data = self.header.to_bytes()?;
+        data.resize(align(data.len() as u64, page_size) as usize, 0);
+        file.write_all(&data)?;
+
+        self.file = Some(file); // Store the file handle
+        Ok(())
+    }
+
+    /// Reads and classifies whatever the loaded file holds after its last section.
+    fn read_trailers(&self) -> io::Result<Vec<Trailer>> {
+        let Some(mut file) = self.file.as_ref() else {
+            return Ok(Vec::new());
         };
+        let image_size = BootImageLayout::new(&self.header).image_size();
+        if file.metadata()?.len() <= image_size {
+            return Ok(Vec::new());
+        }
 
-        let zeros = vec![0u8; page_size as usize - file.stream_position()? as usize];
-        file.write_all(&zeros)?; // Write zeroes
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(image_size))?;
+        file.read_to_end(&mut data)?;
+        Ok(Trailer::parse_all(&data, image_size))
+    }
 
-        match result {
-            Ok(_) => {
-                self.file = Some(file); // Store the file handle
-                Ok(())
-            }
-            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+    /// Kinds of the trailers that sign the image, in file order.
+    pub fn signature_trailers(&self) -> Vec<TrailerKind> {
+        self.trailers
+            .iter()
+            .map(|trailer| trailer.kind)
+            .filter(TrailerKind::is_signature)
+            .collect()
+    }
+
+    /// Drops the trailers that sign the image, which stop matching once the
+    /// header or a component changes. Markers and padding are kept.
+    pub fn drop_signature_trailers(&mut self) {
+        self.trailers.retain(|trailer| !trailer.kind.is_signature());
+    }
+
+    /// Appends a marker trailer unless the image already has one of its kind.
+    /// Markers go before an AVB footer, which must stay last.
+    pub fn append_trailer(&mut self, kind: TrailerKind) -> io::Result<()> {
+        let trailer = Trailer::marker(kind)
+            .ok_or_else(|| io::Error::other(format!("{} trailers cannot be appended", kind)))?;
+        if self.trailers.iter().any(|existing| existing.kind == kind) {
+            return Ok(());
         }
+
+        let position = self
+            .trailers
+            .iter()
+            .position(|existing| existing.kind == TrailerKind::AvbFooter)
+            .unwrap_or(self.trailers.len());
+        self.trailers.insert(position, trailer);
+        Ok(())
     }
 
     pub fn get_file(&self) -> &File {
         self.file.as_ref().unwrap()
     }
+
+    /// Writes the whole image to `path`: the header followed by every
+    /// component, laid out as described by [`BootImageLayout`], and the
+    /// trailers.
+    pub fn save_image<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        // Everything is read first, as `path` may be the loaded file itself
+        let mut components = Vec::new();
+        for &component in self.header.supported_components() {
+            components.push(self.get_component(component)?);
+        }
+
+        self.header.update_header_size();
+        let layout = BootImageLayout::new(&self.header);
+        // Like mkbootimg, the offset always points at the section as laid out
+        let recovery_dtbo_offset = layout
+            .section(BootComponent::RecoveryDtbo)
+            .filter(|section| section.size > 0)
+            .map_or(0, |section| section.offset);
+        self.header.set_recovery_dtbo_offset(recovery_dtbo_offset);
+        self.save(path, layout.page_size)?;
+
+        let mut file = self.get_file();
+        for (section, data) in layout.sections.iter().zip(components) {
+            file.write_all(&data)?;
+            file.write_all(&vec![0u8; section.padding as usize])?;
+        }
+        for trailer in &self.trailers {
+            file.write_all(&trailer.data)?;
+        }
+        Ok(())
+    }
+
+    /// Computes the mkbootimg id over the current components, or `None` for
+    /// header versions without an `id` field.
+    pub fn compute_id(&self, hash: IdHash) -> io::Result<Option<Vec<u8>>> {
+        if self.header.id().is_none() {
+            return Ok(None);
+        }
+
+        let mut components = Vec::new();
+        for &component in self.header.supported_components() {
+            components.push((component, self.get_component(component)?));
+        }
+        Ok(Some(hash.compute(&components)))
+    }
+
+    /// Recomputes the `id` field from the current components.
+    pub fn update_id(&mut self, hash: IdHash) -> io::Result<()> {
+        if let Some(id) = self.compute_id(hash)? {
+            self.header.set_id(Id(id));
+        }
+        Ok(())
+    }
+
+    /// Returns the digest the stored `id` was computed with, or `None` if it
+    /// does not match the components with any of them.
+    pub fn verify_id(&self) -> io::Result<Option<IdHash>> {
+        let Some(stored_id) = self.header.id() else {
+            return Ok(None);
+        };
+        for hash in IdHash::ALL {
+            if self.compute_id(hash)?.as_deref() == Some(stored_id) {
+                return Ok(Some(hash));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Generates the boot signature section of a v4 boot image, like
+    /// mkbootimg does for GKI certification.
+    ///
+    /// The section holds two vbmeta structs, signed with `key`: one with a
+    /// "boot" hash descriptor of the image up to the signature section, and
+    /// one with a "generic_kernel" hash descriptor of the kernel. The image
+    /// digest is computed with the section already sized to 16 KiB, so it
+    /// matches the image as saved.
+    pub fn generate_boot_signature(
+        &mut self,
+        algorithm: AvbAlgorithm,
+        key: Option<&RsaPrivateKey>,
+        salt: Option<Vec<u8>>,
+    ) -> io::Result<()> {
+        let section_size = BOOT_SIGNATURE_SIZE_V4 as usize;
+        self.replace_component(BootComponent::Signature, vec![0; section_size])?;
+        self.header.update_header_size();
+
+        let layout = BootImageLayout::new(&self.header);
+        let mut image = self.header.to_bytes()?;
+        image.resize(layout.page_size as usize, 0);
+        for section in &layout.sections {
+            if section.component == BootComponent::Signature {
+                break;
+            }
+            image.extend(self.get_component(section.component)?);
+            image.resize(image.len() + section.padding as usize, 0);
+        }
+        let kernel = self.get_component(BootComponent::Kernel)?;
+
+        let mut signature = Vec::new();
+        for (partition_name, data) in [("boot", &image), ("generic_kernel", &kernel)] {
+            let descriptor = AvbDescriptor::Hash(AvbHashDescriptor::compute(
+                partition_name,
+                data,
+                salt.clone(),
+            ));
+            signature.extend(VbmetaImage::build(&[descriptor], algorithm, key, 0, 0, 0)?);
+        }
+        if signature.len() > section_size {
+            return Err(io::Error::other(format!(
+                "Boot signature is {} bytes, larger than the {} byte section",
+                signature.len(),
+                section_size
+            )));
+        }
+        signature.resize(section_size, 0);
+        self.replace_component(BootComponent::Signature, signature)
+    }
+
+    /// Reads a component as it is stored in the loaded file.
+    pub fn read_component(&self, component: BootComponent) -> io::Result<Vec<u8>> {
+        let (Some(offset), Some(size)) = (
+            self.header.component_offset(component),
+            self.header.component_size(component),
+        ) else {
+            return Ok(Vec::new());
+        };
+
+        // A new image without a backing file has no stored components
+        let Some(mut file) = self.file.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let mut buf = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Returns a component, preferring the in-memory replacement if any.
+    pub fn get_component(&self, component: BootComponent) -> io::Result<Vec<u8>> {
+        match self.components.get(&component) {
+            Some(data) => Ok(data.clone()),
+            None => self.read_component(component),
+        }
+    }
+
+    /// Replaces a component in memory and updates its size in the header.
+    pub fn replace_component(&mut self, component: BootComponent, data: Vec<u8>) -> io::Result<()> {
+        // Offsets in the file are only valid for the original sizes, so
+        // everything is read before any size changes
+        if self.file.is_some() {
+            for &other in self.header.supported_components() {
+                if !self.components.contains_key(&other) {
+                    let other_data = self.read_component(other)?;
+                    self.components.insert(other, other_data);
+                }
+            }
+        }
+
+        self.header
+            .set_component_size(component, data.len() as u32)?;
+        self.components.insert(component, data);
+        self.drop_signature_trailers();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// An empty image with the given page size and header version.
+    fn image(page_size: u32, header_version: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 8192];
+        data[..8].copy_from_slice(&AndroidBootMagic::default().0);
+        data[36..40].copy_from_slice(&page_size.to_le_bytes());
+        data[40..44].copy_from_slice(&header_version.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn load_rejects_invalid_page_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("boot.img");
+        for (page_size, header_version) in [(0, 0), (0, 2), (3000, 1), (1024, 2), (0, 0x1000)] {
+            fs::write(&path, image(page_size, header_version)).unwrap();
+            let err = AndroidBootFile::open(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), format!("Invalid page size: {}", page_size));
+        }
+
+        fs::write(&path, image(2048, 2)).unwrap();
+        let boot_file = AndroidBootFile::open(&path).unwrap();
+        assert_eq!(boot_file.header.page_size(), 2048);
+    }
 }
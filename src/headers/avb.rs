@@ -0,0 +1,1074 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use binrw::{BinRead, BinWrite};
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    rand_core::{OsRng, RngCore},
+    traits::PublicKeyParts,
+    BigUint, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256, Sha512};
+
+use super::layout::align;
+
+pub const AVB_FOOTER_MAGIC: &[u8; 4] = b"AVBf";
+pub const AVB_FOOTER_SIZE: u64 = 64;
+pub const AVB_VBMETA_MAGIC: &[u8; 4] = b"AVB0";
+pub const AVB_VBMETA_HEADER_SIZE: u64 = 256;
+
+/// Block size avbtool aligns the image, the vbmeta struct and the footer to.
+pub const AVB_BLOCK_SIZE: u32 = 4096;
+
+pub const AVB_DESCRIPTOR_TAG_PROPERTY: u64 = 0;
+pub const AVB_DESCRIPTOR_TAG_HASHTREE: u64 = 1;
+pub const AVB_DESCRIPTOR_TAG_HASH: u64 = 2;
+pub const AVB_DESCRIPTOR_TAG_KERNEL_CMDLINE: u64 = 3;
+pub const AVB_DESCRIPTOR_TAG_CHAIN_PARTITION: u64 = 4;
+
+/// Footer stored in the last 64 bytes of a partition signed by avbtool,
+/// pointing to the vbmeta struct appended after the image.
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbFooter {
+    pub magic: [u8; 4],
+    pub version_major: u32,
+    pub version_minor: u32,
+    pub original_image_size: u64,
+    pub vbmeta_offset: u64,
+    pub vbmeta_size: u64,
+    pub reserved: [u8; 28],
+}
+
+/// Serializes a big-endian structure.
+fn encode<T>(value: &T) -> io::Result<Vec<u8>>
+where
+    for<'a> T: BinWrite<Args<'a> = ()> + binrw::meta::WriteEndian,
+{
+    let mut cursor = Cursor::new(Vec::new());
+    value.write(&mut cursor).map_err(io::Error::other)?;
+    Ok(cursor.into_inner())
+}
+
+/// Copies `text` into a NUL padded field of `N` bytes.
+fn fixed_field<const N: usize>(text: &str) -> [u8; N] {
+    let mut field = [0u8; N];
+    let len = text.len().min(N);
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+    field
+}
+
+impl AvbFooter {
+    pub fn new(original_image_size: u64, vbmeta_offset: u64, vbmeta_size: u64) -> Self {
+        AvbFooter {
+            magic: *AVB_FOOTER_MAGIC,
+            version_major: 1,
+            version_minor: 0,
+            original_image_size,
+            vbmeta_offset,
+            vbmeta_size,
+            reserved: [0; 28],
+        }
+    }
+
+    /// Parses the footer at the end of `data`, or `None` if there is none.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let start = data.len().checked_sub(AVB_FOOTER_SIZE as usize)?;
+        let footer = AvbFooter::read(&mut Cursor::new(&data[start..])).ok()?;
+        (&footer.magic == AVB_FOOTER_MAGIC).then_some(footer)
+    }
+
+    /// Removes the footer, the vbmeta struct and the partition padding from
+    /// `data`, returning whether there was a footer.
+    pub fn erase(data: &mut Vec<u8>) -> bool {
+        match Self::parse(data) {
+            Some(footer) if footer.original_image_size <= data.len() as u64 => {
+                data.truncate(footer.original_image_size as usize);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Appends a hash footer to `data` like `avbtool add_hash_footer`,
+    /// replacing any footer it already has.
+    ///
+    /// The image is padded to a block, followed by the vbmeta struct holding a
+    /// hash descriptor of the image, padding up to the partition size and the
+    /// footer in the last 64 bytes.
+    pub fn add_hash_footer(data: &mut Vec<u8>, options: &AvbHashFooterOptions) -> io::Result<()> {
+        Self::erase(data);
+        let original_image_size = data.len() as u64;
+
+        let descriptor = AvbDescriptor::Hash(AvbHashDescriptor::compute(
+            &options.partition_name,
+            data,
+            options.salt.clone(),
+        ));
+        let vbmeta = VbmetaImage::build(
+            &[descriptor],
+            options.algorithm,
+            options.key.as_ref(),
+            options.rollback_index,
+            0,
+            0,
+        )?;
+
+        data.resize(align(original_image_size, AVB_BLOCK_SIZE) as usize, 0);
+        let vbmeta_offset = data.len() as u64;
+        data.extend_from_slice(&vbmeta);
+        data.resize(align(data.len() as u64, AVB_BLOCK_SIZE) as usize, 0);
+
+        // The footer takes a whole block at the end of the partition
+        if let Some(partition_size) = options.partition_size {
+            if partition_size % AVB_BLOCK_SIZE as u64 != 0 {
+                return Err(io::Error::other(format!(
+                    "Partition size {} is not a multiple of {}",
+                    partition_size, AVB_BLOCK_SIZE
+                )));
+            }
+            let footer_start = partition_size.saturating_sub(AVB_BLOCK_SIZE as u64);
+            if (data.len() as u64) > footer_start {
+                return Err(io::Error::other(format!(
+                    "Image of {} bytes with AVB metadata does not fit in a {} byte partition",
+                    data.len() as u64 + AVB_BLOCK_SIZE as u64,
+                    partition_size
+                )));
+            }
+            data.resize(footer_start as usize, 0);
+        }
+        data.resize(
+            data.len() + (AVB_BLOCK_SIZE as u64 - AVB_FOOTER_SIZE) as usize,
+            0,
+        );
+
+        let footer = AvbFooter::new(original_image_size, vbmeta_offset, vbmeta.len() as u64);
+        data.extend_from_slice(&encode(&footer)?);
+        Ok(())
+    }
+
+    /// Reads the footer at the end of `file`, or `None` if there is none.
+    pub fn read_from(file: &mut File) -> io::Result<Option<Self>> {
+        if file.metadata()?.len() < AVB_FOOTER_SIZE {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::End(-(AVB_FOOTER_SIZE as i64)))?;
+        let footer = AvbFooter::read(file).map_err(io::Error::other)?;
+        if &footer.magic != AVB_FOOTER_MAGIC {
+            return Ok(None);
+        }
+        Ok(Some(footer))
+    }
+
+    /// End of the vbmeta struct the footer points to, if it fits in an image
+    /// of `image_size` bytes.
+    fn vbmeta_end(&self, image_size: u64) -> io::Result<u64> {
+        self.vbmeta_offset
+            .checked_add(self.vbmeta_size)
+            .filter(|&end| end <= image_size)
+            .ok_or_else(|| io::Error::other("vbmeta is outside of the image"))
+    }
+
+    /// The vbmeta struct the footer points to in `data`, the whole image.
+    pub fn vbmeta_data<'a>(&self, data: &'a [u8]) -> io::Result<&'a [u8]> {
+        let end = self.vbmeta_end(data.len() as u64)?;
+        Ok(&data[self.vbmeta_offset as usize..end as usize])
+    }
+
+    /// Reads the vbmeta struct the footer points to.
+    pub fn read_vbmeta(&self, file: &mut File) -> io::Result<VbmetaImage> {
+        self.vbmeta_end(file.metadata()?.len())?;
+        let mut data = vec![0; self.vbmeta_size as usize];
+        file.seek(SeekFrom::Start(self.vbmeta_offset))?;
+        file.read_exact(&mut data)?;
+        VbmetaImage::parse(&data)
+    }
+}
+
+/// Fixed-size header of a vbmeta struct. The authentication block (hash and
+/// signature) and auxiliary block (descriptors and public key) follow it.
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct VbmetaHeader {
+    pub magic: [u8; 4],
+    pub required_libavb_version_major: u32,
+    pub required_libavb_version_minor: u32,
+    pub authentication_data_block_size: u64,
+    pub auxiliary_data_block_size: u64,
+    pub algorithm_type: u32,
+    pub hash_offset: u64,
+    pub hash_size: u64,
+    pub signature_offset: u64,
+    pub signature_size: u64,
+    pub public_key_offset: u64,
+    pub public_key_size: u64,
+    pub public_key_metadata_offset: u64,
+    pub public_key_metadata_size: u64,
+    pub descriptors_offset: u64,
+    pub descriptors_size: u64,
+    pub rollback_index: u64,
+    pub flags: u32,
+    pub rollback_index_location: u32,
+    #[br(count = 48)]
+    pub release_string: Vec<u8>,
+    #[br(count = 80)]
+    pub reserved: Vec<u8>,
+}
+
+impl VbmetaHeader {
+    pub fn algorithm(&self) -> AvbAlgorithm {
+        AvbAlgorithm::from(self.algorithm_type)
+    }
+
+    pub fn release_string(&self) -> String {
+        String::from_utf8_lossy(&self.release_string)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+/// Signing algorithms of a vbmeta struct.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AvbAlgorithm {
+    #[default]
+    None,
+    Sha256Rsa2048,
+    Sha256Rsa4096,
+    Sha256Rsa8192,
+    Sha512Rsa2048,
+    Sha512Rsa4096,
+    Sha512Rsa8192,
+    Unknown(u32),
+}
+
+impl AvbAlgorithm {
+    /// Size of the vbmeta hash, 0 when unsigned.
+    pub fn hash_size(&self) -> usize {
+        match self {
+            AvbAlgorithm::Sha256Rsa2048
+            | AvbAlgorithm::Sha256Rsa4096
+            | AvbAlgorithm::Sha256Rsa8192 => 32,
+            AvbAlgorithm::Sha512Rsa2048
+            | AvbAlgorithm::Sha512Rsa4096
+            | AvbAlgorithm::Sha512Rsa8192 => 64,
+            AvbAlgorithm::None | AvbAlgorithm::Unknown(_) => 0,
+        }
+    }
+
+    /// Size of the RSA key in bits, 0 when unsigned.
+    pub fn key_bits(&self) -> usize {
+        match self {
+            AvbAlgorithm::Sha256Rsa2048 | AvbAlgorithm::Sha512Rsa2048 => 2048,
+            AvbAlgorithm::Sha256Rsa4096 | AvbAlgorithm::Sha512Rsa4096 => 4096,
+            AvbAlgorithm::Sha256Rsa8192 | AvbAlgorithm::Sha512Rsa8192 => 8192,
+            AvbAlgorithm::None | AvbAlgorithm::Unknown(_) => 0,
+        }
+    }
+
+    pub fn signature_size(&self) -> usize {
+        self.key_bits() / 8
+    }
+
+    /// Hashes `data` with the digest of this algorithm.
+    pub fn digest(&self, data: &[&[u8]]) -> Vec<u8> {
+        match self.hash_size() {
+            32 => data
+                .iter()
+                .fold(Sha256::new(), |hasher, part| hasher.chain_update(part))
+                .finalize()
+                .to_vec(),
+            64 => data
+                .iter()
+                .fold(Sha512::new(), |hasher, part| hasher.chain_update(part))
+                .finalize()
+                .to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn padding_scheme(&self) -> Pkcs1v15Sign {
+        match self.hash_size() {
+            64 => Pkcs1v15Sign::new::<Sha512>(),
+            _ => Pkcs1v15Sign::new::<Sha256>(),
+        }
+    }
+}
+
+impl From<u32> for AvbAlgorithm {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => AvbAlgorithm::None,
+            1 => AvbAlgorithm::Sha256Rsa2048,
+            2 => AvbAlgorithm::Sha256Rsa4096,
+            3 => AvbAlgorithm::Sha256Rsa8192,
+            4 => AvbAlgorithm::Sha512Rsa2048,
+            5 => AvbAlgorithm::Sha512Rsa4096,
+            6 => AvbAlgorithm::Sha512Rsa8192,
+            other => AvbAlgorithm::Unknown(other),
+        }
+    }
+}
+
+impl From<AvbAlgorithm> for u32 {
+    fn from(value: AvbAlgorithm) -> Self {
+        match value {
+            AvbAlgorithm::None => 0,
+            AvbAlgorithm::Sha256Rsa2048 => 1,
+            AvbAlgorithm::Sha256Rsa4096 => 2,
+            AvbAlgorithm::Sha256Rsa8192 => 3,
+            AvbAlgorithm::Sha512Rsa2048 => 4,
+            AvbAlgorithm::Sha512Rsa4096 => 5,
+            AvbAlgorithm::Sha512Rsa8192 => 6,
+            AvbAlgorithm::Unknown(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for AvbAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AvbAlgorithm::None => write!(f, "NONE"),
+            AvbAlgorithm::Sha256Rsa2048 => write!(f, "SHA256_RSA2048"),
+            AvbAlgorithm::Sha256Rsa4096 => write!(f, "SHA256_RSA4096"),
+            AvbAlgorithm::Sha256Rsa8192 => write!(f, "SHA256_RSA8192"),
+            AvbAlgorithm::Sha512Rsa2048 => write!(f, "SHA512_RSA2048"),
+            AvbAlgorithm::Sha512Rsa4096 => write!(f, "SHA512_RSA4096"),
+            AvbAlgorithm::Sha512Rsa8192 => write!(f, "SHA512_RSA8192"),
+            AvbAlgorithm::Unknown(other) => write!(f, "Unknown ({})", other),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbPropertyDescriptor {
+    pub key_num_bytes: u64,
+    pub value_num_bytes: u64,
+    #[br(count = key_num_bytes)]
+    pub key: Vec<u8>,
+    #[brw(magic = 0u8)]
+    #[br(count = value_num_bytes)]
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbHashtreeDescriptor {
+    pub dm_verity_version: u32,
+    pub image_size: u64,
+    pub tree_offset: u64,
+    pub tree_size: u64,
+    pub data_block_size: u32,
+    pub hash_block_size: u32,
+    pub fec_num_roots: u32,
+    pub fec_offset: u64,
+    pub fec_size: u64,
+    pub hash_algorithm: [u8; 32],
+    pub partition_name_len: u32,
+    pub salt_len: u32,
+    pub root_digest_len: u32,
+    pub flags: u32,
+    #[br(count = 60)]
+    pub reserved: Vec<u8>,
+    #[br(count = partition_name_len)]
+    pub partition_name: Vec<u8>,
+    #[br(count = salt_len)]
+    pub salt: Vec<u8>,
+    #[br(count = root_digest_len)]
+    pub root_digest: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbHashDescriptor {
+    pub image_size: u64,
+    pub hash_algorithm: [u8; 32],
+    pub partition_name_len: u32,
+    pub salt_len: u32,
+    pub digest_len: u32,
+    pub flags: u32,
+    #[br(count = 60)]
+    pub reserved: Vec<u8>,
+    #[br(count = partition_name_len)]
+    pub partition_name: Vec<u8>,
+    #[br(count = salt_len)]
+    pub salt: Vec<u8>,
+    #[br(count = digest_len)]
+    pub digest: Vec<u8>,
+}
+
+impl AvbHashDescriptor {
+    pub fn new(
+        image_size: u64,
+        hash_algorithm: &str,
+        partition_name: &str,
+        salt: Vec<u8>,
+        digest: Vec<u8>,
+    ) -> Self {
+        AvbHashDescriptor {
+            image_size,
+            hash_algorithm: fixed_field(hash_algorithm),
+            partition_name_len: partition_name.len() as u32,
+            salt_len: salt.len() as u32,
+            digest_len: digest.len() as u32,
+            flags: 0,
+            reserved: vec![0; 60],
+            partition_name: partition_name.as_bytes().to_vec(),
+            salt,
+            digest,
+        }
+    }
+
+    /// Builds a sha256 descriptor of the whole of `image`, with a random
+    /// 32-byte salt unless one is given.
+    pub fn compute(partition_name: &str, image: &[u8], salt: Option<Vec<u8>>) -> Self {
+        let salt = salt.unwrap_or_else(|| {
+            let mut salt = vec![0u8; 32];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        });
+        let digest = Sha256::new()
+            .chain_update(&salt)
+            .chain_update(image)
+            .finalize()
+            .to_vec();
+        Self::new(image.len() as u64, "sha256", partition_name, salt, digest)
+    }
+}
+
+impl AvbHashDescriptor {
+    /// Checks the digest against the first `image_size` bytes of `image`.
+    pub fn verify(&self, image: &[u8]) -> bool {
+        let Some(data) = image.get(..self.image_size as usize) else {
+            return false;
+        };
+        let algorithm = String::from_utf8_lossy(&self.hash_algorithm);
+        let digest = match algorithm.trim_end_matches('\0') {
+            "sha256" => Sha256::new()
+                .chain_update(&self.salt)
+                .chain_update(data)
+                .finalize()
+                .to_vec(),
+            "sha512" => Sha512::new()
+                .chain_update(&self.salt)
+                .chain_update(data)
+                .finalize()
+                .to_vec(),
+            _ => return false,
+        };
+        digest == self.digest
+    }
+}
+
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbKernelCmdlineDescriptor {
+    pub flags: u32,
+    pub kernel_cmdline_length: u32,
+    #[br(count = kernel_cmdline_length)]
+    pub kernel_cmdline: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct AvbChainPartitionDescriptor {
+    pub rollback_index_location: u32,
+    pub partition_name_len: u32,
+    pub public_key_len: u32,
+    pub flags: u32,
+    #[br(count = 60)]
+    pub reserved: Vec<u8>,
+    #[br(count = partition_name_len)]
+    pub partition_name: Vec<u8>,
+    #[br(count = public_key_len)]
+    pub public_key: Vec<u8>,
+}
+
+/// A descriptor from the auxiliary block of a vbmeta struct.
+#[derive(Debug, Clone)]
+pub enum AvbDescriptor {
+    Property(AvbPropertyDescriptor),
+    Hashtree(AvbHashtreeDescriptor),
+    Hash(AvbHashDescriptor),
+    KernelCmdline(AvbKernelCmdlineDescriptor),
+    ChainPartition(AvbChainPartitionDescriptor),
+    Unknown { tag: u64, data: Vec<u8> },
+}
+
+impl AvbDescriptor {
+    /// Parses every descriptor in `data`. Each one starts with its tag and
+    /// the number of bytes following, padded to 8 bytes.
+    pub fn parse_all(data: &[u8]) -> io::Result<Vec<Self>> {
+        let mut descriptors = Vec::new();
+        let mut offset = 0usize;
+
+        while offset + 16 <= data.len() {
+            let tag = u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap());
+            let num_bytes_following =
+                u64::from_be_bytes(data[offset + 8..offset + 16].try_into().unwrap()) as usize;
+            let end = (offset + 16)
+                .checked_add(num_bytes_following)
+                .ok_or_else(|| io::Error::other("AVB descriptor is truncated"))?;
+            let body = data
+                .get(offset + 16..end)
+                .ok_or_else(|| io::Error::other("AVB descriptor is truncated"))?;
+
+            let mut cursor = Cursor::new(body);
+            let descriptor = match tag {
+                AVB_DESCRIPTOR_TAG_PROPERTY => {
+                    AvbPropertyDescriptor::read(&mut cursor).map(AvbDescriptor::Property)
+                }
+                AVB_DESCRIPTOR_TAG_HASHTREE => {
+                    AvbHashtreeDescriptor::read(&mut cursor).map(AvbDescriptor::Hashtree)
+                }
+                AVB_DESCRIPTOR_TAG_HASH => {
+                    AvbHashDescriptor::read(&mut cursor).map(AvbDescriptor::Hash)
+                }
+                AVB_DESCRIPTOR_TAG_KERNEL_CMDLINE => {
+                    AvbKernelCmdlineDescriptor::read(&mut cursor).map(AvbDescriptor::KernelCmdline)
+                }
+                AVB_DESCRIPTOR_TAG_CHAIN_PARTITION => {
+                    AvbChainPartitionDescriptor::read(&mut cursor)
+                        .map(AvbDescriptor::ChainPartition)
+                }
+                _ => Ok(AvbDescriptor::Unknown {
+                    tag,
+                    data: body.to_vec(),
+                }),
+            };
+            descriptors.push(descriptor.map_err(io::Error::other)?);
+            offset = end;
+        }
+
+        Ok(descriptors)
+    }
+
+    pub fn tag(&self) -> u64 {
+        match self {
+            AvbDescriptor::Property(_) => AVB_DESCRIPTOR_TAG_PROPERTY,
+            AvbDescriptor::Hashtree(_) => AVB_DESCRIPTOR_TAG_HASHTREE,
+            AvbDescriptor::Hash(_) => AVB_DESCRIPTOR_TAG_HASH,
+            AvbDescriptor::KernelCmdline(_) => AVB_DESCRIPTOR_TAG_KERNEL_CMDLINE,
+            AvbDescriptor::ChainPartition(_) => AVB_DESCRIPTOR_TAG_CHAIN_PARTITION,
+            AvbDescriptor::Unknown { tag, .. } => *tag,
+        }
+    }
+
+    /// Serializes the descriptor with its tag, size and padding.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut body = match self {
+            AvbDescriptor::Property(property) => {
+                let mut body = encode(property)?;
+                // The value is NUL terminated too
+                body.push(0);
+                body
+            }
+            AvbDescriptor::Hashtree(hashtree) => encode(hashtree)?,
+            AvbDescriptor::Hash(hash) => encode(hash)?,
+            AvbDescriptor::KernelCmdline(cmdline) => encode(cmdline)?,
+            AvbDescriptor::ChainPartition(chain) => encode(chain)?,
+            AvbDescriptor::Unknown { data, .. } => data.clone(),
+        };
+        body.resize(align(body.len() as u64, 8) as usize, 0);
+
+        let mut data = Vec::with_capacity(16 + body.len());
+        data.extend_from_slice(&self.tag().to_be_bytes());
+        data.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        data.extend_from_slice(&body);
+        Ok(data)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AvbDescriptor::Property(_) => "Property",
+            AvbDescriptor::Hashtree(_) => "Hashtree",
+            AvbDescriptor::Hash(_) => "Hash",
+            AvbDescriptor::KernelCmdline(_) => "Kernel Cmdline",
+            AvbDescriptor::ChainPartition(_) => "Chain Partition",
+            AvbDescriptor::Unknown { .. } => "Unknown",
+        }
+    }
+}
+
+/// A vbmeta struct: header, authentication data block and auxiliary data block.
+#[derive(Debug, Default, Clone)]
+pub struct VbmetaImage {
+    pub header: VbmetaHeader,
+    pub authentication: Vec<u8>,
+    pub auxiliary: Vec<u8>,
+}
+
+impl VbmetaImage {
+    /// Builds and signs a vbmeta struct holding `descriptors`, laid out like
+    /// avbtool does. `key` is required unless `algorithm` is NONE.
+    pub fn build(
+        descriptors: &[AvbDescriptor],
+        algorithm: AvbAlgorithm,
+        key: Option<&RsaPrivateKey>,
+        rollback_index: u64,
+        flags: u32,
+        rollback_index_location: u32,
+    ) -> io::Result<Vec<u8>> {
+        let public_key = match (algorithm, key) {
+            (AvbAlgorithm::None, _) => Vec::new(),
+            (AvbAlgorithm::Unknown(value), _) => {
+                return Err(io::Error::other(format!(
+                    "Unknown AVB algorithm: {}",
+                    value
+                )))
+            }
+            (_, None) => return Err(io::Error::other(format!("{} requires a key", algorithm))),
+            (_, Some(key)) if key.size() * 8 != algorithm.key_bits() => {
+                return Err(io::Error::other(format!(
+                    "{} requires a {} bit key, got {} bits",
+                    algorithm,
+                    algorithm.key_bits(),
+                    key.size() * 8
+                )))
+            }
+            (_, Some(key)) => encode_public_key(&key.to_public_key()),
+        };
+
+        let mut encoded_descriptors = Vec::new();
+        for descriptor in descriptors {
+            encoded_descriptors.extend(descriptor.to_bytes()?);
+        }
+
+        let mut auxiliary = encoded_descriptors.clone();
+        auxiliary.extend_from_slice(&public_key);
+        auxiliary.resize(align(auxiliary.len() as u64, 64) as usize, 0);
+
+        let hash_size = algorithm.hash_size() as u64;
+        let signature_size = algorithm.signature_size() as u64;
+        let mut release_string = format!("abootcrafter {}", env!("CARGO_PKG_VERSION")).into_bytes();
+        release_string.resize(48, 0);
+
+        let header = VbmetaHeader {
+            magic: *AVB_VBMETA_MAGIC,
+            required_libavb_version_major: 1,
+            // Rollback index locations other than 0 need libavb 1.2
+            required_libavb_version_minor: if rollback_index_location > 0 { 2 } else { 0 },
+            authentication_data_block_size: align(hash_size + signature_size, 64),
+            auxiliary_data_block_size: auxiliary.len() as u64,
+            algorithm_type: algorithm.into(),
+            hash_offset: 0,
+            hash_size,
+            signature_offset: hash_size,
+            signature_size,
+            public_key_offset: encoded_descriptors.len() as u64,
+            public_key_size: public_key.len() as u64,
+            public_key_metadata_offset: (encoded_descriptors.len() + public_key.len()) as u64,
+            public_key_metadata_size: 0,
+            descriptors_offset: 0,
+            descriptors_size: encoded_descriptors.len() as u64,
+            rollback_index,
+            flags,
+            rollback_index_location,
+            release_string,
+            reserved: vec![0; 80],
+        };
+        let header_data = encode(&header)?;
+
+        // The hash and signature cover the header and the auxiliary block
+        let mut authentication = Vec::new();
+        if let Some(key) = key.filter(|_| algorithm != AvbAlgorithm::None) {
+            let hash = algorithm.digest(&[&header_data, &auxiliary]);
+            let signature = key
+                .sign(algorithm.padding_scheme(), &hash)
+                .map_err(io::Error::other)?;
+            authentication.extend_from_slice(&hash);
+            authentication.extend_from_slice(&signature);
+        }
+        authentication.resize(header.authentication_data_block_size as usize, 0);
+
+        let mut data = header_data;
+        data.extend_from_slice(&authentication);
+        data.extend_from_slice(&auxiliary);
+        Ok(data)
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let header = VbmetaHeader::read(&mut Cursor::new(data)).map_err(io::Error::other)?;
+        if &header.magic != AVB_VBMETA_MAGIC {
+            return Err(io::Error::other("Invalid vbmeta magic"));
+        }
+
+        // The block sizes come from the image, so they may not add up
+        let authentication_start = AVB_VBMETA_HEADER_SIZE;
+        let auxiliary_start = authentication_start
+            .checked_add(header.authentication_data_block_size)
+            .ok_or_else(|| io::Error::other("vbmeta is truncated"))?;
+        let auxiliary_end = auxiliary_start
+            .checked_add(header.auxiliary_data_block_size)
+            .filter(|&end| end <= data.len() as u64)
+            .ok_or_else(|| io::Error::other("vbmeta is truncated"))?;
+        let (authentication_start, auxiliary_start, auxiliary_end) = (
+            authentication_start as usize,
+            auxiliary_start as usize,
+            auxiliary_end as usize,
+        );
+
+        Ok(VbmetaImage {
+            authentication: data[authentication_start..auxiliary_start].to_vec(),
+            auxiliary: data[auxiliary_start..auxiliary_end].to_vec(),
+            header,
+        })
+    }
+
+    /// Parses vbmeta structs stored back to back, like in the boot signature
+    /// section of v4 boot images, stopping at the first block without the
+    /// vbmeta magic.
+    pub fn parse_all(data: &[u8]) -> io::Result<Vec<Self>> {
+        let mut images = Vec::new();
+        let mut offset = 0;
+        while data[offset..].starts_with(AVB_VBMETA_MAGIC) {
+            let image = Self::parse(&data[offset..])?;
+            offset += image.size();
+            images.push(image);
+        }
+        Ok(images)
+    }
+
+    /// Size of the serialized struct: header, authentication and auxiliary block.
+    pub fn size(&self) -> usize {
+        AVB_VBMETA_HEADER_SIZE as usize + self.authentication.len() + self.auxiliary.len()
+    }
+
+    /// Recomputes the hash over the header and auxiliary block and compares
+    /// it with the stored one.
+    pub fn verify_hash(&self) -> io::Result<bool> {
+        let header_data = encode(&self.header)?;
+        let hash = self
+            .header
+            .algorithm()
+            .digest(&[&header_data, &self.auxiliary]);
+        Ok(hash == self.hash())
+    }
+
+    /// Checks the signature against the embedded public key.
+    pub fn verify_signature(&self) -> io::Result<bool> {
+        let algorithm = self.header.algorithm();
+        let public_key = decode_public_key(self.public_key())?;
+        if public_key.size() * 8 != algorithm.key_bits() {
+            return Ok(false);
+        }
+        Ok(public_key
+            .verify(algorithm.padding_scheme(), self.hash(), self.signature())
+            .is_ok())
+    }
+
+    fn slice(block: &[u8], offset: u64, size: u64) -> &[u8] {
+        offset
+            .checked_add(size)
+            .and_then(|end| block.get(offset as usize..end as usize))
+            .unwrap_or_default()
+    }
+
+    pub fn hash(&self) -> &[u8] {
+        Self::slice(
+            &self.authentication,
+            self.header.hash_offset,
+            self.header.hash_size,
+        )
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        Self::slice(
+            &self.authentication,
+            self.header.signature_offset,
+            self.header.signature_size,
+        )
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        Self::slice(
+            &self.auxiliary,
+            self.header.public_key_offset,
+            self.header.public_key_size,
+        )
+    }
+
+    pub fn descriptors(&self) -> io::Result<Vec<AvbDescriptor>> {
+        AvbDescriptor::parse_all(Self::slice(
+            &self.auxiliary,
+            self.header.descriptors_offset,
+            self.header.descriptors_size,
+        ))
+    }
+}
+
+/// Options for [`AvbFooter::add_hash_footer`].
+#[derive(Debug, Default, Clone)]
+pub struct AvbHashFooterOptions {
+    pub partition_name: String,
+    /// Size of the partition, or `None` to make it as small as possible
+    pub partition_size: Option<u64>,
+    /// Salt of the hash descriptor, or `None` for 32 random bytes
+    pub salt: Option<Vec<u8>>,
+    pub rollback_index: u64,
+    pub algorithm: AvbAlgorithm,
+    pub key: Option<RsaPrivateKey>,
+}
+
+/// Encodes a public key the way libavb expects it: key size, the negated
+/// inverse of the modulus mod 2^32, the modulus and R^2 mod n, all big-endian.
+pub fn encode_public_key(key: &RsaPublicKey) -> Vec<u8> {
+    let bits = key.size() * 8;
+    let modulus = key.n();
+
+    let modulus_bytes = modulus.to_bytes_be();
+    let n0 = u32::from_be_bytes(modulus_bytes[modulus_bytes.len() - 4..].try_into().unwrap());
+    // Newton iteration for the inverse of an odd number mod 2^32
+    let mut n0inv = n0;
+    for _ in 0..5 {
+        n0inv = n0inv.wrapping_mul(2u32.wrapping_sub(n0.wrapping_mul(n0inv)));
+    }
+    let rr = (BigUint::from(1u32) << (2 * bits)) % modulus;
+
+    let mut data = Vec::with_capacity(8 + bits / 4);
+    data.extend_from_slice(&(bits as u32).to_be_bytes());
+    data.extend_from_slice(&n0inv.wrapping_neg().to_be_bytes());
+    for value in [modulus, &rr] {
+        let bytes = value.to_bytes_be();
+        data.resize(data.len() + bits / 8 - bytes.len(), 0);
+        data.extend_from_slice(&bytes);
+    }
+    data
+}
+
+/// Reads an RSA private key in PKCS#1 or PKCS#8 PEM format.
+pub fn read_private_key_pem(pem: &str) -> io::Result<RsaPrivateKey> {
+    RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(io::Error::other)
+}
+
+/// Reads an RSA private key in PEM format, or in DER PKCS#8 format like the
+/// `.pk8` keys of the Android tree.
+pub fn read_private_key(data: &[u8]) -> io::Result<RsaPrivateKey> {
+    match std::str::from_utf8(data) {
+        Ok(pem) if pem.contains("-----BEGIN") => read_private_key_pem(pem),
+        _ => RsaPrivateKey::from_pkcs8_der(data).map_err(io::Error::other),
+    }
+}
+
+/// Decodes a public key in the libavb format. libavb only supports an
+/// exponent of 65537.
+pub fn decode_public_key(data: &[u8]) -> io::Result<RsaPublicKey> {
+    let bits = data
+        .get(..4)
+        .map(|bits| u32::from_be_bytes(bits.try_into().unwrap()) as usize)
+        .ok_or_else(|| io::Error::other("AVB public key is truncated"))?;
+    let modulus = data
+        .get(8..8 + bits / 8)
+        .ok_or_else(|| io::Error::other("AVB public key is truncated"))?;
+    RsaPublicKey::new(BigUint::from_bytes_be(modulus), BigUint::from(65537u32))
+        .map_err(io::Error::other)
+}
+
+/// Reads a public key as a PEM public key (SubjectPublicKeyInfo or PKCS#1),
+/// a PEM private key, or an already encoded libavb key (`.avbpubkey`), and
+/// returns it in the libavb format.
+pub fn read_public_key(data: &[u8]) -> io::Result<Vec<u8>> {
+    let Ok(pem) = std::str::from_utf8(data) else {
+        return Ok(data.to_vec());
+    };
+    if !pem.contains("-----BEGIN") {
+        return Ok(data.to_vec());
+    }
+
+    let public_key = RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .or_else(|_| read_private_key_pem(pem).map(|key| key.to_public_key()))
+        .map_err(io::Error::other)?;
+    Ok(encode_public_key(&public_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Throwaway key, only used to sign test images
+    const TEST_KEY: &str = include_str!("testdata/rsa2048.pem");
+
+    fn test_key() -> RsaPrivateKey {
+        read_private_key_pem(TEST_KEY).unwrap()
+    }
+
+    fn signed_vbmeta(image: &[u8]) -> Vec<u8> {
+        let descriptor =
+            AvbDescriptor::Hash(AvbHashDescriptor::compute("boot", image, Some(vec![7; 32])));
+        VbmetaImage::build(
+            &[descriptor],
+            AvbAlgorithm::Sha256Rsa2048,
+            Some(&test_key()),
+            3,
+            0,
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn build_parse_and_verify() {
+        let image = vec![0x42; 10000];
+        let data = signed_vbmeta(&image);
+        let vbmeta = VbmetaImage::parse(&data).unwrap();
+        assert_eq!(vbmeta.size(), data.len());
+        assert_eq!(vbmeta.header.algorithm(), AvbAlgorithm::Sha256Rsa2048);
+        assert_eq!(vbmeta.header.rollback_index, 3);
+        assert_eq!(
+            vbmeta.public_key(),
+            encode_public_key(&test_key().to_public_key())
+        );
+        assert!(vbmeta.verify_hash().unwrap());
+        assert!(vbmeta.verify_signature().unwrap());
+
+        let descriptors = vbmeta.descriptors().unwrap();
+        let [AvbDescriptor::Hash(hash)] = descriptors.as_slice() else {
+            panic!("expected a single hash descriptor");
+        };
+        assert!(hash.verify(&image));
+        let mut modified = image.clone();
+        modified[5000] ^= 1;
+        assert!(!hash.verify(&modified));
+    }
+
+    #[test]
+    fn tampered_vbmeta_fails() {
+        let data = signed_vbmeta(&[0x42; 100]);
+        let vbmeta = VbmetaImage::parse(&data).unwrap();
+
+        // A changed descriptor no longer matches the stored hash
+        let mut tampered = vbmeta.clone();
+        let offset = tampered.header.descriptors_offset as usize + 100;
+        tampered.auxiliary[offset] ^= 1;
+        assert!(!tampered.verify_hash().unwrap());
+
+        // A hash recomputed without the key no longer matches the signature
+        let mut tampered = vbmeta.clone();
+        let offset = tampered.header.hash_offset as usize;
+        tampered.authentication[offset] ^= 1;
+        assert!(!tampered.verify_signature().unwrap());
+    }
+
+    #[test]
+    fn public_key_encoding_matches_libavb() {
+        let key = test_key().to_public_key();
+        let encoded = encode_public_key(&key);
+        assert_eq!(encoded.len(), 8 + 2 * 256);
+        assert_eq!(encoded[..4], 2048u32.to_be_bytes());
+
+        // n0inv is -1/n[0] mod 2^32
+        let n0inv = u32::from_be_bytes(encoded[4..8].try_into().unwrap());
+        let n0 = u32::from_be_bytes(encoded[260..264].try_into().unwrap());
+        assert_eq!(n0.wrapping_mul(n0inv), u32::MAX);
+
+        let modulus = BigUint::from_bytes_be(&encoded[8..264]);
+        assert_eq!(&modulus, key.n());
+        let rr = BigUint::from_bytes_be(&encoded[264..]);
+        assert_eq!(rr, (BigUint::from(1u32) << 4096) % &modulus);
+
+        assert_eq!(decode_public_key(&encoded).unwrap(), key);
+        assert_eq!(read_public_key(TEST_KEY.as_bytes()).unwrap(), encoded);
+    }
+
+    fn footer_options(partition_size: Option<u64>) -> AvbHashFooterOptions {
+        AvbHashFooterOptions {
+            partition_name: "boot".to_string(),
+            partition_size,
+            salt: Some(vec![7; 32]),
+            rollback_index: 0,
+            algorithm: AvbAlgorithm::Sha256Rsa2048,
+            key: Some(test_key()),
+        }
+    }
+
+    #[test]
+    fn add_hash_footer_layout() {
+        let image = vec![0x42; 10000];
+        let mut data = image.clone();
+        AvbFooter::add_hash_footer(&mut data, &footer_options(Some(64 * 1024))).unwrap();
+        assert_eq!(data.len(), 64 * 1024);
+        assert_eq!(data[..image.len()], image);
+
+        let footer = AvbFooter::parse(&data).unwrap();
+        assert_eq!(footer.original_image_size, 10000);
+        assert_eq!(footer.vbmeta_offset, 12288);
+        let vbmeta = VbmetaImage::parse(footer.vbmeta_data(&data).unwrap()).unwrap();
+        assert_eq!(vbmeta.size() as u64, footer.vbmeta_size);
+        assert!(vbmeta.verify_signature().unwrap());
+        let descriptors = vbmeta.descriptors().unwrap();
+        let [AvbDescriptor::Hash(hash)] = descriptors.as_slice() else {
+            panic!("expected a single hash descriptor");
+        };
+        assert_eq!(hash.image_size, 10000);
+        assert!(hash.verify(&data));
+    }
+
+    #[test]
+    fn add_hash_footer_replaces_and_erases() {
+        let image = vec![0x42; 10000];
+        let mut data = image.clone();
+        AvbFooter::add_hash_footer(&mut data, &footer_options(Some(64 * 1024))).unwrap();
+        // Without a partition size, the image is as small as possible
+        AvbFooter::add_hash_footer(&mut data, &footer_options(None)).unwrap();
+        assert_eq!(data.len(), 12288 + 4096 + 4096);
+        assert_eq!(AvbFooter::parse(&data).unwrap().original_image_size, 10000);
+
+        assert!(AvbFooter::erase(&mut data));
+        assert_eq!(data, image);
+        assert!(!AvbFooter::erase(&mut data));
+    }
+
+    #[test]
+    fn add_hash_footer_checks_partition_size() {
+        let mut data = vec![0x42; 10000];
+        assert!(AvbFooter::add_hash_footer(&mut data, &footer_options(Some(16384))).is_err());
+        let mut data = vec![0x42; 10000];
+        assert!(AvbFooter::add_hash_footer(&mut data, &footer_options(Some(65000))).is_err());
+    }
+
+    fn unsigned_vbmeta() -> Vec<u8> {
+        let descriptor = AvbDescriptor::Hash(AvbHashDescriptor::compute(
+            "boot",
+            &[1; 100],
+            Some(vec![0; 32]),
+        ));
+        VbmetaImage::build(&[descriptor], AvbAlgorithm::None, None, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn footer_pointing_past_the_end_is_an_error() {
+        let mut data = vec![0; 4096];
+        let footer = AvbFooter::new(100, u64::MAX - 10, 100);
+        data.extend_from_slice(&encode(&footer).unwrap());
+        let footer = AvbFooter::parse(&data).unwrap();
+        assert!(footer.vbmeta_data(&data).is_err());
+    }
+
+    #[test]
+    fn oversized_vbmeta_blocks_are_an_error() {
+        let mut vbmeta = unsigned_vbmeta();
+        // authentication_data_block_size
+        vbmeta[12..20].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(VbmetaImage::parse(&vbmeta).is_err());
+
+        let mut vbmeta = unsigned_vbmeta();
+        // descriptors_offset, which overflows once the size is added
+        vbmeta[96..104].copy_from_slice(&u64::MAX.to_be_bytes());
+        let image = VbmetaImage::parse(&vbmeta).unwrap();
+        assert!(image.descriptors().unwrap().is_empty());
+    }
+
+    #[test]
+    fn oversized_descriptor_is_an_error() {
+        let mut data = AVB_DESCRIPTOR_TAG_HASH.to_be_bytes().to_vec();
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert!(AvbDescriptor::parse_all(&data).is_err());
+    }
+}
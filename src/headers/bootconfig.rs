@@ -0,0 +1,222 @@
+use std::fmt;
+
+pub const BOOTCONFIG_MAGIC: &[u8] = b"#BOOTCONFIG\n";
+pub const BOOTCONFIG_ALIGN: usize = 4;
+
+/// Size of the trailer appended after the parameters: size, checksum and magic
+pub const BOOTCONFIG_TRAILER_SIZE: usize = 8 + BOOTCONFIG_MAGIC.len();
+
+/// The bootconfig section of a vendor boot v4 image, as ordered key/value pairs.
+///
+/// Values are kept as written (including quotes and array separators) so that
+/// untouched parameters are rewritten exactly as they were. A bare `key` has
+/// no value, unlike `key=` which has an empty one.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Bootconfig {
+    pub params: Vec<(String, Option<String>)>,
+}
+
+impl Bootconfig {
+    /// Parses the bootconfig section, with or without the trailer.
+    pub fn parse(data: &[u8]) -> Self {
+        let params = Self::strip_trailer(data);
+        let text = String::from_utf8_lossy(params);
+
+        let params = Self::split_statements(&text)
+            .iter()
+            .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), Some(value.trim().to_string())),
+                None => (line.to_string(), None),
+            })
+            .collect();
+
+        Bootconfig { params }
+    }
+
+    /// Splits the text on newlines and `;`, skipping `#` comments, while
+    /// leaving quoted values untouched.
+    fn split_statements(text: &str) -> Vec<String> {
+        let mut statements = Vec::new();
+        let mut current = String::new();
+        let mut in_quotes = false;
+        let mut in_comment = false;
+
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    in_comment = false;
+                    in_quotes = false;
+                    statements.push(std::mem::take(&mut current));
+                }
+                _ if in_comment => {}
+                '#' if !in_quotes => in_comment = true,
+                ';' if !in_quotes => statements.push(std::mem::take(&mut current)),
+                '"' => {
+                    in_quotes = !in_quotes;
+                    current.push(c);
+                }
+                _ => current.push(c),
+            }
+        }
+        statements.push(current);
+        statements
+    }
+
+    /// Returns the parameters without the size, checksum and magic trailer.
+    fn strip_trailer(data: &[u8]) -> &[u8] {
+        if data.len() < BOOTCONFIG_TRAILER_SIZE || !data.ends_with(BOOTCONFIG_MAGIC) {
+            return data;
+        }
+
+        let trailer_start = data.len() - BOOTCONFIG_TRAILER_SIZE;
+        let size = u32::from_le_bytes(data[trailer_start..trailer_start + 4].try_into().unwrap());
+        match trailer_start.checked_sub(size as usize) {
+            Some(params_start) => &data[params_start..trailer_start],
+            None => &data[..trailer_start],
+        }
+    }
+
+    /// Computes the checksum the bootloader expects: the sum of every byte.
+    pub fn checksum(data: &[u8]) -> u32 {
+        data.iter()
+            .fold(0u32, |sum, &byte| sum.wrapping_add(byte as u32))
+    }
+
+    /// Returns the value of a parameter, `None` inside for a bare key.
+    pub fn get(&self, key: &str) -> Option<Option<&str>> {
+        self.params
+            .iter()
+            .find(|(param_key, _)| param_key == key)
+            .map(|(_, value)| value.as_deref())
+    }
+
+    /// Adds a new parameter, failing if the key already exists.
+    pub fn add(&mut self, key: &str, value: Option<&str>) -> Result<(), String> {
+        if self.get(key).is_some() {
+            return Err(format!("Bootconfig key already exists: {}", key));
+        }
+        self.params
+            .push((key.to_string(), value.map(Self::quote_if_needed)));
+        Ok(())
+    }
+
+    /// Replaces the value of an existing parameter.
+    pub fn replace(&mut self, key: &str, value: Option<&str>) -> Result<(), String> {
+        match self
+            .params
+            .iter_mut()
+            .find(|(param_key, _)| param_key == key)
+        {
+            Some((_, param_value)) => {
+                *param_value = value.map(Self::quote_if_needed);
+                Ok(())
+            }
+            None => Err(format!("Bootconfig key does not exist: {}", key)),
+        }
+    }
+
+    /// Removes a parameter, failing if the key does not exist.
+    pub fn remove(&mut self, key: &str) -> Result<(), String> {
+        let len = self.params.len();
+        self.params.retain(|(param_key, _)| param_key != key);
+        if self.params.len() == len {
+            return Err(format!("Bootconfig key does not exist: {}", key));
+        }
+        Ok(())
+    }
+
+    fn quote_if_needed(value: &str) -> String {
+        let needs_quotes = value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, ';' | '#' | '\'' | '}' | '{'));
+        if needs_quotes && !value.starts_with('"') {
+            format!("\"{}\"", value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Serializes the parameters followed by the size, checksum and
+    /// `#BOOTCONFIG\n` trailer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.params.is_empty() {
+            return Vec::new();
+        }
+
+        let mut data = self.to_string().into_bytes();
+        data.resize(data.len().div_ceil(BOOTCONFIG_ALIGN) * BOOTCONFIG_ALIGN, 0);
+
+        let size = data.len() as u32;
+        let checksum = Self::checksum(&data);
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data.extend_from_slice(BOOTCONFIG_MAGIC);
+        data
+    }
+}
+
+impl fmt::Display for Bootconfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.params {
+            match value {
+                Some(value) => writeln!(f, "{}={}", key, value)?,
+                None => writeln!(f, "{}", key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_value_is_kept_apart_from_bare_key() {
+        let bootconfig = Bootconfig::parse(b"androidboot.empty=\nandroidboot.flag\n");
+        assert_eq!(bootconfig.get("androidboot.empty"), Some(Some("")));
+        assert_eq!(bootconfig.get("androidboot.flag"), Some(None));
+        assert_eq!(
+            bootconfig.to_string(),
+            "androidboot.empty=\nandroidboot.flag\n"
+        );
+    }
+
+    #[test]
+    fn to_bytes_writes_size_checksum_and_magic() {
+        let mut bootconfig = Bootconfig::parse(b"androidboot.hardware=qcom\nandroidboot.empty=\n");
+        bootconfig.add("androidboot.flag", None).unwrap();
+        bootconfig.add("androidboot.list", Some("a b; c")).unwrap();
+
+        let data = bootconfig.to_bytes();
+        assert!(data.ends_with(BOOTCONFIG_MAGIC));
+        let trailer_start = data.len() - BOOTCONFIG_TRAILER_SIZE;
+        assert_eq!(trailer_start % BOOTCONFIG_ALIGN, 0);
+
+        let params = &data[..trailer_start];
+        let size = u32::from_le_bytes(data[trailer_start..trailer_start + 4].try_into().unwrap());
+        let checksum = u32::from_le_bytes(
+            data[trailer_start + 4..trailer_start + 8]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(size as usize, params.len());
+        assert_eq!(
+            checksum,
+            params.iter().map(|&byte| byte as u32).sum::<u32>()
+        );
+        assert!(params.starts_with(
+            b"androidboot.hardware=qcom\nandroidboot.empty=\nandroidboot.flag\n\
+              androidboot.list=\"a b; c\"\n"
+        ));
+
+        assert_eq!(Bootconfig::parse(&data), bootconfig);
+    }
+
+    #[test]
+    fn empty_bootconfig_has_no_trailer() {
+        assert!(Bootconfig::default().to_bytes().is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+use std::io;
+
+pub const DTBO_MAGIC: u32 = 0xd7b7ab1e;
+
+/// File name of the image description written next to the extracted overlays.
+pub const DTBO_FILE_NAME: &str = "dtbo.cfg";
+
+/// Size of the header and of each entry, as written by `mkdtimg`.
+const DTBO_HEADER_SIZE: u32 = 32;
+const DTBO_ENTRY_SIZE: u32 = 32;
+
+const DEFAULT_PAGE_SIZE: u32 = 2048;
+
+/// Compression of an entry, from the low bits of its flags in version 1
+/// images. Version 0 images are never compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtboCompression {
+    None,
+    Zlib,
+    Gzip,
+    Unknown(u32),
+}
+
+impl DtboCompression {
+    pub fn from_flags(flags: u32) -> Self {
+        match flags & 0xf {
+            0 => DtboCompression::None,
+            1 => DtboCompression::Zlib,
+            2 => DtboCompression::Gzip,
+            other => DtboCompression::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for DtboCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DtboCompression::None => write!(f, "none"),
+            DtboCompression::Zlib => write!(f, "zlib"),
+            DtboCompression::Gzip => write!(f, "gzip"),
+            DtboCompression::Unknown(value) => write!(f, "unknown ({})", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtboEntry {
+    pub size: u32,
+    /// Offset of the overlay from the start of the image
+    pub offset: u32,
+    pub id: u32,
+    pub rev: u32,
+    /// Custom words, named by [`DtboImage::custom_names`]; version 1 images
+    /// store the entry flags in the first one
+    pub custom: [u32; 4],
+}
+
+/// An Android DT table image, as built by `mkdtimg` for the `dtbo` and
+/// `recovery_dtbo` partitions: a big-endian header and entries, followed by
+/// the device tree overlays. Each entry carries an id and revision the
+/// bootloader matches against the board, and four words it is free to use.
+/// Entries with identical overlays share them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtboImage {
+    pub version: u32,
+    /// Page size of the flash the image is written to, only stored
+    pub page_size: u32,
+    pub entries: Vec<DtboEntry>,
+}
+
+impl DtboImage {
+    /// Whether `data` starts with a DT table header.
+    pub fn is_dtbo(data: &[u8]) -> bool {
+        data.starts_with(&DTBO_MAGIC.to_be_bytes())
+    }
+
+    /// Names of the custom words of an entry, in on-disk order.
+    pub fn custom_names(version: u32) -> [&'static str; 4] {
+        match version {
+            0 => ["custom0", "custom1", "custom2", "custom3"],
+            _ => ["flags", "custom0", "custom1", "custom2"],
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if !DtboImage::is_dtbo(data) {
+            return Err(io::Error::other("Not a DTBO image"));
+        }
+        let header_size = read_u32(data, 8)?;
+        let entry_size = read_u32(data, 12)?;
+        if header_size < DTBO_HEADER_SIZE || entry_size < DTBO_ENTRY_SIZE {
+            return Err(io::Error::other(format!(
+                "Invalid DTBO header size {} or entry size {}",
+                header_size, entry_size
+            )));
+        }
+        let entry_count = read_u32(data, 16)? as usize;
+        let entries_offset = read_u32(data, 20)? as usize;
+        let page_size = read_u32(data, 24)?;
+        let version = read_u32(data, 28)?;
+        if version > 1 {
+            return Err(io::Error::other(format!(
+                "Unsupported DTBO version: {}",
+                version
+            )));
+        }
+
+        let mut entries = Vec::with_capacity(entry_count.min(1024));
+        for index in 0..entry_count {
+            let offset = entries_offset + index * entry_size as usize;
+            let mut custom = [0u32; 4];
+            for (word, value) in custom.iter_mut().enumerate() {
+                *value = read_u32(data, offset + 16 + word * 4)?;
+            }
+            entries.push(DtboEntry {
+                size: read_u32(data, offset)?,
+                offset: read_u32(data, offset + 4)?,
+                id: read_u32(data, offset + 8)?,
+                rev: read_u32(data, offset + 12)?,
+                custom,
+            });
+        }
+
+        Ok(DtboImage {
+            version,
+            page_size,
+            entries,
+        })
+    }
+
+    /// Compression of the overlay of `entry`.
+    pub fn compression(&self, entry: &DtboEntry) -> DtboCompression {
+        match self.version {
+            0 => DtboCompression::None,
+            _ => DtboCompression::from_flags(entry.custom[0]),
+        }
+    }
+
+    /// Overlay of `entry` in the image `data`, as stored.
+    pub fn overlay<'a>(&self, data: &'a [u8], entry: &DtboEntry) -> io::Result<&'a [u8]> {
+        let start = entry.offset as usize;
+        start
+            .checked_add(entry.size as usize)
+            .and_then(|end| data.get(start..end))
+            .ok_or_else(|| io::Error::other("DTBO entry is out of bounds"))
+    }
+
+    /// Builds an image like `mkdtimg`, with one entry per `(entry, overlay)`
+    /// pair in the given order; the offset and size of each entry are filled
+    /// in. Identical overlays are only stored once.
+    pub fn build(
+        version: u32,
+        page_size: u32,
+        entries: &[(DtboEntry, Vec<u8>)],
+    ) -> io::Result<Vec<u8>> {
+        if version > 1 {
+            return Err(io::Error::other(format!(
+                "Unsupported DTBO version: {}",
+                version
+            )));
+        }
+
+        let overlays_offset = DTBO_HEADER_SIZE + entries.len() as u32 * DTBO_ENTRY_SIZE;
+        let mut overlays: Vec<u8> = Vec::new();
+        let mut stored: Vec<(&[u8], u32)> = Vec::new();
+        let mut table = Vec::with_capacity(overlays_offset as usize);
+        for (entry, overlay) in entries {
+            let offset = match stored.iter().find(|(data, _)| *data == overlay.as_slice()) {
+                Some(&(_, offset)) => offset,
+                None => {
+                    let offset = overlays_offset + overlays.len() as u32;
+                    overlays.extend_from_slice(overlay);
+                    stored.push((overlay, offset));
+                    offset
+                }
+            };
+
+            table.extend_from_slice(&(overlay.len() as u32).to_be_bytes());
+            table.extend_from_slice(&offset.to_be_bytes());
+            table.extend_from_slice(&entry.id.to_be_bytes());
+            table.extend_from_slice(&entry.rev.to_be_bytes());
+            for word in entry.custom {
+                table.extend_from_slice(&word.to_be_bytes());
+            }
+        }
+
+        let total_size = overlays_offset + overlays.len() as u32;
+        let mut data = Vec::with_capacity(total_size as usize);
+        for word in [
+            DTBO_MAGIC,
+            total_size,
+            DTBO_HEADER_SIZE,
+            DTBO_ENTRY_SIZE,
+            entries.len() as u32,
+            DTBO_HEADER_SIZE,
+            page_size,
+            version,
+        ] {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        data.extend_from_slice(&table);
+        data.extend_from_slice(&overlays);
+
+        Ok(data)
+    }
+}
+
+/// Description of a DTBO image and the overlay files of its entries, stored
+/// as `key=value` lines so an extracted image can be rebuilt:
+///
+/// ```text
+/// version=0
+/// page_size=2048
+/// # entry=<file> id rev custom0 custom1 custom2 custom3
+/// entry=dtbo00.dtbo 0x00000000 0x00000000 0x00000000 0x00000000 ...
+/// ```
+///
+/// Missing custom words default to zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtboConfig {
+    pub version: u32,
+    pub page_size: u32,
+    /// Overlay file names, relative to the description, with their entries
+    pub entries: Vec<(String, DtboEntry)>,
+}
+
+impl DtboConfig {
+    /// Parses an image description. Blank lines and `#` comments are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut version = 0;
+        let mut page_size = DEFAULT_PAGE_SIZE;
+        let mut entries = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Invalid DTBO line: {}", line));
+            };
+            let value = value.trim();
+            match key.trim() {
+                "version" => version = parse_u32(value)?,
+                "page_size" => page_size = parse_u32(value)?,
+                "entry" => {
+                    let mut words = value.split_whitespace();
+                    let file = words
+                        .next()
+                        .ok_or_else(|| format!("Missing overlay file: {}", line))?;
+                    let words = words.map(parse_u32).collect::<Result<Vec<u32>, String>>()?;
+                    if words.len() > 6 {
+                        return Err(format!("Too many entry words: {}", line));
+                    }
+                    let mut fields = [0u32; 6];
+                    fields[..words.len()].copy_from_slice(&words);
+                    let entry = DtboEntry {
+                        size: 0,
+                        offset: 0,
+                        id: fields[0],
+                        rev: fields[1],
+                        custom: [fields[2], fields[3], fields[4], fields[5]],
+                    };
+                    entries.push((file.to_string(), entry));
+                }
+                key => return Err(format!("Unknown DTBO key: {}", key)),
+            }
+        }
+
+        Ok(DtboConfig {
+            version,
+            page_size,
+            entries,
+        })
+    }
+}
+
+impl fmt::Display for DtboConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version={}", self.version)?;
+        writeln!(f, "page_size={}", self.page_size)?;
+        writeln!(
+            f,
+            "# entry=<file> id rev {}",
+            DtboImage::custom_names(self.version).join(" ")
+        )?;
+        for (file, entry) in &self.entries {
+            let words: Vec<String> = [entry.id, entry.rev]
+                .iter()
+                .chain(&entry.custom)
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            writeln!(f, "entry={} {}", file, words.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::other("DTBO image is truncated"))
+}
+
+fn parse_u32(value: &str) -> Result<u32, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    }
+    .map_err(|err| format!("Invalid number {}: {}", value, err))
+}
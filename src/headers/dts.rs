@@ -0,0 +1,1471 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+
+use super::fdt::{Fdt, FdtHeader, FdtNode, FdtProperty, FdtReservation};
+
+/// Node mapping labels to node paths, as written by `dtc -@`.
+pub const SYMBOLS_NODE: &str = "__symbols__";
+
+/// Properties holding a list of phandles, besides `pinctrl-N` and `*-supply`.
+const PHANDLE_PROPERTIES: &[&str] = &[
+    "interrupt-parent",
+    "memory-region",
+    "operating-points-v2",
+    "cpu-idle-states",
+    "next-level-cache",
+    "nvmem-cells",
+    "remote-endpoint",
+];
+
+/// Properties holding phandles each followed by arguments, with the
+/// property of the target giving the number of arguments. `gpios` and
+/// `*-gpios` use `#gpio-cells`.
+const PHANDLE_ARGS_PROPERTIES: &[(&str, &str)] = &[
+    ("clocks", "#clock-cells"),
+    ("resets", "#reset-cells"),
+    ("power-domains", "#power-domain-cells"),
+    ("phys", "#phy-cells"),
+    ("dmas", "#dma-cells"),
+    ("iommus", "#iommu-cells"),
+    ("mboxes", "#mbox-cells"),
+    ("interrupts-extended", "#interrupt-cells"),
+    ("io-channels", "#io-channel-cells"),
+    ("thermal-sensors", "#thermal-sensor-cells"),
+    ("pwms", "#pwm-cells"),
+    ("cooling-device", "#cooling-cells"),
+    ("msi-parent", "#msi-cells"),
+    ("interconnects", "#interconnect-cells"),
+];
+
+/// Decompiles a device tree to DTS source, guessing property types like
+/// `dtc` does. Labels are restored from the `__symbols__` node, and
+/// phandles in well-known properties are written as references to them,
+/// or to the node path if the node has no label.
+pub fn decompile(fdt: &Fdt) -> String {
+    let decompiler = Decompiler::new(&fdt.root);
+    let mut dts = String::from("/dts-v1/;\n\n");
+    for reservation in &fdt.reservations {
+        let _ = writeln!(
+            dts,
+            "/memreserve/ 0x{:016x} 0x{:016x};",
+            reservation.address, reservation.size
+        );
+    }
+    if !fdt.reservations.is_empty() {
+        dts.push('\n');
+    }
+    decompiler.write_node(&mut dts, &fdt.root, "/", 0);
+    dts
+}
+
+struct Decompiler<'a> {
+    /// Labels of each node path
+    labels: HashMap<String, Vec<String>>,
+    /// Path and node of each phandle
+    phandles: HashMap<u32, (String, &'a FdtNode)>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(root: &'a FdtNode) -> Self {
+        let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+        if let Some(symbols) = root.child(SYMBOLS_NODE) {
+            for property in &symbols.properties {
+                if !is_label(&property.name) {
+                    continue;
+                }
+                if let Some([path]) = property.strings().as_deref() {
+                    labels
+                        .entry(path.clone())
+                        .or_default()
+                        .push(property.name.clone());
+                }
+            }
+        }
+
+        let mut decompiler = Decompiler {
+            labels,
+            phandles: HashMap::new(),
+        };
+        decompiler.collect_phandles(root, "/");
+        decompiler
+    }
+
+    fn collect_phandles(&mut self, node: &'a FdtNode, path: &str) {
+        if let Some(phandle) = node_phandle(node) {
+            self.phandles
+                .entry(phandle)
+                .or_insert_with(|| (path.to_string(), node));
+        }
+        for child in &node.children {
+            self.collect_phandles(child, &child_path(path, &child.name));
+        }
+    }
+
+    /// The reference to the node with a phandle: `&label`, or `&{/path}`.
+    fn reference(&self, phandle: u32) -> Option<String> {
+        let (path, _) = self.phandles.get(&phandle)?;
+        Some(
+            match self.labels.get(path).and_then(|labels| labels.first()) {
+                Some(label) => format!("&{}", label),
+                None => format!("&{{{}}}", path),
+            },
+        )
+    }
+
+    fn format_property(&self, property: &FdtProperty) -> Option<String> {
+        let name = property.name.as_str();
+        if let Some(cells) = property.cells().filter(|cells| !cells.is_empty()) {
+            if is_phandle_property(name) {
+                let references: Option<Vec<String>> = cells
+                    .iter()
+                    .map(|&phandle| self.reference(phandle))
+                    .collect();
+                if let Some(references) = references {
+                    return Some(format!("<{}>", references.join(" ")));
+                }
+            } else if let Some(cells_name) = phandle_args_cells(name) {
+                if let Some(value) = self.format_phandle_args(&cells, cells_name) {
+                    return Some(value);
+                }
+            }
+        }
+        property.format_value()
+    }
+
+    /// Formats phandles and their arguments as `<&a 0x1>, <&b 0x2>`, or
+    /// returns `None` if the cells do not match the targets.
+    fn format_phandle_args(&self, cells: &[u32], cells_name: &str) -> Option<String> {
+        let mut groups = Vec::new();
+        let mut rest = cells;
+        while let Some((&phandle, args)) = rest.split_first() {
+            let (_, node) = self.phandles.get(&phandle)?;
+            let count = node.property(cells_name)?.u32()? as usize;
+            if count > args.len() {
+                return None;
+            }
+            let mut group = vec![self.reference(phandle)?];
+            group.extend(args[..count].iter().map(|arg| format!("0x{:x}", arg)));
+            groups.push(format!("<{}>", group.join(" ")));
+            rest = &args[count..];
+        }
+        Some(groups.join(", "))
+    }
+
+    fn write_node(&self, dts: &mut String, node: &FdtNode, path: &str, depth: usize) {
+        let indent = "\t".repeat(depth);
+        dts.push_str(&indent);
+        for label in self.labels.get(path).into_iter().flatten() {
+            let _ = write!(dts, "{}: ", label);
+        }
+        let name = if node.name.is_empty() {
+            "/"
+        } else {
+            &node.name
+        };
+        let _ = writeln!(dts, "{} {{", name);
+        for property in &node.properties {
+            match self.format_property(property) {
+                Some(value) => {
+                    let _ = writeln!(dts, "{}\t{} = {};", indent, property.name, value);
+                }
+                None => {
+                    let _ = writeln!(dts, "{}\t{};", indent, property.name);
+                }
+            }
+        }
+        for child in &node.children {
+            dts.push('\n');
+            self.write_node(dts, child, &child_path(path, &child.name), depth + 1);
+        }
+        let _ = writeln!(dts, "{}}};", indent);
+    }
+}
+
+fn is_phandle_property(name: &str) -> bool {
+    PHANDLE_PROPERTIES.contains(&name)
+        || name.ends_with("-supply")
+        || name
+            .strip_prefix("pinctrl-")
+            .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn phandle_args_cells(name: &str) -> Option<&'static str> {
+    if name == "gpios" || name.ends_with("-gpios") || name.ends_with("-gpio") {
+        return Some("#gpio-cells");
+    }
+    PHANDLE_ARGS_PROPERTIES
+        .iter()
+        .find(|(property, _)| *property == name)
+        .map(|(_, cells_name)| *cells_name)
+}
+
+/// The phandle of a node, from `phandle` or the older `linux,phandle`.
+fn node_phandle(node: &FdtNode) -> Option<u32> {
+    ["phandle", "linux,phandle"]
+        .iter()
+        .filter_map(|name| node.property(name)?.u32())
+        .find(|&phandle| phandle != 0 && phandle != u32::MAX)
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    if path == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Compiles DTS source to a device tree, like `dtc -I dts -O dtb` does.
+///
+/// Source that still needs the C preprocessor (`#include`, macros) is
+/// rejected, as are overlays and `/incbin/`. Nodes referenced by phandle
+/// get one, starting from the lowest free value. The `__symbols__` node is
+/// rebuilt from the labels if the source has one or if `symbols` is set,
+/// like `dtc -@` does; labeled nodes then get a phandle too.
+pub fn compile(source: &str, symbols: bool) -> Result<Fdt, String> {
+    let mut parser = Parser::new(source);
+    let mut root = SourceNode::default();
+    let mut reservations = Vec::new();
+    parser
+        .parse_file(&mut root, &mut reservations)
+        .map_err(|err| format!("Line {}: {}", parser.line, err))?;
+
+    let symbols = symbols || root.children.iter().any(|child| child.name == SYMBOLS_NODE);
+    let mut resolver = Resolver::new(&root)?;
+    resolver.assign_phandles(&root, &root)?;
+    if symbols {
+        resolver.assign_labeled(&root, "/");
+        resolver.build_symbols(&mut root);
+    }
+    let root = resolver.resolve_node(&root, &root, "/")?;
+
+    let boot_cpuid_phys = root
+        .child("cpus")
+        .and_then(|cpus| cpus.children.first())
+        .and_then(|cpu| cpu.property("reg"))
+        .and_then(|reg| reg.value.get(..4))
+        .map_or(0, |cell| u32::from_be_bytes(cell.try_into().unwrap()));
+    Ok(Fdt {
+        header: FdtHeader {
+            boot_cpuid_phys,
+            ..Default::default()
+        },
+        reservations,
+        root,
+    })
+}
+
+/// Parses a property written in DTS syntax, `name = <0x1 0x2>`,
+/// `name = "string"` or `name = [01 02]`, or just `name` for an empty one.
+/// References are rejected, as there is no source to resolve them in.
+pub fn parse_property(text: &str) -> Result<FdtProperty, String> {
+    let mut parser = Parser::new(text);
+    let name = parser.name()?;
+    let chunks = if parser.eat(b'=') {
+        parser.value()?
+    } else {
+        Vec::new()
+    };
+    parser.eat(b';');
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(format!("Unexpected text after the value of {}", name));
+    }
+
+    let mut value = Vec::new();
+    for chunk in chunks {
+        match chunk {
+            Chunk::Bytes(bytes) => value.extend_from_slice(&bytes),
+            Chunk::Phandle(reference) | Chunk::Path(reference) => {
+                return Err(format!(
+                    "{} cannot be resolved here, use the phandle or the path instead",
+                    reference
+                ))
+            }
+        }
+    }
+    Ok(FdtProperty { name, value })
+}
+
+/// How a property value is given and printed, like the `-t` option of
+/// `fdtget` and `fdtput`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// DTS syntax, with the type guessed when printing
+    Dts,
+    /// A list of strings, one per line when printing
+    String,
+    /// 32-bit cells, in decimal when printing
+    U32,
+    /// 64-bit integers, in decimal when printing
+    U64,
+    /// Bytes, in hex
+    Bytes,
+}
+
+impl ValueType {
+    pub fn format(&self, property: &FdtProperty) -> Result<String, String> {
+        let words: Vec<String> = match self {
+            ValueType::Dts => return Ok(property.format_value().unwrap_or_default()),
+            ValueType::String => {
+                return property
+                    .strings()
+                    .map(|strings| strings.join("\n"))
+                    .ok_or_else(|| format!("{} is not a list of strings", property.name))
+            }
+            ValueType::U32 => property
+                .cells()
+                .ok_or_else(|| format!("{} is not a list of 32-bit cells", property.name))?
+                .iter()
+                .map(u32::to_string)
+                .collect(),
+            ValueType::U64 => {
+                if !property.value.len().is_multiple_of(8) {
+                    return Err(format!(
+                        "{} is not a list of 64-bit integers",
+                        property.name
+                    ));
+                }
+                property
+                    .value
+                    .chunks_exact(8)
+                    .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()).to_string())
+                    .collect()
+            }
+            ValueType::Bytes => property
+                .value
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        };
+        Ok(words.join(" "))
+    }
+
+    /// Parses a value given as separate words. Each integer is a word of
+    /// its own, in any base DTS allows or as an expression, negative ones
+    /// included. DTS values may be split across words.
+    pub fn parse(&self, words: &[String]) -> Result<Vec<u8>, String> {
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let text = words.join(" ");
+        let integers = || {
+            words
+                .iter()
+                .map(|word| format!("({})", word))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let value = match self {
+            ValueType::String => {
+                let mut value = Vec::new();
+                for word in words {
+                    value.extend_from_slice(word.as_bytes());
+                    value.push(0);
+                }
+                return Ok(value);
+            }
+            ValueType::Dts => text,
+            ValueType::U32 => format!("<{}>", integers()),
+            ValueType::U64 => format!("/bits/ 64 <{}>", integers()),
+            ValueType::Bytes => format!("[{}]", text),
+        };
+        parse_property(&format!("value = {}", value)).map(|property| property.value)
+    }
+}
+
+/// Drops the `__symbols__` entries whose node no longer exists.
+pub fn prune_symbols(root: &mut FdtNode) {
+    let Some(symbols) = root.child(SYMBOLS_NODE) else {
+        return;
+    };
+    let stale: Vec<String> = symbols
+        .properties
+        .iter()
+        .filter(|property| match property.strings().as_deref() {
+            Some([path]) => root.node(path).is_none(),
+            _ => false,
+        })
+        .map(|property| property.name.clone())
+        .collect();
+    if let Some(symbols) = root.child_mut(SYMBOLS_NODE) {
+        symbols
+            .properties
+            .retain(|property| !stale.contains(&property.name));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Reference {
+    Label(String),
+    Path(String),
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::Label(label) => write!(f, "&{}", label),
+            Reference::Path(path) => write!(f, "&{{{}}}", path),
+        }
+    }
+}
+
+/// Part of a property value, before references are resolved.
+#[derive(Debug, Clone)]
+enum Chunk {
+    Bytes(Vec<u8>),
+    /// A reference in cells, replaced by the phandle of the node
+    Phandle(Reference),
+    /// A bare reference, replaced by the path of the node
+    Path(Reference),
+}
+
+#[derive(Debug, Clone)]
+struct SourceProperty {
+    name: String,
+    value: Vec<Chunk>,
+    line: usize,
+}
+
+impl SourceProperty {
+    /// The value if it has no references.
+    fn bytes(&self) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for chunk in &self.value {
+            match chunk {
+                Chunk::Bytes(data) => bytes.extend_from_slice(data),
+                _ => return None,
+            }
+        }
+        Some(bytes)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct SourceNode {
+    name: String,
+    labels: Vec<String>,
+    properties: Vec<SourceProperty>,
+    children: Vec<SourceNode>,
+}
+
+impl SourceNode {
+    fn set_property(&mut self, property: SourceProperty) {
+        match self
+            .properties
+            .iter_mut()
+            .find(|existing| existing.name == property.name)
+        {
+            Some(existing) => *existing = property,
+            None => self.properties.push(property),
+        }
+    }
+
+    fn add_label(&mut self, label: String) {
+        if !self.labels.contains(&label) {
+            self.labels.push(label);
+        }
+    }
+
+    /// Indices of the nodes leading to the referenced node.
+    fn find(&self, reference: &Reference) -> Option<Vec<usize>> {
+        match reference {
+            Reference::Label(label) => self.find_label(label),
+            Reference::Path(path) => {
+                let mut indices = Vec::new();
+                let mut node = self;
+                for name in path.split('/').filter(|name| !name.is_empty()) {
+                    let index = node
+                        .children
+                        .iter()
+                        .position(|child| child.name == name)
+                        .or_else(|| {
+                            node.children.iter().position(|child| {
+                                !name.contains('@') && child.name.split('@').next() == Some(name)
+                            })
+                        })?;
+                    indices.push(index);
+                    node = &node.children[index];
+                }
+                path.starts_with('/').then_some(indices)
+            }
+        }
+    }
+
+    fn find_label(&self, label: &str) -> Option<Vec<usize>> {
+        if self.labels.iter().any(|existing| existing == label) {
+            return Some(Vec::new());
+        }
+        self.children.iter().enumerate().find_map(|(index, child)| {
+            let mut indices = child.find_label(label)?;
+            indices.insert(0, index);
+            Some(indices)
+        })
+    }
+
+    fn get_mut(&mut self, indices: &[usize]) -> &mut SourceNode {
+        indices
+            .iter()
+            .fold(self, |node, &index| &mut node.children[index])
+    }
+
+    fn path(&self, indices: &[usize]) -> String {
+        let mut path = String::new();
+        let mut node = self;
+        for &index in indices {
+            node = &node.children[index];
+            path.push('/');
+            path.push_str(&node.name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+}
+
+/// Directives left for the C preprocessor, which the parser rejects.
+const PREPROCESSOR_DIRECTIVES: &[&str] = &[
+    "#include", "#define", "#undef", "#if", "#ifdef", "#ifndef", "#else", "#elif", "#endif",
+];
+
+fn error<T>(message: impl fmt::Display) -> Result<T, String> {
+    Err(message.to_string())
+}
+
+/// Characters of node and property names.
+fn is_name_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b",._+*#?@-".contains(&byte)
+}
+
+fn is_label_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// Binary operators of cell expressions, longest first, with their
+/// precedence.
+const OPERATORS: &[(&str, u8)] = &[
+    ("||", 1),
+    ("&&", 2),
+    ("<<", 8),
+    (">>", 8),
+    ("<=", 7),
+    (">=", 7),
+    ("==", 6),
+    ("!=", 6),
+    ("|", 3),
+    ("^", 4),
+    ("&", 5),
+    ("<", 7),
+    (">", 7),
+    ("+", 9),
+    ("-", 9),
+    ("*", 10),
+    ("/", 10),
+    ("%", 10),
+];
+
+struct Parser<'a> {
+    source: &'a [u8],
+    offset: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Parser {
+            source: source.as_bytes(),
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.offset).copied()
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<u8> {
+        self.source.get(self.offset + ahead).copied()
+    }
+
+    fn advance(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.offset += 1;
+        if byte == b'\n' {
+            self.line += 1;
+        }
+        Some(byte)
+    }
+
+    /// Skips whitespace, comments and the line markers left by `cpp`.
+    fn skip_whitespace(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(byte), _) if byte.is_ascii_whitespace() => {
+                    self.advance();
+                }
+                (Some(b'/'), Some(b'/')) => self.skip_line(),
+                (Some(b'#'), Some(b' ')) => self.skip_line(),
+                (Some(b'/'), Some(b'*')) => {
+                    self.offset += 2;
+                    while self.peek().is_some()
+                        && !(self.peek() == Some(b'*') && self.peek_at(1) == Some(b'/'))
+                    {
+                        self.advance();
+                    }
+                    self.offset = (self.offset + 2).min(self.source.len());
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|byte| byte != b'\n') {
+            self.advance();
+        }
+    }
+
+    /// Skips whitespace and consumes `expected` if it comes next.
+    fn eat(&mut self, expected: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(byte) => error(format!(
+                "Expected '{}', found '{}'",
+                expected as char, byte as char
+            )),
+            None => error(format!("Expected '{}', found the end", expected as char)),
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(u8) -> bool) -> String {
+        let start = self.offset;
+        while self.peek().is_some_and(&predicate) {
+            self.advance();
+        }
+        String::from_utf8_lossy(&self.source[start..self.offset]).into_owned()
+    }
+
+    /// Reads a `/keyword/` if one comes next.
+    fn keyword(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.peek() != Some(b'/') || !self.peek_at(1).is_some_and(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        let start = self.offset;
+        self.advance();
+        let keyword = self.take_while(|byte| byte.is_ascii_alphanumeric() || byte == b'-');
+        if self.peek() == Some(b'/') {
+            self.advance();
+            Some(format!("/{}/", keyword))
+        } else {
+            self.offset = start;
+            None
+        }
+    }
+
+    /// Reads the `label:` prefixes that come next.
+    fn labels(&mut self) -> Vec<String> {
+        let mut labels = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.offset;
+            let label = self.take_while(is_label_char);
+            if is_label(&label) && self.peek() == Some(b':') {
+                self.advance();
+                labels.push(label);
+            } else {
+                self.offset = start;
+                return labels;
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let name = self.take_while(is_name_char);
+        if name.is_empty() {
+            return match self.peek() {
+                Some(byte) => error(format!("Expected a name, found '{}'", byte as char)),
+                None => error("Expected a name, found the end"),
+            };
+        }
+        if PREPROCESSOR_DIRECTIVES.contains(&name.as_str()) {
+            return error(format!(
+                "{} needs the C preprocessor, run the source through cpp first",
+                name
+            ));
+        }
+        Ok(name)
+    }
+
+    /// Reads `&label` or `&{/path}`.
+    fn reference(&mut self) -> Result<Reference, String> {
+        self.expect(b'&')?;
+        if self.peek() == Some(b'{') {
+            self.advance();
+            let path = self.take_while(|byte| byte != b'}' && byte != b'\n');
+            self.expect(b'}')?;
+            return Ok(Reference::Path(path));
+        }
+        let label = self.take_while(is_label_char);
+        if !is_label(&label) {
+            return error("Expected a label or a path after '&'");
+        }
+        Ok(Reference::Label(label))
+    }
+
+    fn parse_file(
+        &mut self,
+        root: &mut SourceNode,
+        reservations: &mut Vec<FdtReservation>,
+    ) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            let labels = self.labels();
+            if let Some(keyword) = self.keyword() {
+                match keyword.as_str() {
+                    "/dts-v1/" => self.expect(b';')?,
+                    "/memreserve/" => {
+                        let address = self.integer()?;
+                        let size = self.integer()?;
+                        self.expect(b';')?;
+                        reservations.push(FdtReservation { address, size });
+                    }
+                    "/delete-node/" => {
+                        let reference = self.reference()?;
+                        self.expect(b';')?;
+                        let Some(indices) = root.find(&reference) else {
+                            return error(format!("{} not found", reference));
+                        };
+                        let Some((&index, parent)) = indices.split_last() else {
+                            return error("Cannot delete the root node");
+                        };
+                        root.get_mut(parent).children.remove(index);
+                    }
+                    "/omit-if-no-ref/" => {
+                        self.reference()?;
+                        self.expect(b';')?;
+                    }
+                    "/plugin/" => return error("Overlays (/plugin/) are not supported"),
+                    keyword => return error(format!("Unsupported directive {}", keyword)),
+                }
+                continue;
+            }
+
+            match self.peek() {
+                Some(b'/') => {
+                    self.advance();
+                    for label in labels {
+                        root.add_label(label);
+                    }
+                    self.parse_body(root)?;
+                }
+                Some(b'&') => {
+                    let reference = self.reference()?;
+                    let Some(indices) = root.find(&reference) else {
+                        return error(format!("{} not found", reference));
+                    };
+                    let node = root.get_mut(&indices);
+                    for label in labels {
+                        node.add_label(label);
+                    }
+                    self.parse_body(node)?;
+                }
+                Some(b'#') => {
+                    let directive = self.take_while(|byte| !byte.is_ascii_whitespace());
+                    return error(format!(
+                        "{} needs the C preprocessor, run the source through cpp first",
+                        directive
+                    ));
+                }
+                Some(byte) => return error(format!("Unexpected '{}'", byte as char)),
+                None => return error("Unexpected end after a label"),
+            }
+        }
+    }
+
+    /// Parses `{ ... };` into `node`, merging with what it already holds.
+    fn parse_body(&mut self, node: &mut SourceNode) -> Result<(), String> {
+        self.expect(b'{')?;
+        loop {
+            if self.eat(b'}') {
+                return self.expect(b';');
+            }
+            if let Some(keyword) = self.keyword() {
+                match keyword.as_str() {
+                    "/delete-property/" => {
+                        let name = self.name()?;
+                        self.expect(b';')?;
+                        node.properties.retain(|property| property.name != name);
+                    }
+                    "/delete-node/" => {
+                        let name = self.name()?;
+                        self.expect(b';')?;
+                        node.children.retain(|child| child.name != name);
+                    }
+                    "/omit-if-no-ref/" => {}
+                    keyword => return error(format!("Unsupported directive {}", keyword)),
+                }
+                continue;
+            }
+
+            let labels = self.labels();
+            let line = self.line;
+            let name = self.name()?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'{') => {
+                    let index = match node.children.iter().position(|child| child.name == name) {
+                        Some(index) => index,
+                        None => {
+                            node.children.push(SourceNode {
+                                name,
+                                ..Default::default()
+                            });
+                            node.children.len() - 1
+                        }
+                    };
+                    let child = &mut node.children[index];
+                    for label in labels {
+                        child.add_label(label);
+                    }
+                    self.parse_body(child)?;
+                }
+                Some(b'=') => {
+                    self.advance();
+                    let value = self.value()?;
+                    self.expect(b';')?;
+                    node.set_property(SourceProperty { name, value, line });
+                }
+                Some(b';') => {
+                    self.advance();
+                    node.set_property(SourceProperty {
+                        name,
+                        value: Vec::new(),
+                        line,
+                    });
+                }
+                _ => return error(format!("Expected '=', ';' or '{{' after {}", name)),
+            }
+        }
+    }
+
+    /// Parses a property value: strings, cells, bytes and references,
+    /// separated by commas.
+    fn value(&mut self) -> Result<Vec<Chunk>, String> {
+        let mut value = Vec::new();
+        loop {
+            self.labels();
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'"') => {
+                    self.advance();
+                    let mut string = Vec::new();
+                    loop {
+                        match self.advance() {
+                            Some(b'"') => break,
+                            Some(b'\\') => string.push(self.escape()?),
+                            Some(byte) => string.push(byte),
+                            None => return error("Unterminated string"),
+                        }
+                    }
+                    string.push(0);
+                    value.push(Chunk::Bytes(string));
+                }
+                Some(b'<') => self.cells(32, &mut value)?,
+                Some(b'[') => {
+                    self.advance();
+                    let mut bytes = Vec::new();
+                    loop {
+                        self.labels();
+                        if self.eat(b']') {
+                            break;
+                        }
+                        let digits = [self.advance(), self.advance()];
+                        let byte = match digits {
+                            [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                                .ok()
+                                .and_then(|digits| u8::from_str_radix(digits, 16).ok()),
+                            _ => None,
+                        };
+                        match byte {
+                            Some(byte) => bytes.push(byte),
+                            None => return error("Invalid byte string"),
+                        }
+                    }
+                    value.push(Chunk::Bytes(bytes));
+                }
+                Some(b'&') => value.push(Chunk::Path(self.reference()?)),
+                Some(b'/') => match self.keyword().as_deref() {
+                    Some("/bits/") => {
+                        let bits = self.integer()?;
+                        if ![8, 16, 32, 64].contains(&bits) {
+                            return error(format!("Invalid /bits/ size: {}", bits));
+                        }
+                        self.skip_whitespace();
+                        self.cells(bits as u32, &mut value)?;
+                    }
+                    Some(keyword) => return error(format!("Unsupported directive {}", keyword)),
+                    None => return error("Expected a property value"),
+                },
+                _ => return error("Expected a property value"),
+            }
+            if !self.eat(b',') {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Parses `<...>` cells of `bits` bits each.
+    fn cells(&mut self, bits: u32, value: &mut Vec<Chunk>) -> Result<(), String> {
+        self.expect(b'<')?;
+        let mut bytes = Vec::new();
+        loop {
+            self.labels();
+            if self.eat(b'>') {
+                break;
+            }
+            if self.peek() == Some(b'&') {
+                if bits != 32 {
+                    return error("References are only allowed in 32-bit cells");
+                }
+                value.push(Chunk::Bytes(std::mem::take(&mut bytes)));
+                value.push(Chunk::Phandle(self.reference()?));
+                continue;
+            }
+            let cell = self.integer()?;
+            let mask = u64::MAX >> (64 - bits);
+            if cell & !mask != 0 && cell | mask != u64::MAX {
+                return error(format!("0x{:x} does not fit in {} bits", cell, bits));
+            }
+            bytes.extend_from_slice(&cell.to_be_bytes()[(64 - bits as usize) / 8..]);
+        }
+        value.push(Chunk::Bytes(bytes));
+        Ok(())
+    }
+
+    /// Reads an escape sequence, after the backslash.
+    fn escape(&mut self) -> Result<u8, String> {
+        let Some(byte) = self.advance() else {
+            return error("Unterminated escape sequence");
+        };
+        Ok(match byte {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            b'x' => {
+                let start = self.offset;
+                while self.offset - start < 2 && self.peek().is_some_and(|b| b.is_ascii_hexdigit())
+                {
+                    self.advance();
+                }
+                let digits = std::str::from_utf8(&self.source[start..self.offset]).unwrap();
+                match u8::from_str_radix(digits, 16) {
+                    Ok(byte) => byte,
+                    Err(_) => return error("Invalid \\x escape sequence"),
+                }
+            }
+            b'0'..=b'7' => {
+                let mut value = (byte - b'0') as u32;
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(digit @ b'0'..=b'7') => {
+                            self.advance();
+                            value = value * 8 + (digit - b'0') as u32;
+                        }
+                        _ => break,
+                    }
+                }
+                value as u8
+            }
+            byte => byte,
+        })
+    }
+
+    /// Reads a number, a character literal or a parenthesized expression.
+    fn integer(&mut self) -> Result<u64, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'(') => {
+                self.advance();
+                let value = self.expression()?;
+                self.expect(b')')?;
+                Ok(value)
+            }
+            Some(b'\'') => {
+                self.advance();
+                let value = match self.advance() {
+                    Some(b'\\') => self.escape()?,
+                    Some(byte) => byte,
+                    None => return error("Unterminated character literal"),
+                };
+                if self.advance() != Some(b'\'') {
+                    return error("Unterminated character literal");
+                }
+                Ok(value as u64)
+            }
+            Some(byte) if byte.is_ascii_digit() => {
+                let literal = self.take_while(|byte| byte.is_ascii_alphanumeric());
+                let digits = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+                let value = if let Some(hex) = digits
+                    .strip_prefix("0x")
+                    .or_else(|| digits.strip_prefix("0X"))
+                {
+                    u64::from_str_radix(hex, 16)
+                } else if digits.len() > 1 && digits.starts_with('0') {
+                    u64::from_str_radix(&digits[1..], 8)
+                } else {
+                    digits.parse()
+                };
+                match value {
+                    Ok(value) => Ok(value),
+                    Err(_) => error(format!("Invalid number: {}", literal)),
+                }
+            }
+            Some(byte) if is_label_char(byte) => {
+                let identifier = self.take_while(is_label_char);
+                error(format!(
+                    "Unknown identifier {}, run the source through cpp first to expand macros",
+                    identifier
+                ))
+            }
+            Some(byte) => error(format!("Expected a number, found '{}'", byte as char)),
+            None => error("Expected a number, found the end"),
+        }
+    }
+
+    fn expression(&mut self) -> Result<u64, String> {
+        let condition = self.binary(1)?;
+        if !self.eat(b'?') {
+            return Ok(condition);
+        }
+        let if_true = self.expression()?;
+        self.expect(b':')?;
+        let if_false = self.expression()?;
+        Ok(if condition != 0 { if_true } else { if_false })
+    }
+
+    fn binary(&mut self, precedence: u8) -> Result<u64, String> {
+        if precedence > 10 {
+            return self.unary();
+        }
+        let mut left = self.binary(precedence + 1)?;
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.offset..];
+            let Some(&(operator, _)) = OPERATORS
+                .iter()
+                .find(|(operator, _)| rest.starts_with(operator.as_bytes()))
+                .filter(|(_, level)| *level == precedence)
+            else {
+                return Ok(left);
+            };
+            self.offset += operator.len();
+            let right = self.binary(precedence + 1)?;
+            left = match operator {
+                "||" => (left != 0 || right != 0) as u64,
+                "&&" => (left != 0 && right != 0) as u64,
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "==" => (left == right) as u64,
+                "!=" => (left != right) as u64,
+                "<" => (left < right) as u64,
+                ">" => (left > right) as u64,
+                "<=" => (left <= right) as u64,
+                ">=" => (left >= right) as u64,
+                "<<" => left.checked_shl(right as u32).unwrap_or(0),
+                ">>" => left.checked_shr(right as u32).unwrap_or(0),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                _ if right == 0 => return error("Division by zero"),
+                "/" => left / right,
+                _ => left % right,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<u64, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'-') => {
+                self.advance();
+                Ok(self.unary()?.wrapping_neg())
+            }
+            Some(b'~') => {
+                self.advance();
+                Ok(!self.unary()?)
+            }
+            Some(b'!') => {
+                self.advance();
+                Ok((self.unary()? == 0) as u64)
+            }
+            _ => self.integer(),
+        }
+    }
+}
+
+/// Resolves references once the whole source is parsed.
+struct Resolver {
+    /// Path of each label
+    labels: HashMap<String, String>,
+    /// Phandle of each node path, given in the source or assigned
+    phandles: HashMap<String, u32>,
+    /// Paths of the nodes that were assigned a phandle, in order
+    assigned: Vec<String>,
+}
+
+impl Resolver {
+    fn new(root: &SourceNode) -> Result<Self, String> {
+        let mut resolver = Resolver {
+            labels: HashMap::new(),
+            phandles: HashMap::new(),
+            assigned: Vec::new(),
+        };
+        resolver.collect(root, "/")?;
+        Ok(resolver)
+    }
+
+    fn collect(&mut self, node: &SourceNode, path: &str) -> Result<(), String> {
+        for label in &node.labels {
+            if self.labels.contains_key(label) {
+                return Err(format!("Duplicate label {}", label));
+            }
+            self.labels.insert(label.clone(), path.to_string());
+        }
+        let phandle = ["phandle", "linux,phandle"].iter().find_map(|name| {
+            let bytes = node
+                .properties
+                .iter()
+                .find(|property| property.name == *name)?
+                .bytes()?;
+            Some(u32::from_be_bytes(bytes.try_into().ok()?))
+        });
+        if let Some(phandle) = phandle {
+            self.phandles.insert(path.to_string(), phandle);
+        }
+        for child in &node.children {
+            self.collect(child, &child_path(path, &child.name))?;
+        }
+        Ok(())
+    }
+
+    /// Path of the referenced node.
+    fn path(&self, root: &SourceNode, reference: &Reference) -> Option<String> {
+        match reference {
+            Reference::Label(label) => self.labels.get(label).cloned(),
+            Reference::Path(_) => root.find(reference).map(|indices| root.path(&indices)),
+        }
+    }
+
+    fn phandle(&mut self, path: String) -> u32 {
+        if let Some(&phandle) = self.phandles.get(&path) {
+            return phandle;
+        }
+        let mut phandle = 1;
+        while self.phandles.values().any(|&used| used == phandle) {
+            phandle += 1;
+        }
+        self.phandles.insert(path.clone(), phandle);
+        self.assigned.push(path);
+        phandle
+    }
+
+    /// Gives a phandle to every node referenced in cells, in the order of
+    /// the references.
+    fn assign_phandles(&mut self, root: &SourceNode, node: &SourceNode) -> Result<(), String> {
+        for property in &node.properties {
+            for chunk in &property.value {
+                if let Chunk::Phandle(reference) = chunk {
+                    let Some(path) = self.path(root, reference) else {
+                        return Err(format!("Line {}: {} not found", property.line, reference));
+                    };
+                    self.phandle(path);
+                }
+            }
+        }
+        for child in &node.children {
+            self.assign_phandles(root, child)?;
+        }
+        Ok(())
+    }
+
+    /// Gives a phandle to every node with a label, so overlays can refer
+    /// to it through `__symbols__`.
+    fn assign_labeled(&mut self, node: &SourceNode, path: &str) {
+        if !node.labels.is_empty() {
+            self.phandle(path.to_string());
+        }
+        for child in &node.children {
+            self.assign_labeled(child, &child_path(path, &child.name));
+        }
+    }
+
+    /// Rebuilds the `__symbols__` node from the labels, keeping the order
+    /// of the symbols it already lists.
+    fn build_symbols(&self, root: &mut SourceNode) {
+        let mut labels = Vec::new();
+        collect_labels(root, "/", &mut labels);
+
+        let index = match root
+            .children
+            .iter()
+            .position(|child| child.name == SYMBOLS_NODE)
+        {
+            Some(index) => index,
+            None => {
+                root.children.push(SourceNode {
+                    name: SYMBOLS_NODE.to_string(),
+                    ..Default::default()
+                });
+                root.children.len() - 1
+            }
+        };
+        let symbols = &mut root.children[index];
+        symbols
+            .properties
+            .retain(|property| self.labels.contains_key(&property.name));
+        for (label, path) in labels {
+            let mut value = path.into_bytes();
+            value.push(0);
+            symbols.set_property(SourceProperty {
+                name: label,
+                value: vec![Chunk::Bytes(value)],
+                line: 0,
+            });
+        }
+    }
+
+    fn resolve_node(
+        &self,
+        root: &SourceNode,
+        node: &SourceNode,
+        path: &str,
+    ) -> Result<FdtNode, String> {
+        let mut properties = Vec::with_capacity(node.properties.len() + 1);
+        for property in &node.properties {
+            let mut value = Vec::new();
+            for chunk in &property.value {
+                match chunk {
+                    Chunk::Bytes(bytes) => value.extend_from_slice(bytes),
+                    Chunk::Phandle(reference) => {
+                        let phandle = self
+                            .path(root, reference)
+                            .and_then(|path| self.phandles.get(&path));
+                        match phandle {
+                            Some(phandle) => value.extend_from_slice(&phandle.to_be_bytes()),
+                            None => {
+                                return Err(format!(
+                                    "Line {}: {} not found",
+                                    property.line, reference
+                                ))
+                            }
+                        }
+                    }
+                    Chunk::Path(reference) => match self.path(root, reference) {
+                        Some(path) => {
+                            value.extend_from_slice(path.as_bytes());
+                            value.push(0);
+                        }
+                        None => {
+                            return Err(format!("Line {}: {} not found", property.line, reference))
+                        }
+                    },
+                }
+            }
+            properties.push(FdtProperty {
+                name: property.name.clone(),
+                value,
+            });
+        }
+        if self.assigned.iter().any(|assigned| assigned == path) {
+            properties.push(FdtProperty {
+                name: "phandle".to_string(),
+                value: self.phandles[path].to_be_bytes().to_vec(),
+            });
+        }
+
+        let mut children = Vec::with_capacity(node.children.len());
+        for child in &node.children {
+            children.push(self.resolve_node(root, child, &child_path(path, &child.name))?);
+        }
+        Ok(FdtNode {
+            name: node.name.clone(),
+            properties,
+            children,
+        })
+    }
+}
+
+/// Labels and node paths, in tree order.
+fn collect_labels(node: &SourceNode, path: &str, labels: &mut Vec<(String, String)>) {
+    for label in &node.labels {
+        labels.push((label.clone(), path.to_string()));
+    }
+    for child in &node.children {
+        collect_labels(child, &child_path(path, &child.name), labels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"/dts-v1/;
+
+/memreserve/ 0x80000000 0x100000;
+
+/ {
+	model = "Test Board";
+	compatible = "vendor,board";
+	#address-cells = <1>;
+	#size-cells = <1>;
+
+	intc: interrupt-controller@1000 {
+		reg = <0x1000 0x100>;
+		interrupt-controller;
+		#interrupt-cells = <1>;
+	};
+
+	gpio: gpio@2000 {
+		reg = <0x2000 0x100>;
+		gpio-controller;
+		#gpio-cells = <2>;
+	};
+
+	serial: serial@3000 {
+		reg = <0x3000 0x100>;
+		interrupt-parent = <&intc>;
+		interrupts = <5>;
+		reset-gpios = <&gpio 3 0>;
+		timestamp = /bits/ 64 <0x123456789 (1 << 40)>;
+		status = "disabled";
+	};
+};
+
+&serial {
+	status = "okay";
+};
+"#;
+
+    fn compile_source(symbols: bool) -> Fdt {
+        compile(SOURCE, symbols).unwrap()
+    }
+
+    fn phandle(fdt: &Fdt, path: &str) -> u32 {
+        fdt.root
+            .node(path)
+            .unwrap()
+            .property("phandle")
+            .unwrap()
+            .u32()
+            .unwrap()
+    }
+
+    #[test]
+    fn compile_resolves_references() {
+        let fdt = compile_source(false);
+        let serial = fdt.root.node("/serial@3000").unwrap();
+        let intc = phandle(&fdt, "/interrupt-controller@1000");
+        let gpio = phandle(&fdt, "/gpio@2000");
+        assert_ne!(intc, gpio);
+        assert_eq!(
+            serial.property("interrupt-parent").unwrap().cells(),
+            Some(vec![intc])
+        );
+        assert_eq!(
+            serial.property("reset-gpios").unwrap().cells(),
+            Some(vec![gpio, 3, 0])
+        );
+        assert!(fdt.root.child(SYMBOLS_NODE).is_none());
+    }
+
+    #[test]
+    fn compile_bits_memreserve_and_overrides() {
+        let fdt = compile_source(false);
+        let serial = fdt.root.node("/serial@3000").unwrap();
+        let timestamp = [0x123456789u64, 1 << 40]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(serial.property("timestamp").unwrap().value, timestamp);
+        assert_eq!(
+            serial.property("status").unwrap().strings(),
+            Some(vec!["okay".to_string()])
+        );
+        assert_eq!(
+            fdt.reservations,
+            vec![FdtReservation {
+                address: 0x80000000,
+                size: 0x100000,
+            }]
+        );
+    }
+
+    #[test]
+    fn compile_generates_symbols() {
+        let fdt = compile_source(true);
+        let symbols = fdt.root.child(SYMBOLS_NODE).unwrap();
+        let names: Vec<&str> = symbols
+            .properties
+            .iter()
+            .map(|property| property.name.as_str())
+            .collect();
+        assert_eq!(names, ["intc", "gpio", "serial"]);
+        assert_eq!(
+            symbols.property("serial").unwrap().strings(),
+            Some(vec!["/serial@3000".to_string()])
+        );
+        // Labeled nodes get a phandle even when nothing references them
+        phandle(&fdt, "/serial@3000");
+    }
+
+    #[test]
+    fn decompile_keeps_labels_and_references() {
+        let source = decompile(&compile_source(true));
+        assert!(source.contains("intc: interrupt-controller@1000 {"));
+        assert!(source.contains("interrupt-parent = <&intc>;"));
+        assert!(source.contains("reset-gpios = <&gpio 0x3 0x0>;"));
+        assert!(source.contains("/memreserve/ 0x0000000080000000 0x0000000000100000;"));
+    }
+
+    #[test]
+    fn round_trip_is_byte_stable() {
+        for symbols in [false, true] {
+            let dtb = compile_source(symbols).to_bytes().unwrap();
+            let source = decompile(&Fdt::parse(&dtb).unwrap());
+            let recompiled = compile(&source, symbols).unwrap().to_bytes().unwrap();
+            assert!(recompiled == dtb, "{}", source);
+            assert_eq!(decompile(&Fdt::parse(&recompiled).unwrap()), source);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::io::{self, Cursor};
+
+use binrw::{BinRead, BinWrite};
+
+/// Magic of a flattened device tree, stored big-endian.
+pub const FDT_MAGIC: u32 = 0xd00dfeed;
+pub const FDT_HEADER_SIZE: usize = 40;
+
+/// Oldest version whose structure block can be read: node names are
+/// relative and property names live in the strings block.
+pub const FDT_MIN_VERSION: u32 = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Header of a flattened device tree blob (DTB).
+#[derive(Debug, Default, Clone, PartialEq, Eq, BinRead, BinWrite)]
+#[brw(big, magic = 0xd00dfeedu32)]
+pub struct FdtHeader {
+    pub totalsize: u32,
+    pub off_dt_struct: u32,
+    pub off_dt_strings: u32,
+    pub off_mem_rsvmap: u32,
+    pub version: u32,
+    pub last_comp_version: u32,
+    pub boot_cpuid_phys: u32,
+    pub size_dt_strings: u32,
+    /// Only set from version 17 on
+    pub size_dt_struct: u32,
+}
+
+impl FdtHeader {
+    /// Reads the header at the start of `data`. Returns `None` if `data`
+    /// does not start with a header whose blocks all fit in the blob and
+    /// the blob in `data`.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let header = Self::read(&mut Cursor::new(data.get(..FDT_HEADER_SIZE)?)).ok()?;
+        let totalsize = header.totalsize as u64;
+        let fits = |offset: u32, size: u32| offset as u64 + size as u64 <= totalsize;
+        let valid = totalsize >= FDT_HEADER_SIZE as u64
+            && totalsize <= data.len() as u64
+            && header.last_comp_version <= header.version
+            && fits(header.off_mem_rsvmap, 0)
+            && fits(header.off_dt_strings, header.size_dt_strings)
+            && (header.version < 17 || fits(header.off_dt_struct, header.size_dt_struct));
+        valid.then_some(header)
+    }
+
+    /// Size of the blob at the start of `data`, or `None` if it does not
+    /// start with one.
+    pub fn total_size(data: &[u8]) -> Option<usize> {
+        Self::parse(data).map(|header| header.totalsize as usize)
+    }
+}
+
+/// Splits `data` into the device tree blobs it is made of, or returns
+/// `None` if it is not exactly a sequence of blobs.
+pub fn split_fdts(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut blobs = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let size = FdtHeader::total_size(rest)?;
+        let (blob, next) = rest.split_at(size);
+        blobs.push(blob);
+        rest = next;
+    }
+    Some(blobs)
+}
+
+/// Entry of the memory reservation map.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FdtReservation {
+    pub address: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FdtProperty {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+impl FdtProperty {
+    /// The value as a list of strings, if it looks like one: printable
+    /// ASCII strings, none of them empty, each one NUL terminated.
+    pub fn strings(&self) -> Option<Vec<String>> {
+        let value = self.value.strip_suffix(&[0])?;
+        let strings: Vec<&[u8]> = value.split(|&byte| byte == 0).collect();
+        let printable = |string: &&[u8]| {
+            !string.is_empty()
+                && string
+                    .iter()
+                    .all(|&byte| byte.is_ascii_graphic() || byte == b' ' || byte == b'\t')
+        };
+        strings.iter().all(printable).then(|| {
+            strings
+                .iter()
+                .map(|string| String::from_utf8_lossy(string).into_owned())
+                .collect()
+        })
+    }
+
+    /// The value as big-endian u32 cells, if its size is a multiple of 4.
+    pub fn cells(&self) -> Option<Vec<u32>> {
+        (self.value.len().is_multiple_of(4)).then(|| {
+            self.value
+                .chunks_exact(4)
+                .map(|cell| u32::from_be_bytes(cell.try_into().unwrap()))
+                .collect()
+        })
+    }
+
+    pub fn u32(&self) -> Option<u32> {
+        match self.cells()?.as_slice() {
+            [cell] => Some(*cell),
+            _ => None,
+        }
+    }
+
+    /// The value in DTS syntax, guessing its type like `dtc` does when
+    /// decompiling: strings, then u32 cells, then bytes. Empty values
+    /// return `None`, as boolean properties have no value.
+    pub fn format_value(&self) -> Option<String> {
+        if self.value.is_empty() {
+            return None;
+        }
+        if let Some(strings) = self.strings() {
+            let strings: Vec<String> = strings
+                .iter()
+                .map(|string| format!("\"{}\"", escape_string(string)))
+                .collect();
+            return Some(strings.join(", "));
+        }
+        if let Some(cells) = self.cells() {
+            let cells: Vec<String> = cells.iter().map(|cell| format!("0x{:x}", cell)).collect();
+            return Some(format!("<{}>", cells.join(" ")));
+        }
+        let bytes: Vec<String> = self
+            .value
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Some(format!("[{}]", bytes.join(" ")))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FdtNode {
+    /// Name with the unit address (`serial@78b0000`), empty for the root
+    pub name: String,
+    pub properties: Vec<FdtProperty>,
+    pub children: Vec<FdtNode>,
+}
+
+impl FdtNode {
+    pub fn property(&self, name: &str) -> Option<&FdtProperty> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// Replaces the value of a property, or adds it after the others.
+    pub fn set_property(&mut self, name: &str, value: Vec<u8>) {
+        match self
+            .properties
+            .iter_mut()
+            .find(|property| property.name == name)
+        {
+            Some(property) => property.value = value,
+            None => self.properties.push(FdtProperty {
+                name: name.to_string(),
+                value,
+            }),
+        }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&FdtNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut FdtNode> {
+        self.children.iter_mut().find(|child| child.name == name)
+    }
+
+    /// Position of the child called `name`. Like `dtc` does for paths, a
+    /// name without a unit address also matches a child that has one.
+    fn child_index(&self, name: &str) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|child| child.name == name)
+            .or_else(|| {
+                self.children.iter().position(|child| {
+                    !name.contains('@') && child.name.split('@').next() == Some(name)
+                })
+            })
+    }
+
+    /// The node at `path` below this one, `/soc/serial@78b0000` for instance.
+    pub fn node(&self, path: &str) -> Option<&FdtNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.child_index(name).map(|index| &node.children[index])
+            })
+    }
+
+    pub fn node_mut(&mut self, path: &str) -> Option<&mut FdtNode> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| {
+                node.child_index(name)
+                    .map(|index| &mut node.children[index])
+            })
+    }
+
+    /// The node at `path` below this one, adding the missing nodes along
+    /// the way.
+    pub fn create_node(&mut self, path: &str) -> &mut FdtNode {
+        let mut node = self;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let index = match node.child_index(name) {
+                Some(index) => index,
+                None => {
+                    node.children.push(FdtNode {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index];
+        }
+        node
+    }
+
+    /// Removes the child called `name`, matched like in [`FdtNode::node`].
+    pub fn remove_child(&mut self, name: &str) -> Option<FdtNode> {
+        self.child_index(name)
+            .map(|index| self.children.remove(index))
+    }
+
+    /// Number of nodes in this subtree, this one included.
+    pub fn node_count(&self) -> usize {
+        1 + self.children.iter().map(FdtNode::node_count).sum::<usize>()
+    }
+}
+
+/// A flattened device tree: the header, the memory reservation map and the
+/// node tree, with property names resolved from the strings block.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Fdt {
+    pub header: FdtHeader,
+    pub reservations: Vec<FdtReservation>,
+    pub root: FdtNode,
+}
+
+impl Fdt {
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let header = FdtHeader::parse(data).ok_or_else(|| io::Error::other("Not a valid DTB"))?;
+        if header.version < FDT_MIN_VERSION {
+            return Err(io::Error::other(format!(
+                "Unsupported DTB version: {}",
+                header.version
+            )));
+        }
+        let blob = &data[..header.totalsize as usize];
+
+        let mut reservations = Vec::new();
+        let mut offset = header.off_mem_rsvmap as usize;
+        loop {
+            let address = read_u64(blob, offset)?;
+            let size = read_u64(blob, offset + 8)?;
+            if address == 0 && size == 0 {
+                break;
+            }
+            reservations.push(FdtReservation { address, size });
+            offset += 16;
+        }
+
+        let strings_start = header.off_dt_strings as usize;
+        let strings = &blob[strings_start..strings_start + header.size_dt_strings as usize];
+        let struct_start = header.off_dt_struct as usize;
+        let struct_end = if header.version >= 17 {
+            struct_start + header.size_dt_struct as usize
+        } else {
+            blob.len()
+        };
+        let structure = blob
+            .get(struct_start..struct_end)
+            .ok_or_else(|| io::Error::other("DTB structure block is out of bounds"))?;
+        let root = parse_structure(structure, strings)?;
+
+        Ok(Fdt {
+            header,
+            reservations,
+            root,
+        })
+    }
+
+    pub fn model(&self) -> Option<String> {
+        self.root
+            .property("model")
+            .and_then(FdtProperty::strings)
+            .and_then(|strings| strings.into_iter().next())
+    }
+
+    pub fn compatible(&self) -> Vec<String> {
+        self.root
+            .property("compatible")
+            .and_then(FdtProperty::strings)
+            .unwrap_or_default()
+    }
+    /// Serializes the tree as a version 17 blob laid out like `dtc` does:
+    /// the reservation map, the structure block, then the strings block.
+    /// Only the boot CPU is kept from the header.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut structure = Vec::new();
+        let mut strings = Vec::new();
+        write_structure(&self.root, &mut structure, &mut strings);
+        structure.extend_from_slice(&FDT_END.to_be_bytes());
+
+        let mut reservations = Vec::new();
+        for reservation in &self.reservations {
+            reservations.extend_from_slice(&reservation.address.to_be_bytes());
+            reservations.extend_from_slice(&reservation.size.to_be_bytes());
+        }
+        reservations.extend_from_slice(&[0; 16]);
+
+        let off_mem_rsvmap = FDT_HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + reservations.len();
+        let off_dt_strings = off_dt_struct + structure.len();
+        let header = FdtHeader {
+            totalsize: (off_dt_strings + strings.len()) as u32,
+            off_dt_struct: off_dt_struct as u32,
+            off_dt_strings: off_dt_strings as u32,
+            off_mem_rsvmap: off_mem_rsvmap as u32,
+            version: 17,
+            last_comp_version: FDT_MIN_VERSION,
+            boot_cpuid_phys: self.header.boot_cpuid_phys,
+            size_dt_strings: strings.len() as u32,
+            size_dt_struct: structure.len() as u32,
+        };
+
+        let mut writer = Cursor::new(Vec::with_capacity(header.totalsize as usize));
+        header.write(&mut writer).map_err(io::Error::other)?;
+        let mut data = writer.into_inner();
+        data.extend_from_slice(&reservations);
+        data.extend_from_slice(&structure);
+        data.extend_from_slice(&strings);
+        Ok(data)
+    }
+}
+
+/// Writes a node and its subtree to the structure block, adding property
+/// names to the strings block.
+fn write_structure(node: &FdtNode, structure: &mut Vec<u8>, strings: &mut Vec<u8>) {
+    structure.extend_from_slice(&FDT_BEGIN_NODE.to_be_bytes());
+    structure.extend_from_slice(node.name.as_bytes());
+    structure.push(0);
+    structure.resize(align4(structure.len()), 0);
+    for property in &node.properties {
+        structure.extend_from_slice(&FDT_PROP.to_be_bytes());
+        structure.extend_from_slice(&(property.value.len() as u32).to_be_bytes());
+        structure.extend_from_slice(&(add_string(strings, &property.name) as u32).to_be_bytes());
+        structure.extend_from_slice(&property.value);
+        structure.resize(align4(structure.len()), 0);
+    }
+    for child in &node.children {
+        write_structure(child, structure, strings);
+    }
+    structure.extend_from_slice(&FDT_END_NODE.to_be_bytes());
+}
+
+/// Returns the offset of `name` in the strings block, adding it if it is
+/// not already there. Like `dtc`, the tail of a longer name is reused.
+fn add_string(strings: &mut Vec<u8>, name: &str) -> usize {
+    let mut needle = name.as_bytes().to_vec();
+    needle.push(0);
+    if let Some(offset) = strings
+        .windows(needle.len())
+        .position(|window| window == needle)
+    {
+        return offset;
+    }
+    let offset = strings.len();
+    strings.extend_from_slice(&needle);
+    offset
+}
+
+/// Reads the structure block into the root node.
+fn parse_structure(structure: &[u8], strings: &[u8]) -> io::Result<FdtNode> {
+    let mut stack: Vec<FdtNode> = Vec::new();
+    let mut root = None;
+    let mut offset = 0;
+
+    loop {
+        let token = read_u32(structure, offset)?;
+        offset += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = read_c_string(structure, offset)?;
+                offset = align4(offset + name.len() + 1);
+                if root.is_some() {
+                    return Err(io::Error::other("DTB has more than one root node"));
+                }
+                stack.push(FdtNode {
+                    name,
+                    ..Default::default()
+                });
+            }
+            FDT_END_NODE => {
+                let node = stack
+                    .pop()
+                    .ok_or_else(|| io::Error::other("Unbalanced DTB node end"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => root = Some(node),
+                }
+            }
+            FDT_PROP => {
+                let length = read_u32(structure, offset)? as usize;
+                let name_offset = read_u32(structure, offset + 4)? as usize;
+                offset += 8;
+                let value = structure
+                    .get(offset..offset + length)
+                    .ok_or_else(|| io::Error::other("DTB property is out of bounds"))?
+                    .to_vec();
+                offset = align4(offset + length);
+                let name = read_c_string(strings, name_offset)?;
+                stack
+                    .last_mut()
+                    .ok_or_else(|| io::Error::other("DTB property outside of a node"))?
+                    .properties
+                    .push(FdtProperty { name, value });
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            token => {
+                return Err(io::Error::other(format!(
+                    "Unknown DTB token 0x{:x} at offset {}",
+                    token,
+                    offset - 4
+                )))
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err(io::Error::other("DTB ends inside a node"));
+    }
+    root.ok_or_else(|| io::Error::other("DTB has no root node"))
+}
+
+fn align4(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::other("DTB is truncated"))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> io::Result<u64> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::other("DTB is truncated"))
+}
+
+fn read_c_string(data: &[u8], offset: usize) -> io::Result<String> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| io::Error::other("DTB string is out of bounds"))?;
+    let length = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| io::Error::other("DTB string is not terminated"))?;
+    Ok(String::from_utf8_lossy(&bytes[..length]).into_owned())
+}
+
+fn escape_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for character in string.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
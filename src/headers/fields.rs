@@ -23,6 +23,27 @@ impl Default for AndroidBootMagic {
     }
 }
 
+#[derive(Debug, BinRead, BinWrite, Clone)]
+pub struct VendorBootMagic(#[br(count = 8)] pub Vec<u8>);
+
+impl fmt::Display for VendorBootMagic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ascii_str: String = self
+            .0
+            .iter()
+            .filter(|&&c| c.is_ascii())
+            .map(|&c| c as char)
+            .collect();
+        write!(f, "{}", ascii_str)
+    }
+}
+
+impl Default for VendorBootMagic {
+    fn default() -> Self {
+        VendorBootMagic(b"VNDRBOOT".to_vec())
+    }
+}
+
 #[derive(Debug, Default, BinRead, BinWrite, Clone)]
 pub struct Name(#[br(count = 16)] pub Vec<u8>);
 
@@ -121,7 +142,58 @@ impl From<String> for CmdlineExtended {
 }
 
 #[derive(Debug, Default, BinRead, BinWrite, Clone)]
-pub struct Id(#[br(count = 8)] pub Vec<u8>);
+pub struct VendorCmdline(#[br(count = 2048)] pub Vec<u8>);
+
+impl fmt::Display for VendorCmdline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ascii_str: String = self
+            .0
+            .iter()
+            .filter(|&&c| c.is_ascii())
+            .map(|&c| c as char)
+            .collect();
+        write!(f, "{}", ascii_str)
+    }
+}
+
+impl From<String> for VendorCmdline {
+    fn from(s: String) -> Self {
+        let mut vec = Vec::with_capacity(2048);
+        vec.extend(s.as_bytes().iter().take(2048));
+        // Pad with zeros if string is shorter than 2048 bytes
+        vec.resize(2048, 0);
+        VendorCmdline(vec)
+    }
+}
+
+#[derive(Debug, Default, BinRead, BinWrite, Clone)]
+pub struct VendorRamdiskName(#[br(count = 32)] pub Vec<u8>);
+
+impl fmt::Display for VendorRamdiskName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ascii_str: String = self
+            .0
+            .iter()
+            .take_while(|&&c| c != 0)
+            .filter(|&&c| c.is_ascii())
+            .map(|&c| c as char)
+            .collect();
+        write!(f, "{}", ascii_str)
+    }
+}
+
+impl From<String> for VendorRamdiskName {
+    fn from(s: String) -> Self {
+        let mut vec = Vec::with_capacity(32);
+        vec.extend(s.as_bytes().iter().take(32));
+        // Pad with zeros if string is shorter than 32 bytes
+        vec.resize(32, 0);
+        VendorRamdiskName(vec)
+    }
+}
+
+#[derive(Debug, Default, BinRead, BinWrite, Clone)]
+pub struct Id(#[br(count = 32)] pub Vec<u8>);
 
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -135,12 +207,19 @@ impl fmt::Display for Id {
     }
 }
 
+impl Id {
+    /// The raw bytes as a hex string, as digests are rarely printable.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
 impl From<String> for Id {
     fn from(s: String) -> Self {
-        let mut vec = Vec::with_capacity(8);
-        vec.extend(s.as_bytes().iter().take(8));
-        // Pad with zeros if string is shorter than 8 bytes
-        vec.resize(8, 0);
+        let mut vec = Vec::with_capacity(32);
+        vec.extend(s.as_bytes().iter().take(32));
+        // Pad with zeros if string is shorter than 32 bytes
+        vec.resize(32, 0);
         Id(vec)
     }
 }
@@ -158,9 +237,9 @@ impl fmt::Display for AddressU32 {
 
 impl From<String> for AddressU32 {
     fn from(s: String) -> Self {
-        let addr = if s.starts_with("0x") {
+        let addr = if let Some(hex) = s.strip_prefix("0x") {
             // Parse hexadecimal string
-            u32::from_str_radix(&s[2..], 16).unwrap_or_default()
+            u32::from_str_radix(hex, 16).unwrap_or_default()
         } else {
             // Try parsing as decimal
             s.parse::<u32>().unwrap_or_default()
@@ -187,9 +266,9 @@ impl fmt::Display for AddressU64 {
 }
 impl From<String> for AddressU64 {
     fn from(s: String) -> Self {
-        let addr = if s.starts_with("0x") {
+        let addr = if let Some(hex) = s.strip_prefix("0x") {
             // Parse hexadecimal string
-            u64::from_str_radix(&s[2..], 16).unwrap_or_default()
+            u64::from_str_radix(hex, 16).unwrap_or_default()
         } else {
             // Try parsing as decimal
             s.parse::<u64>().unwrap_or_default()
@@ -202,23 +281,23 @@ impl From<String> for AddressU64 {
 pub struct OSVersion(pub u32);
 
 impl OSVersion {
-    fn major(&self) -> u32 {
+    pub fn major(&self) -> u32 {
         self.0 >> 25
     }
 
-    fn minor(&self) -> u32 {
+    pub fn minor(&self) -> u32 {
         (self.0 >> 18) & 0x7F
     }
 
-    fn patch(&self) -> u32 {
+    pub fn patch(&self) -> u32 {
         (self.0 >> 11) & 0x7F
     }
 
-    fn year(&self) -> u32 {
-        ((self.0 >> 4) & 0xF) + 2000
+    pub fn year(&self) -> u32 {
+        ((self.0 >> 4) & 0x7F) + 2000
     }
 
-    fn month(&self) -> u32 {
+    pub fn month(&self) -> u32 {
         self.0 & 0xF
     }
 }
@@ -240,7 +319,10 @@ impl fmt::Display for OSVersion {
 impl From<String> for OSVersion {
     fn from(s: String) -> Self {
         // Expected format: "major.minor.patch (yyyy-mm)"
-        let parts: Vec<&str> = s.split(&['.', ' ', '(', ')', '-'][..]).collect();
+        let parts: Vec<&str> = s
+            .split(&['.', ' ', '(', ')', '-'][..])
+            .filter(|part| !part.is_empty())
+            .collect();
         if parts.len() >= 5 {
             let major = parts[0].parse::<u32>().unwrap_or_default();
             let minor = parts[1].parse::<u32>().unwrap_or_default();
@@ -252,7 +334,7 @@ impl From<String> for OSVersion {
             let version = (major << 25)
                 | ((minor & 0x7F) << 18)
                 | ((patch & 0x7F) << 11)
-                | (((year.saturating_sub(2000)) & 0xF) << 4)
+                | (((year.saturating_sub(2000)) & 0x7F) << 4)
                 | (month & 0xF);
 
             OSVersion(version)
@@ -0,0 +1,64 @@
+use std::fmt;
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::android::BootComponent;
+
+/// Size of the `id` field of boot image headers v0 to v2.
+pub const ID_SIZE: usize = 32;
+
+/// Digest mkbootimg stores in the `id` field of boot image headers v0 to v2.
+///
+/// It covers every section the header version has, each one followed by its
+/// size as a little-endian u32, and is zero padded to 32 bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IdHash {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl IdHash {
+    pub const ALL: [IdHash; 2] = [IdHash::Sha1, IdHash::Sha256];
+
+    /// Name used in metadata files and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdHash::Sha1 => "sha1",
+            IdHash::Sha256 => "sha256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IdHash> {
+        IdHash::ALL.into_iter().find(|hash| hash.name() == name)
+    }
+
+    /// Computes the id over `components`, which must be given in on-disk order.
+    pub fn compute(&self, components: &[(BootComponent, Vec<u8>)]) -> Vec<u8> {
+        let mut id = match self {
+            IdHash::Sha1 => Self::digest::<Sha1>(components),
+            IdHash::Sha256 => Self::digest::<Sha256>(components),
+        };
+        id.resize(ID_SIZE, 0);
+        id
+    }
+
+    fn digest<D: Digest>(components: &[(BootComponent, Vec<u8>)]) -> Vec<u8> {
+        let mut hasher = D::new();
+        for (_, data) in components {
+            hasher.update(data);
+            hasher.update((data.len() as u32).to_le_bytes());
+        }
+        hasher.finalize().to_vec()
+    }
+}
+
+impl fmt::Display for IdHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdHash::Sha1 => write!(f, "SHA-1"),
+            IdHash::Sha256 => write!(f, "SHA-256"),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+use super::fdt::{split_fdts, FDT_MAGIC};
+
+/// Magic of the arm64 `Image` header, at offset 0x38.
+const ARM64_IMAGE_MAGIC: &[u8; 4] = b"ARM\x64";
+/// Magic of the arm `zImage` header, little-endian at offset 0x24.
+const ARM_ZIMAGE_MAGIC: u32 = 0x016f2818;
+
+/// Compression or image format of a kernel, detected from its magic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelFormat {
+    Gzip,
+    Lz4,
+    Lz4Legacy,
+    Xz,
+    Lzma,
+    Bzip2,
+    Zstd,
+    /// Uncompressed arm64 `Image`
+    Arm64Image,
+    /// Self-decompressing arm `zImage`
+    ArmZImage,
+    Unknown,
+}
+
+impl KernelFormat {
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            KernelFormat::Gzip
+        } else if data.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            KernelFormat::Lz4
+        } else if data.starts_with(&[0x02, 0x21, 0x4c, 0x18]) {
+            KernelFormat::Lz4Legacy
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            KernelFormat::Xz
+        } else if data.starts_with(&[0x5d, 0x00, 0x00]) {
+            KernelFormat::Lzma
+        } else if data.starts_with(b"BZh") {
+            KernelFormat::Bzip2
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            KernelFormat::Zstd
+        } else if data.get(0x38..0x3c) == Some(ARM64_IMAGE_MAGIC) {
+            KernelFormat::Arm64Image
+        } else if data.get(0x24..0x28) == Some(&ARM_ZIMAGE_MAGIC.to_le_bytes()) {
+            KernelFormat::ArmZImage
+        } else {
+            KernelFormat::Unknown
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KernelFormat::Gzip => "gzip",
+            KernelFormat::Lz4 => "lz4",
+            KernelFormat::Lz4Legacy => "lz4 (legacy)",
+            KernelFormat::Xz => "xz",
+            KernelFormat::Lzma => "lzma",
+            KernelFormat::Bzip2 => "bzip2",
+            KernelFormat::Zstd => "zstd",
+            KernelFormat::Arm64Image => "arm64 Image",
+            KernelFormat::ArmZImage => "arm zImage",
+            KernelFormat::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for KernelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Splits a kernel built with appended DTBs (`Image.gz-dtb`, `zImage-dtb`)
+/// into the kernel itself and the DTBs.
+///
+/// The DTBs start at the first FDT magic from which the rest of the data is
+/// exactly a sequence of blobs, so a magic that happens to show up in the
+/// compressed kernel is not taken for one.
+pub fn split_appended_dtbs(data: &[u8]) -> (&[u8], Vec<&[u8]>) {
+    let magic = FDT_MAGIC.to_be_bytes();
+    let candidates = data
+        .windows(magic.len())
+        .enumerate()
+        .filter(|(_, window)| *window == magic)
+        .map(|(offset, _)| offset);
+    for offset in candidates {
+        if let Some(dtbs) = split_fdts(&data[offset..]) {
+            return (&data[..offset], dtbs);
+        }
+    }
+    (data, Vec::new())
+}
+
+/// Appends DTBs to a kernel, like the `Image.gz-dtb` build target does.
+pub fn append_dtbs(kernel: &[u8], dtbs: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = kernel.to_vec();
+    for dtb in dtbs {
+        data.extend_from_slice(dtb);
+    }
+    data
+}
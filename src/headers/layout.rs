@@ -0,0 +1,83 @@
+use super::android::{AndroidHeader, BootComponent};
+
+/// Page sizes mkbootimg accepts, for boot and vendor boot images alike,
+/// smallest first. Device tree tables are aligned to the same sizes.
+pub const PAGE_SIZES: [u32; 4] = [2048, 4096, 8192, 16384];
+
+/// Rounds `size` up to the next multiple of `alignment`.
+pub fn align(size: u64, alignment: u32) -> u64 {
+    size.div_ceil(alignment as u64) * alignment as u64
+}
+
+/// Number of zero bytes needed after `size` bytes to reach `alignment`.
+pub fn padding(size: u64, alignment: u32) -> u64 {
+    align(size, alignment) - size
+}
+
+/// Where a single section lives inside a boot image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionLayout {
+    pub component: BootComponent,
+    pub offset: u64,
+    pub size: u32,
+    pub padding: u64,
+}
+
+/// Layout of a boot image as built by mkbootimg and expected by bootloaders
+/// and `unpack_bootimg`.
+///
+/// The header is padded to a full page, then every section the header version
+/// supports follows in order (kernel, ramdisk, second, recovery_dtbo, dtb for
+/// v0 to v2, with the QCOM v0 variant going straight from second to dtb;
+/// kernel, ramdisk and boot signature for v3 and v4), each one padded to the
+/// page size. Empty sections take no space.
+#[derive(Debug, Clone)]
+pub struct BootImageLayout {
+    pub page_size: u32,
+    pub header_size: u32,
+    pub header_padding: u64,
+    pub sections: Vec<SectionLayout>,
+}
+
+impl BootImageLayout {
+    pub fn new(header: &AndroidHeader) -> Self {
+        let page_size = header.page_size();
+        let header_size = header.header_size();
+
+        let mut offset = align(header_size as u64, page_size);
+        let mut sections = Vec::new();
+        for &component in header.supported_components() {
+            let size = header.component_size(component).unwrap_or(0);
+            let alignment = header.component_alignment(component);
+            let section = SectionLayout {
+                component,
+                offset,
+                size,
+                padding: padding(size as u64, alignment),
+            };
+            offset += size as u64 + section.padding;
+            sections.push(section);
+        }
+
+        BootImageLayout {
+            page_size,
+            header_size,
+            header_padding: padding(header_size as u64, page_size),
+            sections,
+        }
+    }
+
+    pub fn section(&self, component: BootComponent) -> Option<&SectionLayout> {
+        self.sections
+            .iter()
+            .find(|section| section.component == component)
+    }
+
+    /// Size of the image up to the end of the last padded section.
+    pub fn image_size(&self) -> u64 {
+        match self.sections.last() {
+            Some(section) => section.offset + section.size as u64 + section.padding,
+            None => self.header_size as u64 + self.header_padding,
+        }
+    }
+}
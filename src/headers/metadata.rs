@@ -0,0 +1,349 @@
+use std::fmt;
+
+use super::android::{
+    AndroidHeader, AndroidHeaderVersion0, AndroidHeaderVersion0Qcom, AndroidHeaderVersion1,
+    AndroidHeaderVersion2, AndroidHeaderVersion3, AndroidHeaderVersion4,
+};
+use super::fields::{Cmdline, CmdlineExtended, ExtraCmdline, Id, Name, OSVersion};
+use super::layout::PAGE_SIZES;
+
+/// File name of the header metadata written next to the extracted components.
+pub const METADATA_FILE_NAME: &str = "bootimg.cfg";
+
+/// Header fields of a boot image that are not part of any section, stored as
+/// ordered `key=value` pairs so an extracted image fully describes the original.
+///
+/// Text fields (name, cmdline, id) keep their raw bytes: trailing NULs are
+/// dropped, backslashes are doubled and any non-printable byte is written as
+/// `\xNN`.
+#[derive(Debug, Default, Clone)]
+pub struct BootImageMetadata {
+    pub fields: Vec<(String, String)>,
+}
+
+impl BootImageMetadata {
+    pub fn from_header(header: &AndroidHeader) -> Self {
+        let mut metadata = BootImageMetadata::default();
+        metadata.set("header_version", header.version().to_string());
+
+        match header {
+            AndroidHeader::V0(header) => {
+                // Old v0 images may hold anything in place of header_version
+                if header.header_version != 0 {
+                    metadata.set(
+                        "header_version_raw",
+                        format!("0x{:08x}", header.header_version),
+                    );
+                }
+                metadata.set("page_size", header.page_size.to_string());
+                metadata.set("kernel_addr", header.kernel_addr.to_string());
+                metadata.set("ramdisk_addr", header.ramdisk_addr.to_string());
+                metadata.set("second_addr", header.second_addr.to_string());
+                metadata.set("tags_addr", header.tags_addr.to_string());
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("name", escape_bytes(&header.name.0));
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("extra_cmdline", escape_bytes(&header.extra_cmdline.0));
+                metadata.set("id", escape_bytes(&header.id.0));
+            }
+            AndroidHeader::V0Qcom(header) => {
+                metadata.set("header_variant", "qcom".to_string());
+                metadata.set("page_size", header.page_size.to_string());
+                metadata.set("kernel_addr", header.kernel_addr.to_string());
+                metadata.set("ramdisk_addr", header.ramdisk_addr.to_string());
+                metadata.set("second_addr", header.second_addr.to_string());
+                metadata.set("tags_addr", header.tags_addr.to_string());
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("name", escape_bytes(&header.name.0));
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("extra_cmdline", escape_bytes(&header.extra_cmdline.0));
+                metadata.set("id", escape_bytes(&header.id.0));
+            }
+            AndroidHeader::V1(header) => {
+                metadata.set("page_size", header.page_size.to_string());
+                metadata.set("kernel_addr", header.kernel_addr.to_string());
+                metadata.set("ramdisk_addr", header.ramdisk_addr.to_string());
+                metadata.set("second_addr", header.second_addr.to_string());
+                metadata.set("tags_addr", header.tags_addr.to_string());
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("name", escape_bytes(&header.name.0));
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("extra_cmdline", escape_bytes(&header.extra_cmdline.0));
+                metadata.set("id", escape_bytes(&header.id.0));
+                metadata.set(
+                    "recovery_dtbo_offset",
+                    header.recovery_dtbo_offset.to_string(),
+                );
+            }
+            AndroidHeader::V2(header) => {
+                metadata.set("page_size", header.page_size.to_string());
+                metadata.set("kernel_addr", header.kernel_addr.to_string());
+                metadata.set("ramdisk_addr", header.ramdisk_addr.to_string());
+                metadata.set("second_addr", header.second_addr.to_string());
+                metadata.set("tags_addr", header.tags_addr.to_string());
+                metadata.set("dtb_addr", header.dtb_addr.to_string());
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("name", escape_bytes(&header.name.0));
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("extra_cmdline", escape_bytes(&header.extra_cmdline.0));
+                metadata.set("id", escape_bytes(&header.id.0));
+                metadata.set(
+                    "recovery_dtbo_offset",
+                    header.recovery_dtbo_offset.to_string(),
+                );
+            }
+            AndroidHeader::V3(header) => {
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("reserved", format_reserved(&header.reserved));
+            }
+            AndroidHeader::V4(header) => {
+                metadata.set("os_version", header.os_version.to_string());
+                metadata.set("cmdline", escape_bytes(&header.cmdline.0));
+                metadata.set("reserved", format_reserved(&header.reserved));
+            }
+        }
+
+        metadata
+    }
+
+    /// Parses a metadata file. Blank lines and `#` comments are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut metadata = BootImageMetadata::default();
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => metadata.set(key.trim(), value.to_string()),
+                None => return Err(format!("Invalid metadata line: {}", line)),
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Builds a header from the metadata, with every component size left at zero.
+    pub fn to_header(&self) -> Result<AndroidHeader, String> {
+        let header_version = self.get_u32("header_version")?.unwrap_or(0);
+        let qcom = self.get_string("header_variant") == "qcom";
+        let mut header = match header_version {
+            0 if qcom => AndroidHeader::V0Qcom(AndroidHeaderVersion0Qcom {
+                page_size: self.get_page_size()?,
+                kernel_addr: self.get_string("kernel_addr").into(),
+                ramdisk_addr: self.get_string("ramdisk_addr").into(),
+                second_addr: self.get_string("second_addr").into(),
+                tags_addr: self.get_string("tags_addr").into(),
+                os_version: self.get_os_version(),
+                name: Name(self.get_bytes("name", 16)?),
+                cmdline: Cmdline(self.get_bytes("cmdline", 512)?),
+                id: Id(self.get_bytes("id", 32)?),
+                extra_cmdline: ExtraCmdline(self.get_bytes("extra_cmdline", 1024)?),
+                ..Default::default()
+            }),
+            0 => AndroidHeader::V0(AndroidHeaderVersion0 {
+                header_version: self.get_u32("header_version_raw")?.unwrap_or(0),
+                page_size: self.get_page_size()?,
+                kernel_addr: self.get_string("kernel_addr").into(),
+                ramdisk_addr: self.get_string("ramdisk_addr").into(),
+                second_addr: self.get_string("second_addr").into(),
+                tags_addr: self.get_string("tags_addr").into(),
+                os_version: self.get_os_version(),
+                name: Name(self.get_bytes("name", 16)?),
+                cmdline: Cmdline(self.get_bytes("cmdline", 512)?),
+                id: Id(self.get_bytes("id", 32)?),
+                extra_cmdline: ExtraCmdline(self.get_bytes("extra_cmdline", 1024)?),
+                ..Default::default()
+            }),
+            1 => AndroidHeader::V1(AndroidHeaderVersion1 {
+                page_size: self.get_page_size()?,
+                header_version,
+                kernel_addr: self.get_string("kernel_addr").into(),
+                ramdisk_addr: self.get_string("ramdisk_addr").into(),
+                second_addr: self.get_string("second_addr").into(),
+                tags_addr: self.get_string("tags_addr").into(),
+                os_version: self.get_os_version(),
+                name: Name(self.get_bytes("name", 16)?),
+                cmdline: Cmdline(self.get_bytes("cmdline", 512)?),
+                id: Id(self.get_bytes("id", 32)?),
+                extra_cmdline: ExtraCmdline(self.get_bytes("extra_cmdline", 1024)?),
+                recovery_dtbo_offset: self.get_string("recovery_dtbo_offset").into(),
+                ..Default::default()
+            }),
+            2 => AndroidHeader::V2(AndroidHeaderVersion2 {
+                page_size: self.get_page_size()?,
+                header_version,
+                kernel_addr: self.get_string("kernel_addr").into(),
+                ramdisk_addr: self.get_string("ramdisk_addr").into(),
+                second_addr: self.get_string("second_addr").into(),
+                tags_addr: self.get_string("tags_addr").into(),
+                dtb_addr: self.get_string("dtb_addr").into(),
+                os_version: self.get_os_version(),
+                name: Name(self.get_bytes("name", 16)?),
+                cmdline: Cmdline(self.get_bytes("cmdline", 512)?),
+                id: Id(self.get_bytes("id", 32)?),
+                extra_cmdline: ExtraCmdline(self.get_bytes("extra_cmdline", 1024)?),
+                recovery_dtbo_offset: self.get_string("recovery_dtbo_offset").into(),
+                ..Default::default()
+            }),
+            3 => AndroidHeader::V3(AndroidHeaderVersion3 {
+                header_version,
+                os_version: self.get_os_version(),
+                cmdline: CmdlineExtended(self.get_bytes("cmdline", 1536)?),
+                reserved: self.get_reserved()?,
+                ..Default::default()
+            }),
+            4 => AndroidHeader::V4(AndroidHeaderVersion4 {
+                header_version,
+                os_version: self.get_os_version(),
+                cmdline: CmdlineExtended(self.get_bytes("cmdline", 1536)?),
+                reserved: self.get_reserved()?,
+                ..Default::default()
+            }),
+            _ => return Err(format!("Unsupported header version: {}", header_version)),
+        };
+
+        header.update_header_size();
+        Ok(header)
+    }
+
+    fn get_string(&self, key: &str) -> String {
+        self.get(key).unwrap_or_default().trim().to_string()
+    }
+
+    fn get_u32(&self, key: &str) -> Result<Option<u32>, String> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        let value = value.trim();
+        let parsed = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse::<u32>(),
+        };
+        parsed
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", key, value))
+    }
+
+    fn get_page_size(&self) -> Result<u32, String> {
+        match self.get_u32("page_size")?.unwrap_or(2048) {
+            page_size if PAGE_SIZES.contains(&page_size) => Ok(page_size),
+            page_size => Err(format!("Invalid page size: {}", page_size)),
+        }
+    }
+
+    fn get_os_version(&self) -> OSVersion {
+        OSVersion::from(self.get_string("os_version"))
+    }
+
+    /// Decodes a text field and pads it with NULs to the size of its header field.
+    fn get_bytes(&self, key: &str, size: usize) -> Result<Vec<u8>, String> {
+        let mut bytes = unescape_bytes(self.get(key).unwrap_or_default())?;
+        if bytes.len() > size {
+            return Err(format!("{} is longer than {} bytes", key, size));
+        }
+        bytes.resize(size, 0);
+        Ok(bytes)
+    }
+
+    fn get_reserved(&self) -> Result<[u32; 4], String> {
+        let mut reserved = [0u32; 4];
+        let value = self.get_string("reserved");
+        if value.is_empty() {
+            return Ok(reserved);
+        }
+        let values: Vec<&str> = value.split(',').map(str::trim).collect();
+        if values.len() != reserved.len() {
+            return Err(format!("Invalid value for reserved: {}", value));
+        }
+        for (slot, value) in reserved.iter_mut().zip(values) {
+            let parsed = match value.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => value.parse::<u32>(),
+            };
+            *slot = parsed.map_err(|_| format!("Invalid value for reserved: {}", value))?;
+        }
+        Ok(reserved)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field_key, _)| field_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a field, replacing its value if the key already exists.
+    pub fn set(&mut self, key: &str, value: String) {
+        match self
+            .fields
+            .iter_mut()
+            .find(|(field_key, _)| field_key == key)
+        {
+            Some((_, field_value)) => *field_value = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+    }
+}
+
+impl fmt::Display for BootImageMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.fields {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a fixed-size header field as text, without its trailing NUL
+/// padding, escaping anything that would not survive a text file.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |pos| pos + 1);
+    let mut escaped = String::with_capacity(end);
+    for &byte in &bytes[..end] {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7E => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape_bytes`].
+pub fn unescape_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match input.next() {
+            Some(b'\\') => bytes.push(b'\\'),
+            Some(b'x') => {
+                let digits = [input.next(), input.next()];
+                let escaped = match digits {
+                    [Some(high), Some(low)] => {
+                        u8::from_str_radix(&String::from_utf8_lossy(&[high, low]), 16).ok()
+                    }
+                    _ => None,
+                };
+                match escaped {
+                    Some(escaped) => bytes.push(escaped),
+                    None => return Err(format!("Invalid escape sequence in: {}", text)),
+                }
+            }
+            _ => return Err(format!("Invalid escape sequence in: {}", text)),
+        }
+    }
+    Ok(bytes)
+}
+
+fn format_reserved(reserved: &[u32; 4]) -> String {
+    reserved
+        .iter()
+        .map(|value| format!("0x{:08x}", value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
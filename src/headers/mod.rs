@@ -1,2 +1,16 @@
 pub mod android;
+pub mod avb;
+pub mod bootconfig;
+pub mod dtbo;
+pub mod dts;
+pub mod fdt;
 pub mod fields;
+pub mod id;
+pub mod kernel;
+pub mod layout;
+pub mod metadata;
+pub mod mtk;
+pub mod qcdt;
+pub mod trailer;
+pub mod vboot;
+pub mod vendor;
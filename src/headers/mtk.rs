@@ -0,0 +1,91 @@
+use std::io::{self, Cursor};
+
+use binrw::{BinRead, BinWrite};
+
+use super::android::BootComponent;
+
+pub const MTK_HEADER_MAGIC: u32 = 0x58881688;
+pub const MTK_HEADER_SIZE: usize = 512;
+
+/// Suffix of the files `extract` writes stripped headers to, after the
+/// component name.
+pub const MTK_HEADER_FILE_SUFFIX: &str = "_mtk_header";
+
+/// Kind of image MediaTek headers are written for, which names the ramdisk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MtkImageKind {
+    #[default]
+    Boot,
+    Recovery,
+}
+
+/// Header MediaTek bootloaders expect in front of the kernel and ramdisk of
+/// a boot image: magic, payload size and name, then `0xFF` fill. Newer
+/// bootloaders store extra fields in the fill, which are kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq, BinRead, BinWrite)]
+#[brw(little, magic = 0x58881688u32)]
+pub struct MtkHeader {
+    pub size: u32,
+    pub name: [u8; 32],
+    pub reserved: [u8; 472],
+}
+
+impl MtkHeader {
+    pub fn new(name: &str) -> Self {
+        let length = name.len().min(32);
+        let mut name_field = [0u8; 32];
+        name_field[..length].copy_from_slice(&name.as_bytes()[..length]);
+        MtkHeader {
+            size: 0,
+            name: name_field,
+            reserved: [0xff; 472],
+        }
+    }
+
+    /// Name MediaTek images use for a component, or `None` if it never has
+    /// a header. Recovery images name their ramdisk `RECOVERY` instead of
+    /// `ROOTFS`.
+    pub fn default_name(component: BootComponent, image: MtkImageKind) -> Option<&'static str> {
+        match (component, image) {
+            (BootComponent::Kernel, _) => Some("KERNEL"),
+            (BootComponent::Ramdisk, MtkImageKind::Boot) => Some("ROOTFS"),
+            (BootComponent::Ramdisk, MtkImageKind::Recovery) => Some("RECOVERY"),
+            _ => None,
+        }
+    }
+
+    /// Splits a component into its header and payload, or returns `None`
+    /// if it does not start with one.
+    pub fn split(data: &[u8]) -> Option<(Self, &[u8])> {
+        let header = MtkHeader::read(&mut Cursor::new(data.get(..MTK_HEADER_SIZE)?)).ok()?;
+        Some((header, &data[MTK_HEADER_SIZE..]))
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.name)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    /// Prepends this header to `payload`, with the size set to match it.
+    pub fn wrap(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let header = MtkHeader {
+            size: payload.len() as u32,
+            ..self.clone()
+        };
+        let mut writer = Cursor::new(Vec::with_capacity(MTK_HEADER_SIZE + payload.len()));
+        header.write(&mut writer).map_err(io::Error::other)?;
+        let mut data = writer.into_inner();
+        data.extend_from_slice(payload);
+        Ok(data)
+    }
+
+    /// Wraps a component with a header, reusing the one it already has so
+    /// only the size is refreshed.
+    pub fn rewrap(data: &[u8], name: &str) -> io::Result<Vec<u8>> {
+        match Self::split(data) {
+            Some((header, payload)) => header.wrap(payload),
+            None => Self::new(name).wrap(data),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+use std::io;
+
+use super::fdt::FdtHeader;
+use super::layout::{align, PAGE_SIZES};
+
+pub const QCDT_MAGIC: &[u8; 4] = b"QCDT";
+pub const DTBH_MAGIC: &[u8; 4] = b"DTBH";
+
+/// File name of the table description written next to the extracted DTBs.
+pub const DT_TABLE_FILE_NAME: &str = "dt_table.cfg";
+
+/// Device tree tables bootloaders pick a DTB from by matching the ids of each
+/// entry against the board: `QCDT` on Qualcomm SoCs (built by `dtbTool`) and
+/// `DTBH` on Samsung Exynos SoCs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtTableFormat {
+    Qcdt,
+    Dtbh,
+}
+
+impl DtTableFormat {
+    pub const ALL: [DtTableFormat; 2] = [DtTableFormat::Qcdt, DtTableFormat::Dtbh];
+
+    /// Name used in table descriptions and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            DtTableFormat::Qcdt => "qcdt",
+            DtTableFormat::Dtbh => "dtbh",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DtTableFormat> {
+        DtTableFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+
+    pub fn magic(&self) -> &'static [u8; 4] {
+        match self {
+            DtTableFormat::Qcdt => QCDT_MAGIC,
+            DtTableFormat::Dtbh => DTBH_MAGIC,
+        }
+    }
+
+    /// Table versions that can be read and built.
+    pub fn versions(&self) -> &'static [u32] {
+        match self {
+            DtTableFormat::Qcdt => &[1, 2, 3],
+            DtTableFormat::Dtbh => &[2],
+        }
+    }
+
+    /// Names of the id words of an entry, in on-disk order.
+    pub fn id_names(&self, version: u32) -> &'static [&'static str] {
+        match (self, version) {
+            (DtTableFormat::Qcdt, 1) => &["platform_id", "variant_id", "soc_rev"],
+            (DtTableFormat::Qcdt, 2) => &["platform_id", "variant_id", "subtype_id", "soc_rev"],
+            (DtTableFormat::Qcdt, _) => &[
+                "platform_id",
+                "variant_id",
+                "subtype_id",
+                "soc_rev",
+                "pmic_id0",
+                "pmic_id1",
+                "pmic_id2",
+                "pmic_id3",
+            ],
+            (DtTableFormat::Dtbh, _) => &[
+                "chip_id",
+                "platform_id",
+                "subtype_id",
+                "hw_rev",
+                "hw_rev_end",
+            ],
+        }
+    }
+
+    /// Size of an entry: the ids, the offset and size of the DTB and, for
+    /// `DTBH`, the space the DTB takes once padded.
+    fn entry_size(&self, version: u32) -> usize {
+        let words = self.id_names(version).len() + 2;
+        match self {
+            DtTableFormat::Qcdt => words * 4,
+            DtTableFormat::Dtbh => (words + 1) * 4,
+        }
+    }
+
+    /// Size of the table up to its end marker, before padding.
+    fn table_size(&self, version: u32, entries: usize) -> usize {
+        12 + entries * self.entry_size(version) + 4
+    }
+}
+
+impl fmt::Display for DtTableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DtTableFormat::Qcdt => write!(f, "QCDT"),
+            DtTableFormat::Dtbh => write!(f, "DTBH"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtTableEntry {
+    /// Id words, named by [`DtTableFormat::id_names`]
+    pub id: Vec<u32>,
+    /// Offset of the DTB from the start of the table
+    pub offset: u32,
+    /// Size of the DTB; `dtbTool` stores it padded to the page size
+    pub size: u32,
+}
+
+/// A `QCDT` or `DTBH` table: a header (magic, version, entry count), the
+/// entries and a zero end marker, padded to the page size and followed by
+/// the DTBs, each one padded to the page size. Entries with identical DTBs
+/// share them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtTable {
+    pub format: DtTableFormat,
+    pub version: u32,
+    pub entries: Vec<DtTableEntry>,
+}
+
+impl DtTable {
+    /// Whether `data` starts with a `QCDT` or `DTBH` table.
+    pub fn is_dt_table(data: &[u8]) -> bool {
+        data.starts_with(QCDT_MAGIC) || data.starts_with(DTBH_MAGIC)
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let format = DtTableFormat::ALL
+            .into_iter()
+            .find(|format| data.starts_with(format.magic()))
+            .ok_or_else(|| io::Error::other("Not a QCDT or DTBH device tree table"))?;
+        let version = read_u32(data, 4)?;
+        if !format.versions().contains(&version) {
+            return Err(io::Error::other(format!(
+                "Unsupported {} version: {}",
+                format, version
+            )));
+        }
+
+        let entry_count = read_u32(data, 8)? as usize;
+        let id_count = format.id_names(version).len();
+        let mut entries = Vec::with_capacity(entry_count.min(1024));
+        for index in 0..entry_count {
+            let offset = 12 + index * format.entry_size(version);
+            let id = (0..id_count)
+                .map(|word| read_u32(data, offset + word * 4))
+                .collect::<io::Result<Vec<u32>>>()?;
+            entries.push(DtTableEntry {
+                id,
+                offset: read_u32(data, offset + id_count * 4)?,
+                size: read_u32(data, offset + id_count * 4 + 4)?,
+            });
+        }
+
+        Ok(DtTable {
+            format,
+            version,
+            entries,
+        })
+    }
+
+    /// DTB of `entry` in the table `data`, without the page padding when it
+    /// is a flattened device tree.
+    pub fn dtb<'a>(&self, data: &'a [u8], entry: &DtTableEntry) -> io::Result<&'a [u8]> {
+        let start = entry.offset as usize;
+        let dtb = start
+            .checked_add(entry.size as usize)
+            .and_then(|end| data.get(start..end))
+            .ok_or_else(|| io::Error::other("DTB entry is out of bounds"))?;
+        Ok(FdtHeader::total_size(dtb).map_or(dtb, |size| &dtb[..size]))
+    }
+
+    /// Page size the table was most likely built with, from where the first
+    /// DTB starts.
+    pub fn page_size(&self) -> u32 {
+        let table_size = self.format.table_size(self.version, self.entries.len()) as u64;
+        let first_offset = self.entries.iter().map(|entry| entry.offset).min();
+        PAGE_SIZES
+            .into_iter()
+            .find(|&page_size| first_offset == Some(align(table_size, page_size) as u32))
+            .unwrap_or(PAGE_SIZES[0])
+    }
+
+    /// Builds a table like `dtbTool`, with one entry per `(id, dtb)` pair in
+    /// the given order. Identical DTBs are only stored once.
+    pub fn build(
+        format: DtTableFormat,
+        version: u32,
+        page_size: u32,
+        entries: &[(Vec<u32>, Vec<u8>)],
+    ) -> io::Result<Vec<u8>> {
+        if !format.versions().contains(&version) {
+            return Err(io::Error::other(format!(
+                "Unsupported {} version: {}",
+                format, version
+            )));
+        }
+        let id_count = format.id_names(version).len();
+        if let Some((id, _)) = entries.iter().find(|(id, _)| id.len() != id_count) {
+            return Err(io::Error::other(format!(
+                "{} v{} entries need {} ids, got {}",
+                format,
+                version,
+                id_count,
+                id.len()
+            )));
+        }
+
+        let table_size = format.table_size(version, entries.len());
+        let mut dtbs: Vec<u8> = Vec::new();
+        let mut stored: Vec<(&[u8], u32)> = Vec::new();
+        let mut table = Vec::with_capacity(table_size);
+        table.extend_from_slice(format.magic());
+        table.extend_from_slice(&version.to_le_bytes());
+        table.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+        let dtbs_offset = align(table_size as u64, page_size) as u32;
+        for (id, dtb) in entries {
+            let space = align(dtb.len() as u64, page_size) as u32;
+            let offset = match stored.iter().find(|(data, _)| *data == dtb.as_slice()) {
+                Some(&(_, offset)) => offset,
+                None => {
+                    let offset = dtbs_offset + dtbs.len() as u32;
+                    dtbs.extend_from_slice(dtb);
+                    dtbs.resize(dtbs.len() + (space as usize - dtb.len()), 0);
+                    stored.push((dtb, offset));
+                    offset
+                }
+            };
+
+            for word in id {
+                table.extend_from_slice(&word.to_le_bytes());
+            }
+            table.extend_from_slice(&offset.to_le_bytes());
+            match format {
+                DtTableFormat::Qcdt => table.extend_from_slice(&space.to_le_bytes()),
+                DtTableFormat::Dtbh => {
+                    table.extend_from_slice(&(dtb.len() as u32).to_le_bytes());
+                    table.extend_from_slice(&space.to_le_bytes());
+                }
+            }
+        }
+        table.extend_from_slice(&0u32.to_le_bytes());
+        table.resize(dtbs_offset as usize, 0);
+        table.extend_from_slice(&dtbs);
+
+        Ok(table)
+    }
+}
+
+/// Description of a table and the DTB files of its entries, stored as
+/// `key=value` lines so an extracted table can be rebuilt:
+///
+/// ```text
+/// format=qcdt
+/// version=3
+/// page_size=2048
+/// # entry=<file> platform_id variant_id subtype_id soc_rev pmic_id0 ...
+/// entry=dtb00.dtb 0x00000046 0x00000008 0x00000000 0x00010000 ...
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtTableConfig {
+    pub format: DtTableFormat,
+    pub version: u32,
+    pub page_size: u32,
+    /// DTB file names, relative to the description, with their ids
+    pub entries: Vec<(String, Vec<u32>)>,
+}
+
+impl DtTableConfig {
+    /// Parses a table description. Blank lines and `#` comments are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut format = DtTableFormat::Qcdt;
+        let mut version = None;
+        let mut page_size = PAGE_SIZES[0];
+        let mut entries = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Invalid table line: {}", line));
+            };
+            let value = value.trim();
+            match key.trim() {
+                "format" => {
+                    format = DtTableFormat::from_name(value)
+                        .ok_or_else(|| format!("Unknown table format: {}", value))?;
+                }
+                "version" => version = Some(parse_u32(value)?),
+                "page_size" => page_size = parse_u32(value)?,
+                "entry" => {
+                    let mut words = value.split_whitespace();
+                    let file = words
+                        .next()
+                        .ok_or_else(|| format!("Missing DTB file: {}", line))?;
+                    let id = words.map(parse_u32).collect::<Result<Vec<u32>, String>>()?;
+                    entries.push((file.to_string(), id));
+                }
+                key => return Err(format!("Unknown table key: {}", key)),
+            }
+        }
+
+        Ok(DtTableConfig {
+            format,
+            version: version.unwrap_or(*format.versions().last().unwrap()),
+            page_size,
+            entries,
+        })
+    }
+}
+
+impl fmt::Display for DtTableConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "format={}", self.format.name())?;
+        writeln!(f, "version={}", self.version)?;
+        writeln!(f, "page_size={}", self.page_size)?;
+        writeln!(
+            f,
+            "# entry=<file> {}",
+            self.format.id_names(self.version).join(" ")
+        )?;
+        for (file, id) in &self.entries {
+            let id: Vec<String> = id.iter().map(|word| format!("0x{:08x}", word)).collect();
+            writeln!(f, "entry={} {}", file, id.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::other("Device tree table is truncated"))
+}
+
+fn parse_u32(value: &str) -> Result<u32, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    }
+    .map_err(|err| format!("Invalid number {}: {}", value, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::dts;
+
+    fn dtb(model: &str) -> Vec<u8> {
+        let source = format!("/dts-v1/;\n/ {{\n\tmodel = \"{}\";\n}};\n", model);
+        dts::compile(&source, false).unwrap().to_bytes().unwrap()
+    }
+
+    #[test]
+    fn build_and_parse_qcdt() {
+        let (a, b) = (dtb("A"), dtb("B"));
+        let entries = vec![
+            (vec![0xce, 8, 0, 0x10000, 1, 2, 3, 4], a.clone()),
+            (vec![0xce, 11, 0, 0x10000, 0, 0, 0, 0], b.clone()),
+            (vec![0xcf, 8, 0, 0x10000, 0, 0, 0, 0], a.clone()),
+        ];
+        let data = DtTable::build(DtTableFormat::Qcdt, 3, 4096, &entries).unwrap();
+        assert!(DtTable::is_dt_table(&data));
+        assert_eq!(data.len(), 3 * 4096);
+
+        let table = DtTable::parse(&data).unwrap();
+        assert_eq!(table.format, DtTableFormat::Qcdt);
+        assert_eq!(table.version, 3);
+        assert_eq!(table.page_size(), 4096);
+        let offsets: Vec<u32> = table.entries.iter().map(|entry| entry.offset).collect();
+        assert_eq!(offsets, [4096, 8192, 4096]);
+        for (entry, (id, dtb)) in table.entries.iter().zip(&entries) {
+            assert_eq!(&entry.id, id);
+            // dtbTool stores the padded size, the DTB itself is cut at its own size
+            assert_eq!(entry.size, 4096);
+            assert_eq!(table.dtb(&data, entry).unwrap(), dtb.as_slice());
+        }
+
+        let rebuilt: Vec<(Vec<u32>, Vec<u8>)> = table
+            .entries
+            .iter()
+            .map(|entry| (entry.id.clone(), table.dtb(&data, entry).unwrap().to_vec()))
+            .collect();
+        assert_eq!(
+            DtTable::build(table.format, table.version, table.page_size(), &rebuilt).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn build_and_parse_dtbh() {
+        let a = dtb("A");
+        let data = DtTable::build(
+            DtTableFormat::Dtbh,
+            2,
+            2048,
+            &[(vec![1, 2, 3, 4, 5], a.clone())],
+        )
+        .unwrap();
+        assert!(data.starts_with(DTBH_MAGIC));
+        // DTBH entries store both the DTB size and the space it takes
+        assert_eq!(read_u32(&data, 12 + 6 * 4).unwrap(), a.len() as u32);
+        assert_eq!(read_u32(&data, 12 + 7 * 4).unwrap(), 2048);
+
+        let table = DtTable::parse(&data).unwrap();
+        assert_eq!(table.format, DtTableFormat::Dtbh);
+        assert_eq!(table.entries[0].id, [1, 2, 3, 4, 5]);
+        assert_eq!(table.entries[0].offset, 2048);
+        assert_eq!(table.dtb(&data, &table.entries[0]).unwrap(), a.as_slice());
+    }
+
+    #[test]
+    fn build_checks_version_and_ids() {
+        let a = dtb("A");
+        assert!(DtTable::build(DtTableFormat::Qcdt, 4, 2048, &[(vec![0; 8], a.clone())]).is_err());
+        assert!(DtTable::build(DtTableFormat::Qcdt, 2, 2048, &[(vec![0; 3], a)]).is_err());
+        assert!(DtTable::parse(b"QCDT\x09\x00\x00\x00\x00\x00\x00\x00").is_err());
+        assert!(DtTable::parse(b"QCDT\x01\x00\x00\x00\x05\x00\x00\x00").is_err());
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let config = DtTableConfig {
+            format: DtTableFormat::Qcdt,
+            version: 2,
+            page_size: 4096,
+            entries: vec![
+                ("dtb00.dtb".to_string(), vec![0xce, 8, 0, 0x10000]),
+                ("dtb01.dtb".to_string(), vec![0xcf, 8, 1, 0x20000]),
+            ],
+        };
+        let text = config.to_string();
+        assert!(text.contains("# entry=<file> platform_id variant_id subtype_id soc_rev\n"));
+        assert_eq!(DtTableConfig::parse(&text).unwrap(), config);
+
+        let config = DtTableConfig::parse("format=dtbh\nentry=a.dtb 1 0x2 3 4 5\n").unwrap();
+        assert_eq!(config.format, DtTableFormat::Dtbh);
+        assert_eq!(config.version, 2);
+        assert_eq!(config.page_size, 2048);
+        assert_eq!(config.entries, [("a.dtb".to_string(), vec![1, 2, 3, 4, 5])]);
+        assert!(DtTableConfig::parse("format=other\n").is_err());
+        assert!(DtTableConfig::parse("entry=a.dtb 0xzz\n").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use super::avb::AvbFooter;
+use super::vboot::VbootSignature;
+
+/// Name of the file `extract` writes the trailers to.
+pub const TRAILER_FILE_NAME: &str = "trailer";
+
+/// Marker Samsung appends to boot images it accepts on locked bootloaders.
+pub const SEANDROIDENFORCE_MAGIC: &[u8; 16] = b"SEANDROIDENFORCE";
+
+/// Magic of the LG bootloader signature check bypass ("bump").
+pub const LG_BUMP_MAGIC: &[u8; 16] = &[
+    0x41, 0xa9, 0xe4, 0x67, 0x74, 0x4d, 0x1d, 0x1b, 0xa4, 0x29, 0xf2, 0xec, 0xea, 0x65, 0x52, 0x79,
+];
+
+/// Kinds of data found after the last section of a boot image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailerKind {
+    SeAndroidEnforce,
+    LgBump,
+    /// VBoot 1.0 signature appended by `boot_signer`
+    VbootSignature,
+    /// AVB footer with its vbmeta struct and partition padding
+    AvbFooter,
+    /// Zeros, such as the rest of a partition dump
+    Padding,
+    Unknown,
+}
+
+impl TrailerKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrailerKind::SeAndroidEnforce => "SEANDROIDENFORCE",
+            TrailerKind::LgBump => "LG Bump",
+            TrailerKind::VbootSignature => "VBoot Signature",
+            TrailerKind::AvbFooter => "AVB Footer",
+            TrailerKind::Padding => "Padding",
+            TrailerKind::Unknown => "Unknown",
+        }
+    }
+
+    /// Fixed contents of marker trailers, which can be appended to any image.
+    pub fn magic(&self) -> Option<&'static [u8]> {
+        match self {
+            TrailerKind::SeAndroidEnforce => Some(SEANDROIDENFORCE_MAGIC),
+            TrailerKind::LgBump => Some(LG_BUMP_MAGIC),
+            _ => None,
+        }
+    }
+
+    /// Whether the trailer signs the image, so it no longer matches once the
+    /// header or a component changes.
+    pub fn is_signature(&self) -> bool {
+        matches!(self, TrailerKind::VbootSignature | TrailerKind::AvbFooter)
+    }
+}
+
+impl fmt::Display for TrailerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Data found after the last page-aligned section of a boot image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    pub kind: TrailerKind,
+    pub data: Vec<u8>,
+}
+
+impl Trailer {
+    /// Builds a marker trailer, or `None` if `kind` has no fixed contents.
+    pub fn marker(kind: TrailerKind) -> Option<Self> {
+        kind.magic().map(|magic| Trailer {
+            kind,
+            data: magic.to_vec(),
+        })
+    }
+
+    /// Splits the data following the last section into trailers.
+    ///
+    /// An AVB footer always comes last and covers everything from the end of
+    /// the image it signs. Before it, markers and VBoot signatures are
+    /// recognized in order; zeros up to the end become padding and anything
+    /// else is kept as a single unknown trailer.
+    pub fn parse_all(data: &[u8], image_size: u64) -> Vec<Self> {
+        let mut trailers = Vec::new();
+
+        let mut end = data.len();
+        let mut avb_footer = None;
+        if let Some(footer) = AvbFooter::parse(data) {
+            end = footer
+                .original_image_size
+                .saturating_sub(image_size)
+                .min(data.len() as u64) as usize;
+            avb_footer = Some(Trailer {
+                kind: TrailerKind::AvbFooter,
+                data: data[end..].to_vec(),
+            });
+        }
+
+        let mut offset = 0;
+        while offset < end {
+            let rest = &data[offset..end];
+            let (kind, size) = if rest.starts_with(SEANDROIDENFORCE_MAGIC) {
+                (TrailerKind::SeAndroidEnforce, SEANDROIDENFORCE_MAGIC.len())
+            } else if rest.starts_with(LG_BUMP_MAGIC) {
+                (TrailerKind::LgBump, LG_BUMP_MAGIC.len())
+            } else if let Some(size) = VbootSignature::parse(rest)
+                .and_then(|signature| signature.to_bytes().ok())
+                .map(|encoded| encoded.len())
+            {
+                (TrailerKind::VbootSignature, size)
+            } else if rest.iter().all(|&byte| byte == 0) {
+                (TrailerKind::Padding, rest.len())
+            } else {
+                (TrailerKind::Unknown, rest.len())
+            };
+
+            trailers.push(Trailer {
+                kind,
+                data: rest[..size].to_vec(),
+            });
+            offset += size;
+        }
+
+        trailers.extend(avb_footer);
+        trailers
+    }
+}
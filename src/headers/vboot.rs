@@ -0,0 +1,179 @@
+use std::io;
+
+use der::asn1::{ObjectIdentifier, OctetString, PrintableString};
+use der::{Decode, DecodePem, Encode, Sequence, SliceReader};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+/// Version of the `AndroidVerifiedBootSignature` struct written by `boot_signer`.
+pub const VBOOT_FORMAT_VERSION: u32 = 1;
+
+pub const SHA1_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.5");
+pub const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+pub const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+
+/// The part of the signature that is signed along with the image.
+#[derive(Debug, Clone, PartialEq, Eq, Sequence)]
+pub struct AuthenticatedAttributes {
+    /// Mount point of the partition, `/boot` or `/recovery`
+    pub target: PrintableString,
+    /// Size of the signed image
+    pub length: u64,
+}
+
+/// VBoot 1.0 signature appended to boot images by `boot_signer` on devices
+/// that predate AVB:
+///
+/// ```text
+/// AndroidVerifiedBootSignature ::= SEQUENCE {
+///     formatVersion ::= INTEGER,
+///     certificate ::= Certificate,
+///     algorithmIdentifier ::= AlgorithmIdentifier,
+///     authenticatedAttributes ::= SEQUENCE { target PrintableString, length INTEGER },
+///     signature ::= OCTET STRING
+/// }
+/// ```
+///
+/// The signature covers the image, page aligned, followed by the DER
+/// encoded authenticated attributes.
+#[derive(Debug, Clone, PartialEq, Eq, Sequence)]
+pub struct VbootSignature {
+    pub format_version: u32,
+    pub certificate: Certificate,
+    pub algorithm_identifier: AlgorithmIdentifierOwned,
+    pub authenticated_attributes: AuthenticatedAttributes,
+    pub signature: OctetString,
+}
+
+impl VbootSignature {
+    /// Signs `image` for `target` like `boot_signer`, with SHA256withRSA.
+    pub fn sign(
+        image: &[u8],
+        target: &str,
+        key: &RsaPrivateKey,
+        certificate: Certificate,
+    ) -> io::Result<Self> {
+        let authenticated_attributes = AuthenticatedAttributes {
+            target: PrintableString::new(target).map_err(io::Error::other)?,
+            length: image.len() as u64,
+        };
+        let attributes = authenticated_attributes
+            .to_der()
+            .map_err(io::Error::other)?;
+        let digest = Sha256::new()
+            .chain_update(image)
+            .chain_update(&attributes)
+            .finalize();
+        let signature = key
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &digest)
+            .map_err(io::Error::other)?;
+
+        Ok(VbootSignature {
+            format_version: VBOOT_FORMAT_VERSION,
+            certificate,
+            algorithm_identifier: AlgorithmIdentifierOwned {
+                oid: SHA256_WITH_RSA,
+                parameters: None,
+            },
+            authenticated_attributes,
+            signature: OctetString::new(signature).map_err(io::Error::other)?,
+        })
+    }
+
+    /// Decodes the signature at the start of `data`, ignoring anything after
+    /// it. Returns `None` if `data` does not start with one.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut reader = SliceReader::new(data).ok()?;
+        Self::decode(&mut reader).ok()
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        self.to_der().map_err(io::Error::other)
+    }
+
+    /// Name of the signature algorithm, like Java names it.
+    pub fn algorithm_name(&self) -> String {
+        match self.algorithm_identifier.oid {
+            SHA1_WITH_RSA => "SHA1withRSA".to_string(),
+            SHA256_WITH_RSA => "SHA256withRSA".to_string(),
+            SHA512_WITH_RSA => "SHA512withRSA".to_string(),
+            oid => oid.to_string(),
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        self.authenticated_attributes.target.as_str()
+    }
+
+    /// Public key of the certificate.
+    pub fn public_key(&self) -> io::Result<RsaPublicKey> {
+        certificate_public_key(&self.certificate)
+    }
+
+    /// Checks the signature of `image`, which must be exactly the signed
+    /// length, with `key`.
+    pub fn verify(&self, image: &[u8], key: &RsaPublicKey) -> io::Result<bool> {
+        let attributes = self
+            .authenticated_attributes
+            .to_der()
+            .map_err(io::Error::other)?;
+        let (scheme, digest) = match self.algorithm_identifier.oid {
+            SHA1_WITH_RSA => (
+                Pkcs1v15Sign::new::<Sha1>(),
+                Sha1::new()
+                    .chain_update(image)
+                    .chain_update(&attributes)
+                    .finalize()
+                    .to_vec(),
+            ),
+            SHA256_WITH_RSA => (
+                Pkcs1v15Sign::new::<Sha256>(),
+                Sha256::new()
+                    .chain_update(image)
+                    .chain_update(&attributes)
+                    .finalize()
+                    .to_vec(),
+            ),
+            SHA512_WITH_RSA => (
+                Pkcs1v15Sign::new::<Sha512>(),
+                Sha512::new()
+                    .chain_update(image)
+                    .chain_update(&attributes)
+                    .finalize()
+                    .to_vec(),
+            ),
+            oid => {
+                return Err(io::Error::other(format!(
+                    "Unsupported signature algorithm: {}",
+                    oid
+                )))
+            }
+        };
+        Ok(key
+            .verify(scheme, &digest, self.signature.as_bytes())
+            .is_ok())
+    }
+}
+
+/// Reads an X.509 certificate in PEM or DER format.
+pub fn read_certificate(data: &[u8]) -> io::Result<Certificate> {
+    match std::str::from_utf8(data) {
+        Ok(pem) if pem.contains("-----BEGIN") => Certificate::from_pem(pem),
+        _ => Certificate::from_der(data),
+    }
+    .map_err(io::Error::other)
+}
+
+/// Extracts the RSA public key of a certificate.
+pub fn certificate_public_key(certificate: &Certificate) -> io::Result<RsaPublicKey> {
+    let spki = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(io::Error::other)?;
+    RsaPublicKey::from_public_key_der(&spki).map_err(io::Error::other)
+}
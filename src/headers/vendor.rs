@@ -0,0 +1,461 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::fields::{
+    AddressU32, AddressU64, Name, VendorBootMagic, VendorCmdline, VendorRamdiskName,
+};
+use super::layout::{align, padding, PAGE_SIZES};
+use super::trailer::{Trailer, TrailerKind};
+use binrw::{BinRead, BinWrite};
+
+pub const VENDOR_HEADER_SIZE_V3: u32 = 2112;
+pub const VENDOR_HEADER_SIZE_V4: u32 = 2128;
+pub const VENDOR_RAMDISK_TABLE_ENTRY_SIZE: u32 = 108;
+
+#[derive(Debug, Default, BinRead, BinWrite)]
+#[br(little)]
+pub struct VendorHeaderVersion3 {
+    pub magic: VendorBootMagic,
+    pub header_version: u32,
+    pub page_size: u32,
+    pub kernel_addr: AddressU32,
+    pub ramdisk_addr: AddressU32,
+    pub vendor_ramdisk_size: u32,
+    pub cmdline: VendorCmdline,
+    pub tags_addr: AddressU32,
+    pub name: Name,
+    pub header_size: u32,
+    pub dtb_size: u32,
+    pub dtb_addr: AddressU64,
+}
+
+#[derive(Debug, Default, BinRead, BinWrite)]
+#[br(little)]
+pub struct VendorHeaderVersion4 {
+    pub magic: VendorBootMagic,
+    pub header_version: u32,
+    pub page_size: u32,
+    pub kernel_addr: AddressU32,
+    pub ramdisk_addr: AddressU32,
+    pub vendor_ramdisk_size: u32,
+    pub cmdline: VendorCmdline,
+    pub tags_addr: AddressU32,
+    pub name: Name,
+    pub header_size: u32,
+    pub dtb_size: u32,
+    pub dtb_addr: AddressU64,
+    pub vendor_ramdisk_table_size: u32,
+    pub vendor_ramdisk_table_entry_num: u32,
+    pub vendor_ramdisk_table_entry_size: u32,
+    pub bootconfig_size: u32,
+}
+
+#[derive(Debug, Default, BinRead, BinWrite, Clone)]
+#[br(little)]
+pub struct VendorRamdiskTableEntry {
+    pub ramdisk_size: u32,
+    pub ramdisk_offset: u32,
+    pub ramdisk_type: u32,
+    pub ramdisk_name: VendorRamdiskName,
+    pub board_id: [u32; 16],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VendorRamdiskType {
+    None,
+    Platform,
+    Recovery,
+    Dlkm,
+    Unknown(u32),
+}
+
+impl From<u32> for VendorRamdiskType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => VendorRamdiskType::None,
+            1 => VendorRamdiskType::Platform,
+            2 => VendorRamdiskType::Recovery,
+            3 => VendorRamdiskType::Dlkm,
+            other => VendorRamdiskType::Unknown(other),
+        }
+    }
+}
+
+impl From<VendorRamdiskType> for u32 {
+    fn from(value: VendorRamdiskType) -> Self {
+        match value {
+            VendorRamdiskType::None => 0,
+            VendorRamdiskType::Platform => 1,
+            VendorRamdiskType::Recovery => 2,
+            VendorRamdiskType::Dlkm => 3,
+            VendorRamdiskType::Unknown(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for VendorRamdiskType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VendorRamdiskType::None => write!(f, "none"),
+            VendorRamdiskType::Platform => write!(f, "platform"),
+            VendorRamdiskType::Recovery => write!(f, "recovery"),
+            VendorRamdiskType::Dlkm => write!(f, "dlkm"),
+            VendorRamdiskType::Unknown(other) => write!(f, "unknown ({})", other),
+        }
+    }
+}
+
+/// A vendor ramdisk fragment together with its table entry (v4 only)
+#[derive(Debug, Clone)]
+pub struct VendorRamdiskFragment {
+    pub entry: VendorRamdiskTableEntry,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct VendorBootFile {
+    pub header: VendorHeader,
+    pub version: u32,
+    pub file: Option<File>,
+    /// Data after the last section, written back after it on save
+    pub trailers: Vec<Trailer>,
+}
+
+#[derive(Debug)]
+pub enum VendorHeader {
+    V3(VendorHeaderVersion3),
+    V4(VendorHeaderVersion4),
+}
+
+impl Default for VendorHeader {
+    fn default() -> Self {
+        VendorHeader::V3(VendorHeaderVersion3::default())
+    }
+}
+
+impl VendorBootFile {
+    fn detect_version(file: &mut File) -> io::Result<u32> {
+        file.seek(SeekFrom::Start(0))?;
+        let header_v3 = VendorHeaderVersion3::read_le(file).map_err(io::Error::other)?;
+
+        if header_v3.magic.0 != VendorBootMagic::default().0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid vendor boot magic: {}", header_v3.magic),
+            ));
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        Ok(header_v3.header_version)
+    }
+
+    /// Opens and parses the image at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut boot_file = VendorBootFile::default();
+        boot_file.load(path)?;
+        Ok(boot_file)
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let version = Self::detect_version(&mut file)?;
+
+        let result = match version {
+            3 => VendorHeaderVersion3::read_le(&mut file)
+                .map(VendorHeader::V3)
+                .map(|header| VendorBootFile {
+                    version: 3,
+                    header,
+                    file: Some(file),
+                    ..Default::default()
+                }),
+            4 => VendorHeaderVersion4::read_le(&mut file)
+                .map(VendorHeader::V4)
+                .map(|header| VendorBootFile {
+                    version: 4,
+                    header,
+                    file: Some(file),
+                    ..Default::default()
+                }),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unsupported vendor boot header version: {}", version),
+                ))
+            }
+        };
+
+        match result {
+            Ok(mut boot_file) => {
+                let page_size = boot_file.page_size();
+                if !PAGE_SIZES.contains(&page_size) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid page size: {}", page_size),
+                    ));
+                }
+                boot_file.trailers = boot_file.read_trailers()?;
+                // Just to be sure we are at the beginning of the file
+                if let Some(ref mut file) = boot_file.file {
+                    file.seek(SeekFrom::Start(0))?;
+                }
+                *self = boot_file;
+                Ok(())
+            }
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&mut self, path: P, page_size: u32) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let result = match &self.header {
+            VendorHeader::V3(header) => header.write_le(&mut file),
+            VendorHeader::V4(header) => header.write_le(&mut file),
+        };
+
+        // The vendor header is bigger than the smallest page sizes, so it
+        // may span more than one page
+        let header_end = file.stream_position()?;
+        let zeros = vec![0u8; padding(header_end, page_size) as usize];
+        file.write_all(&zeros)?; // Write zeroes
+
+        match result {
+            Ok(_) => {
+                self.file = Some(file); // Store the file handle
+                Ok(())
+            }
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+
+    pub fn get_file(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+
+    pub fn page_size(&self) -> u32 {
+        match self.header {
+            VendorHeader::V3(ref header) => header.page_size,
+            VendorHeader::V4(ref header) => header.page_size,
+        }
+    }
+
+    /// Returns the offset and size of every section, in on-disk order:
+    /// vendor ramdisk, dtb, vendor ramdisk table and bootconfig.
+    fn sections(&self) -> [(u64, u32); 4] {
+        let page_size = self.page_size();
+        let (header_size, vendor_ramdisk_size, dtb_size, table_size, bootconfig_size) =
+            match self.header {
+                VendorHeader::V3(ref header) => (
+                    VENDOR_HEADER_SIZE_V3,
+                    header.vendor_ramdisk_size,
+                    header.dtb_size,
+                    0,
+                    0,
+                ),
+                VendorHeader::V4(ref header) => (
+                    VENDOR_HEADER_SIZE_V4,
+                    header.vendor_ramdisk_size,
+                    header.dtb_size,
+                    header.vendor_ramdisk_table_size,
+                    header.bootconfig_size,
+                ),
+            };
+
+        let mut offset = align(header_size as u64, page_size);
+        let mut sections = [(0, 0); 4];
+        for (section, size) in
+            sections
+                .iter_mut()
+                .zip([vendor_ramdisk_size, dtb_size, table_size, bootconfig_size])
+        {
+            *section = (offset, size);
+            offset += align(size as u64, page_size);
+        }
+        sections
+    }
+
+    /// Size of the image up to the end of its last section.
+    fn image_size(&self) -> u64 {
+        let (offset, size) = self.sections()[3];
+        offset + align(size as u64, self.page_size())
+    }
+
+    /// Reads and classifies whatever the loaded file holds after its last section.
+    fn read_trailers(&self) -> io::Result<Vec<Trailer>> {
+        let Some(mut file) = self.file.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let image_size = self.image_size();
+        if file.metadata()?.len() <= image_size {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(image_size))?;
+        file.read_to_end(&mut data)?;
+        Ok(Trailer::parse_all(&data, image_size))
+    }
+
+    /// Kinds of the trailers that sign the image, in file order.
+    pub fn signature_trailers(&self) -> Vec<TrailerKind> {
+        self.trailers
+            .iter()
+            .map(|trailer| trailer.kind)
+            .filter(TrailerKind::is_signature)
+            .collect()
+    }
+
+    /// Writes the sections after the header saved by [`VendorBootFile::save`],
+    /// each padded to the page size, followed by the trailers. The trailers
+    /// that sign the image no longer match it and are dropped.
+    fn write_sections(&mut self, sections: &[&[u8]], page_size: u32) -> io::Result<()> {
+        self.trailers.retain(|trailer| !trailer.kind.is_signature());
+        let mut file = self.get_file();
+        for section in sections {
+            file.write_all(section)?;
+            file.write_all(&vec![
+                0u8;
+                padding(section.len() as u64, page_size) as usize
+            ])?;
+        }
+        for trailer in &self.trailers {
+            file.write_all(&trailer.data)?;
+        }
+        Ok(())
+    }
+
+    fn read_section(&self, (offset, size): (u64, u32)) -> io::Result<Vec<u8>> {
+        let mut file = self.get_file();
+        let mut buf = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn read_vendor_ramdisk(&self) -> io::Result<Vec<u8>> {
+        self.read_section(self.sections()[0])
+    }
+
+    pub fn read_dtb(&self) -> io::Result<Vec<u8>> {
+        self.read_section(self.sections()[1])
+    }
+
+    pub fn read_bootconfig(&self) -> io::Result<Vec<u8>> {
+        self.read_section(self.sections()[3])
+    }
+
+    pub fn read_vendor_ramdisk_table(&self) -> io::Result<Vec<VendorRamdiskTableEntry>> {
+        let (entry_num, entry_size) = match self.header {
+            VendorHeader::V3(_) => return Ok(Vec::new()),
+            VendorHeader::V4(ref header) => (
+                header.vendor_ramdisk_table_entry_num,
+                header.vendor_ramdisk_table_entry_size,
+            ),
+        };
+
+        let mut table = Cursor::new(self.read_section(self.sections()[2])?);
+        let mut entries = Vec::with_capacity(entry_num as usize);
+        for index in 0..entry_num {
+            // Entries may be bigger than the ones we know about
+            table.seek(SeekFrom::Start(index as u64 * entry_size as u64))?;
+            let entry = VendorRamdiskTableEntry::read_le(&mut table).map_err(io::Error::other)?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    pub fn read_vendor_ramdisk_fragments(&self) -> io::Result<Vec<VendorRamdiskFragment>> {
+        let vendor_ramdisk = self.read_vendor_ramdisk()?;
+        self.read_vendor_ramdisk_table()?
+            .into_iter()
+            .map(|entry| {
+                let start = entry.ramdisk_offset as usize;
+                let end = start + entry.ramdisk_size as usize;
+                match vendor_ramdisk.get(start..end) {
+                    Some(data) => Ok(VendorRamdiskFragment {
+                        data: data.to_vec(),
+                        entry,
+                    }),
+                    None => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Vendor ramdisk fragment {} is out of bounds",
+                            entry.ramdisk_name
+                        ),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Rewrites the image at `path` with a new DTB section, keeping every
+    /// other section and the trailers that do not sign the image.
+    pub fn save_with_dtb<P: AsRef<Path>>(&mut self, path: P, dtb: &[u8]) -> io::Result<()> {
+        // Everything else is read before the file gets truncated
+        if self.version >= 4 {
+            let fragments = self.read_vendor_ramdisk_fragments()?;
+            let bootconfig = self.read_bootconfig()?;
+            return self.save_v4(path, &fragments, dtb, &bootconfig);
+        }
+
+        let vendor_ramdisk = self.read_vendor_ramdisk()?;
+        if let VendorHeader::V3(ref mut header) = self.header {
+            header.dtb_size = dtb.len() as u32;
+        }
+        let page_size = self.page_size();
+        self.save(path, page_size)?;
+        self.write_sections(&[&vendor_ramdisk, dtb], page_size)
+    }
+
+    /// Rewrites a v4 vendor boot image at `path` with the given fragments,
+    /// dtb and bootconfig, rebuilding the vendor ramdisk table. Trailers are
+    /// kept, except those that sign the image.
+    pub fn save_v4<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        fragments: &[VendorRamdiskFragment],
+        dtb: &[u8],
+        bootconfig: &[u8],
+    ) -> io::Result<()> {
+        let mut vendor_ramdisk = Vec::new();
+        let mut table = Cursor::new(Vec::new());
+        for fragment in fragments {
+            let mut entry = fragment.entry.clone();
+            entry.ramdisk_offset = vendor_ramdisk.len() as u32;
+            entry.ramdisk_size = fragment.data.len() as u32;
+            entry.write_le(&mut table).map_err(io::Error::other)?;
+            vendor_ramdisk.extend_from_slice(&fragment.data);
+        }
+        let table = table.into_inner();
+
+        let page_size = match self.header {
+            VendorHeader::V4(ref mut header) => {
+                header.vendor_ramdisk_size = vendor_ramdisk.len() as u32;
+                header.dtb_size = dtb.len() as u32;
+                header.vendor_ramdisk_table_size = table.len() as u32;
+                header.vendor_ramdisk_table_entry_num = fragments.len() as u32;
+                header.vendor_ramdisk_table_entry_size = VENDOR_RAMDISK_TABLE_ENTRY_SIZE;
+                header.bootconfig_size = bootconfig.len() as u32;
+                header.page_size
+            }
+            VendorHeader::V3(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Vendor ramdisk table is only supported on vendor boot v4",
+                ))
+            }
+        };
+
+        self.save(path, page_size)?;
+        self.write_sections(&[&vendor_ramdisk, dtb, &table, bootconfig], page_size)
+    }
+}
@@ -0,0 +1,29 @@
+//! Manipulate android boot images like a real blacksmith.
+//!
+//! `abootcrafter` can be used as a library to parse, inspect, build and
+//! write Android boot and vendor boot images. The command line tool is a
+//! thin client of this API.
+//!
+//! ```no_run
+//! use abootcrafter::{AbootCrafterError, AndroidBootFile, BootComponent};
+//!
+//! fn main() -> Result<(), AbootCrafterError> {
+//!     let mut boot_file = AndroidBootFile::open("boot.img")?;
+//!     for &component in boot_file.header.supported_components() {
+//!         let size = boot_file.header.component_size(component).unwrap_or(0);
+//!         println!("{}: {}", component, size);
+//!     }
+//!     let kernel = boot_file.get_component(BootComponent::Kernel)?;
+//!     boot_file.replace_component(BootComponent::Kernel, kernel)?;
+//!     Ok(())
+//! }
+//! ```
+#![forbid(unsafe_code)]
+
+pub mod commands;
+pub mod errors;
+pub mod headers;
+
+pub use errors::AbootCrafterError;
+pub use headers::android::{AndroidBootFile, AndroidHeader, BootComponent};
+pub use headers::vendor::{VendorBootFile, VendorHeader};
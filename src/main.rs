@@ -1,12 +1,14 @@
 #![forbid(unsafe_code)]
 mod cli;
-mod commands;
-mod errors;
-mod headers;
 
+use abootcrafter::commands;
+use abootcrafter::AbootCrafterError;
 use clap::Parser;
-use cli::{Cli, CreateCommand, ExtractCommand, InfoCommand, MainCommand, UpdateCommand};
-use errors::AbootCrafterError;
+use cli::{
+    BootconfigCommand, Cli, CreateCommand, DevicetreeCommand, DtboCommand, ExtractCommand,
+    InfoCommand, KernelCommand, MainCommand, QcdtCommand, RepackCommand, SignatureCommand,
+    UpdateCommand, VendorRamdiskCommand,
+};
 
 fn main() -> Result<(), AbootCrafterError> {
     let cli = Cli::parse();
@@ -14,12 +16,100 @@ fn main() -> Result<(), AbootCrafterError> {
     match cli.command {
         MainCommand::Info { command } => match command {
             InfoCommand::Bootimg { input_boot_file } => commands::info::info(&input_boot_file)?,
+            InfoCommand::VendorBootimg {
+                input_vendor_boot_file,
+            } => commands::info::info_vendor(&input_vendor_boot_file)?,
         },
         MainCommand::Extract { command } => match command {
             ExtractCommand::Bootimg {
                 input_boot_file,
                 output_dir,
-            } => commands::extract::extract(&input_boot_file, output_dir)?,
+                strip_mtk_headers,
+            } => commands::extract::extract(&input_boot_file, output_dir, strip_mtk_headers)?,
+            ExtractCommand::VendorBootimg {
+                input_vendor_boot_file,
+                output_dir,
+            } => commands::extract::extract_vendor(&input_vendor_boot_file, output_dir)?,
+        },
+        MainCommand::Signature { command } => match command {
+            SignatureCommand::Info { input_file } => commands::signature::info(&input_file)?,
+            SignatureCommand::Remove {
+                input_file,
+                output_file,
+            } => commands::signature::remove(&input_file, output_file)?,
+            SignatureCommand::Verify {
+                input_file,
+                key_file,
+            } => commands::signature::verify(&input_file, key_file)?,
+            SignatureCommand::Generate {
+                input_file,
+                key_file,
+                output_file,
+                algorithm,
+                partition_name,
+                partition_size,
+                salt,
+                rollback_index,
+            } => commands::signature::generate(
+                &input_file,
+                output_file,
+                key_file,
+                algorithm.into(),
+                partition_name,
+                partition_size,
+                salt,
+                rollback_index,
+            )?,
+            SignatureCommand::GenerateVbootSignature {
+                input_file,
+                key_file,
+                certificate_file,
+                output_file,
+                target,
+            } => commands::signature::generate_vboot_signature(
+                &input_file,
+                output_file,
+                &key_file,
+                &certificate_file,
+                target,
+            )?,
+            SignatureCommand::ExtractBootSignature {
+                input_file,
+                output_file,
+            } => commands::signature::extract_boot_signature(&input_file, &output_file)?,
+            SignatureCommand::ReplaceBootSignature {
+                input_file,
+                signature_file,
+                output_file,
+            } => commands::signature::replace_boot_signature(
+                &input_file,
+                &signature_file,
+                output_file,
+            )?,
+            SignatureCommand::RemoveBootSignature {
+                input_file,
+                output_file,
+            } => commands::signature::remove_boot_signature(&input_file, output_file)?,
+            SignatureCommand::GenerateBootSignature {
+                input_file,
+                key_file,
+                output_file,
+                algorithm,
+                salt,
+            } => commands::signature::generate_boot_signature(
+                &input_file,
+                output_file,
+                key_file,
+                algorithm.into(),
+                salt,
+            )?,
+        },
+        MainCommand::Repack { command } => match command {
+            RepackCommand::Bootimg {
+                input_dir,
+                output_boot_file,
+                metadata_file,
+            } => commands::repack::repack(&input_dir, &output_boot_file, metadata_file)?,
         },
         MainCommand::Update { command } => match command {
             UpdateCommand::Bootimg {
@@ -31,6 +121,8 @@ fn main() -> Result<(), AbootCrafterError> {
                 dtb_file,
                 cmdline,
                 extra_cmdline,
+                strip_trailers,
+                append_trailers,
             } => commands::update::update(
                 &input_boot_file,
                 kernel_file,
@@ -40,6 +132,8 @@ fn main() -> Result<(), AbootCrafterError> {
                 dtb_file,
                 cmdline,
                 extra_cmdline,
+                strip_trailers,
+                append_trailers.into_iter().map(Into::into).collect(),
             )?,
         },
         MainCommand::Create { command } => match command {
@@ -48,6 +142,7 @@ fn main() -> Result<(), AbootCrafterError> {
                 kernel_file,
                 ramdisk_file,
                 second_file,
+                dt_file,
                 page_size,
                 kernel_addr,
                 ramdisk_addr,
@@ -57,12 +152,16 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash,
                 extra_cmdline,
+                mtk_header,
+                append_trailers,
             } => commands::create::create_v0(
                 output_boot_file,
                 kernel_file,
                 ramdisk_file,
                 second_file,
+                dt_file,
                 page_size as u32,
                 kernel_addr,
                 ramdisk_addr,
@@ -72,7 +171,10 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash.into(),
                 extra_cmdline,
+                append_trailers.into_iter().map(Into::into).collect(),
+                mtk_header.map(Into::into),
             )?,
             CreateCommand::BootimgV1 {
                 output_boot_file,
@@ -89,8 +191,10 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash,
                 extra_cmdline,
-                recovery_dtbo_offset,
+                mtk_header,
+                append_trailers,
             } => commands::create::create_v1(
                 output_boot_file,
                 kernel_file,
@@ -106,8 +210,10 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash.into(),
                 extra_cmdline,
-                recovery_dtbo_offset,
+                append_trailers.into_iter().map(Into::into).collect(),
+                mtk_header.map(Into::into),
             )?,
             CreateCommand::BootimgV2 {
                 output_boot_file,
@@ -125,9 +231,11 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash,
                 extra_cmdline,
-                recovery_dtbo_offset,
                 dtb_addr,
+                mtk_header,
+                append_trailers,
             } => commands::create::create_v2(
                 output_boot_file,
                 kernel_file,
@@ -144,9 +252,11 @@ fn main() -> Result<(), AbootCrafterError> {
                 name,
                 cmdline,
                 id,
+                id_hash.into(),
                 extra_cmdline,
-                recovery_dtbo_offset,
                 dtb_addr,
+                append_trailers.into_iter().map(Into::into).collect(),
+                mtk_header.map(Into::into),
             )?,
             CreateCommand::BootimgV3 {
                 output_boot_file,
@@ -154,26 +264,152 @@ fn main() -> Result<(), AbootCrafterError> {
                 ramdisk_file,
                 os_version,
                 cmdline,
+                append_trailers,
             } => commands::create::create_v3(
                 output_boot_file,
                 kernel_file,
                 ramdisk_file,
                 os_version,
                 cmdline,
+                append_trailers.into_iter().map(Into::into).collect(),
             )?,
             CreateCommand::BootimgV4 {
                 output_boot_file,
                 kernel_file,
                 ramdisk_file,
+                signature_file,
                 os_version,
                 cmdline,
+                append_trailers,
             } => commands::create::create_v4(
                 output_boot_file,
                 kernel_file,
                 ramdisk_file,
+                signature_file,
                 os_version,
                 cmdline,
+                append_trailers.into_iter().map(Into::into).collect(),
+            )?,
+            CreateCommand::VendorBootimgV3 {
+                output_vendor_boot_file,
+                vendor_ramdisk_file,
+                dtb_file,
+                page_size,
+                kernel_addr,
+                ramdisk_addr,
+                tags_addr,
+                dtb_addr,
+                name,
+                cmdline,
+            } => commands::create::create_vendor_v3(
+                output_vendor_boot_file,
+                vendor_ramdisk_file,
+                dtb_file,
+                page_size as u32,
+                kernel_addr,
+                ramdisk_addr,
+                tags_addr,
+                dtb_addr,
+                name,
+                cmdline,
             )?,
+            CreateCommand::VendorBootimgV4 {
+                output_vendor_boot_file,
+                vendor_ramdisk_file,
+                dtb_file,
+                page_size,
+                kernel_addr,
+                ramdisk_addr,
+                tags_addr,
+                dtb_addr,
+                name,
+                cmdline,
+                bootconfig_file,
+            } => commands::create::create_vendor_v4(
+                output_vendor_boot_file,
+                vendor_ramdisk_file,
+                dtb_file,
+                page_size as u32,
+                kernel_addr,
+                ramdisk_addr,
+                tags_addr,
+                dtb_addr,
+                name,
+                cmdline,
+                bootconfig_file,
+            )?,
+        },
+        MainCommand::VendorRamdisk { command } => match command {
+            VendorRamdiskCommand::List {
+                input_vendor_boot_file,
+            } => commands::vendor_ramdisk::list(&input_vendor_boot_file)?,
+            VendorRamdiskCommand::Extract {
+                input_vendor_boot_file,
+                output_dir,
+                index,
+            } => commands::vendor_ramdisk::extract(&input_vendor_boot_file, output_dir, index)?,
+            VendorRamdiskCommand::Add {
+                input_vendor_boot_file,
+                fragment_file,
+                ramdisk_type,
+                name,
+                board_id,
+            } => commands::vendor_ramdisk::add(
+                &input_vendor_boot_file,
+                fragment_file,
+                ramdisk_type as u32,
+                name,
+                board_id,
+            )?,
+            VendorRamdiskCommand::Remove {
+                input_vendor_boot_file,
+                index,
+            } => commands::vendor_ramdisk::remove(&input_vendor_boot_file, index)?,
+            VendorRamdiskCommand::Reorder {
+                input_vendor_boot_file,
+                order,
+            } => commands::vendor_ramdisk::reorder(&input_vendor_boot_file, order)?,
+        },
+        MainCommand::Bootconfig { command } => match command {
+            BootconfigCommand::List {
+                input_vendor_boot_file,
+            } => commands::bootconfig::list(&input_vendor_boot_file)?,
+            BootconfigCommand::Add {
+                input_vendor_boot_file,
+                params,
+            } => commands::bootconfig::add(&input_vendor_boot_file, params)?,
+            BootconfigCommand::Replace {
+                input_vendor_boot_file,
+                params,
+            } => commands::bootconfig::replace(&input_vendor_boot_file, params)?,
+            BootconfigCommand::Remove {
+                input_vendor_boot_file,
+                keys,
+            } => commands::bootconfig::remove(&input_vendor_boot_file, keys)?,
+        },
+        MainCommand::Qcdt { command } => match command {
+            QcdtCommand::List { input_file } => commands::qcdt::list(&input_file)?,
+            QcdtCommand::Extract {
+                input_file,
+                output_dir,
+            } => commands::qcdt::extract(&input_file, output_dir)?,
+            QcdtCommand::Create {
+                input_dir,
+                output_file,
+                config_file,
+            } => commands::qcdt::create(&input_dir, &output_file, config_file)?,
+        },
+        MainCommand::Dtbo { command } => match command {
+            DtboCommand::List { input_file } => commands::dtbo::list(&input_file)?,
+            DtboCommand::Extract {
+                input_file,
+                output_dir,
+            } => commands::dtbo::extract(&input_file, output_dir)?,
+            DtboCommand::Create {
+                input_dir,
+                output_file,
+                config_file,
+            } => commands::dtbo::create(&input_dir, &output_file, config_file)?,
         },
         // MainCommand::Ramdisk { command } => match command {
         //     RamdiskCommand::Info { input_file: _ } => unimplemented!(),
@@ -200,43 +436,95 @@ fn main() -> Result<(), AbootCrafterError> {
         //         target_path: _,
         //     } => unimplemented!(),
         // },
-        // MainCommand::Devicetree { command } => match command {
-        //     DevicetreeCommand::Info { input_file: _ } => unimplemented!(),
-        //     DevicetreeCommand::Remove {
-        //         input_file: _,
-        //         node_path: _,
-        //     } => unimplemented!(),
-        //     DevicetreeCommand::Add {
-        //         input_file: _,
-        //         node_path: _,
-        //         properties: _,
-        //     } => unimplemented!(),
-        //     DevicetreeCommand::Replace {
-        //         input_file: _,
-        //         node_path: _,
-        //         replacement_file: _,
-        //     } => unimplemented!(),
-        // },
-        // MainCommand::Signature { command } => match command {
-        //     SignatureCommand::Info { input_file: _ } => unimplemented!(),
-        //     SignatureCommand::Remove { input_file: _ } => unimplemented!(),
-        //     SignatureCommand::Replace {
-        //         input_file: _,
-        //         signature_file: _,
-        //     } => unimplemented!(),
-        //     SignatureCommand::Generate {
-        //         input_file: _,
-        //         key_file: _,
-        //         output_file: _,
-        //     } => unimplemented!(),
-        // },
-        // MainCommand::Kernel { command } => match command {
-        //     KernelCommand::Info { input_file: _ } => unimplemented!(),
-        //     KernelCommand::ExtractConfig {
-        //         input_file: _,
-        //         output_file: _,
-        //     } => unimplemented!(),
-        // },
+        MainCommand::Devicetree { command } => match command {
+            DevicetreeCommand::Info { input_file } => commands::devicetree::info(&input_file)?,
+            DevicetreeCommand::Decompile {
+                input_file,
+                output_file,
+                index,
+            } => commands::devicetree::decompile(&input_file, output_file, index)?,
+            DevicetreeCommand::Compile {
+                input_file,
+                output_file,
+                symbols,
+            } => commands::devicetree::compile(&input_file, &output_file, symbols)?,
+            DevicetreeCommand::Get {
+                input_file,
+                node_path,
+                property,
+                value_type,
+                index,
+            } => commands::devicetree::get(
+                &input_file,
+                &node_path,
+                &property,
+                value_type.into(),
+                index,
+            )?,
+            DevicetreeCommand::Set {
+                input_file,
+                node_path,
+                property,
+                values,
+                value_type,
+                create,
+                index,
+                output_file,
+            } => commands::devicetree::set(
+                &input_file,
+                &node_path,
+                &property,
+                values,
+                value_type.into(),
+                create,
+                index,
+                output_file,
+            )?,
+            DevicetreeCommand::Remove {
+                input_file,
+                node_path,
+                index,
+                output_file,
+            } => commands::devicetree::remove(&input_file, &node_path, index, output_file)?,
+            DevicetreeCommand::Add {
+                input_file,
+                node_path,
+                properties,
+                index,
+                output_file,
+            } => {
+                commands::devicetree::add(&input_file, &node_path, properties, index, output_file)?
+            }
+            DevicetreeCommand::Replace {
+                input_file,
+                node_path,
+                replacement_file,
+                index,
+                output_file,
+            } => commands::devicetree::replace(
+                &input_file,
+                &node_path,
+                &replacement_file,
+                index,
+                output_file,
+            )?,
+        },
+        MainCommand::Kernel { command } => match command {
+            KernelCommand::Info { input_file } => commands::kernel::info(&input_file)?,
+            KernelCommand::SplitDtb {
+                input_file,
+                output_dir,
+            } => commands::kernel::split_dtb(&input_file, output_dir)?,
+            KernelCommand::JoinDtb {
+                input_file,
+                dtb_files,
+                output_file,
+            } => commands::kernel::join_dtb(&input_file, dtb_files, output_file)?,
+            // KernelCommand::ExtractConfig {
+            //     input_file: _,
+            //     output_file: _,
+            // } => unimplemented!(),
+        },
     }
 
     Ok(())